@@ -0,0 +1,321 @@
+#![allow(dead_code)]
+use crate::clock::{self, Timestamp};
+use crate::report::{Report, ReportFormat, Value};
+use crate::tax;
+use crate::transactions::StoreError;
+
+/// What an account is used for. Postings name the role; the chart of
+/// accounts says which account code and name stand for it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AccountRole {
+    Cash,
+    /// Card takings not yet settled by the card processor.
+    CardClearing,
+    Inventory,
+    AccountsPayable,
+    TaxPayable,
+    /// Balances owed on gift cards and store credit.
+    StoredValue,
+    Revenue,
+    /// Balances written off on expired gift cards and store credit.
+    Breakage,
+    Cogs,
+    /// Value of loyalty points customers paid with.
+    LoyaltyRedemptions,
+    /// Stock counted short or over.
+    InventoryAdjustments,
+}
+
+impl AccountRole {
+    pub const ALL: [AccountRole; 11] = [
+        AccountRole::Cash,
+        AccountRole::CardClearing,
+        AccountRole::Inventory,
+        AccountRole::AccountsPayable,
+        AccountRole::TaxPayable,
+        AccountRole::StoredValue,
+        AccountRole::Revenue,
+        AccountRole::Breakage,
+        AccountRole::Cogs,
+        AccountRole::LoyaltyRedemptions,
+        AccountRole::InventoryAdjustments,
+    ];
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Account {
+    code: String,
+    name: String,
+}
+
+impl Account {
+    pub fn code(&self) -> &str { &self.code }
+    pub fn name(&self) -> &str { &self.name }
+}
+
+/// Account code and name for every role, so postings line up with the
+/// accounts in the bookkeeper's own ledger.
+pub struct ChartOfAccounts {
+    accounts: Vec<(AccountRole, Account)>,
+}
+
+impl Default for ChartOfAccounts {
+    fn default() -> Self {
+        let defaults = [
+            (AccountRole::Cash, "1000", "Cash"),
+            (AccountRole::CardClearing, "1010", "Card clearing"),
+            (AccountRole::Inventory, "1200", "Inventory"),
+            (AccountRole::AccountsPayable, "2000", "Accounts payable"),
+            (AccountRole::TaxPayable, "2100", "Sales tax payable"),
+            (AccountRole::StoredValue, "2200", "Gift cards & store credit"),
+            (AccountRole::Revenue, "4000", "Sales revenue"),
+            (AccountRole::Breakage, "4900", "Gift card breakage"),
+            (AccountRole::Cogs, "5000", "Cost of goods sold"),
+            (AccountRole::LoyaltyRedemptions, "5100", "Loyalty redemptions"),
+            (AccountRole::InventoryAdjustments, "5200", "Inventory adjustments"),
+        ];
+        Self {
+            accounts: defaults
+                .iter()
+                .map(|(role, code, name)| {
+                    (
+                        *role,
+                        Account {
+                            code: code.to_string(),
+                            name: name.to_string(),
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+impl ChartOfAccounts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn account(&self, role: AccountRole) -> &Account {
+        &self.accounts.iter().find(|(r, _)| *r == role).unwrap().1
+    }
+
+    /// Renumbers or renames the account for `role`. Codes must stay unique.
+    pub fn set_account(&mut self, role: AccountRole, code: &str, name: &str) -> Result<(), StoreError> {
+        let code = code.trim();
+        if code.is_empty() || name.trim().is_empty() {
+            return Err(StoreError::InvalidInput("An account needs a code and a name".into()));
+        }
+        if self.accounts.iter().any(|(r, a)| *r != role && a.code == code) {
+            return Err(StoreError::InvalidInput(format!("Account code {code} is already in use")));
+        }
+        let account = &mut self.accounts.iter_mut().find(|(r, _)| *r == role).unwrap().1;
+        account.code = code.to_string();
+        account.name = name.trim().to_string();
+        Ok(())
+    }
+}
+
+/// What kind of record an entry was posted for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EntryKind {
+    Sale,
+    Return,
+    Purchase,
+    LandedCharge,
+    SupplierReturn,
+    StockCount,
+    /// Expired card balances written off.
+    Breakage,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct JournalLine {
+    role: AccountRole,
+    debit: f64,
+    credit: f64,
+}
+
+impl JournalLine {
+    pub fn debit(role: AccountRole, amount: f64) -> Self {
+        Self {
+            role,
+            debit: amount,
+            credit: 0.0,
+        }
+    }
+
+    pub fn credit(role: AccountRole, amount: f64) -> Self {
+        Self {
+            role,
+            debit: 0.0,
+            credit: amount,
+        }
+    }
+
+    pub fn role(&self) -> AccountRole { self.role }
+    pub fn debit_amount(&self) -> f64 { self.debit }
+    pub fn credit_amount(&self) -> f64 { self.credit }
+}
+
+/// One balanced posting: its debits always equal its credits.
+#[derive(Debug, Clone)]
+pub struct JournalEntry {
+    id: u32,
+    kind: EntryKind,
+    posted_at: Timestamp,
+    /// The record that caused it, e.g. "Order #3" or "Purchase #1".
+    reference: String,
+    lines: Vec<JournalLine>,
+}
+
+impl JournalEntry {
+    /// Rounds every line to cents, drops empty ones and turns negative
+    /// amounts to the other side. Fails unless debits equal credits.
+    pub fn new(
+        kind: EntryKind,
+        reference: &str,
+        posted_at: Timestamp,
+        lines: Vec<JournalLine>,
+    ) -> Result<Self, StoreError> {
+        let mut balanced = Vec::new();
+        for l in lines {
+            let amount = tax::round_cents(l.debit - l.credit);
+            if amount > 0.0 {
+                balanced.push(JournalLine::debit(l.role, amount));
+            } else if amount < 0.0 {
+                balanced.push(JournalLine::credit(l.role, -amount));
+            }
+        }
+        let debits: f64 = balanced.iter().map(|l| l.debit).sum();
+        let credits: f64 = balanced.iter().map(|l| l.credit).sum();
+        if tax::round_cents(debits - credits) != 0.0 {
+            return Err(StoreError::InvalidInput(format!(
+                "{reference} does not balance: debits {debits:.2}, credits {credits:.2}"
+            )));
+        }
+        Ok(Self {
+            id: 0,
+            kind,
+            posted_at,
+            reference: reference.to_string(),
+            lines: balanced,
+        })
+    }
+
+    pub fn id(&self) -> u32 { self.id }
+    pub fn kind(&self) -> EntryKind { self.kind }
+    pub fn posted_at(&self) -> Timestamp { self.posted_at }
+    pub fn reference(&self) -> &str { &self.reference }
+    pub fn lines(&self) -> &[JournalLine] { &self.lines }
+    pub fn total(&self) -> f64 { self.lines.iter().map(|l| l.debit).sum() }
+}
+
+/// Every posting made by `Transactions`, oldest first.
+pub struct Journal {
+    entries: Vec<JournalEntry>,
+}
+
+impl Default for Journal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Journal {
+    pub fn new() -> Self {
+        Self { entries: Vec::new() }
+    }
+
+    pub(crate) fn post(&mut self, mut entry: JournalEntry) -> &JournalEntry {
+        entry.id = self.entries.len() as u32 + 1;
+        self.entries.push(entry);
+        self.entries.last().unwrap()
+    }
+
+    pub fn entries(&self) -> &[JournalEntry] {
+        &self.entries
+    }
+
+    pub fn find(&self, id: u32) -> Option<&JournalEntry> {
+        self.entries.iter().find(|e| e.id == id)
+    }
+
+    /// Debits less credits on the account for `role`.
+    pub fn balance(&self, role: AccountRole) -> f64 {
+        self.balance_at(role, Timestamp::MAX)
+    }
+
+    /// Debits less credits on the account for `role` from entries posted
+    /// before `at`.
+    pub fn balance_at(&self, role: AccountRole, at: Timestamp) -> f64 {
+        let sum: f64 = self
+            .entries
+            .iter()
+            .filter(|e| e.posted_at < at)
+            .flat_map(|e| &e.lines)
+            .filter(|l| l.role == role)
+            .map(|l| l.debit - l.credit)
+            .sum();
+        tax::round_cents(sum)
+    }
+
+    /// Debit or credit balance of every account that has postings, in chart order.
+    pub fn trial_balance(&self) -> Vec<(AccountRole, f64, f64)> {
+        AccountRole::ALL
+            .iter()
+            .filter(|r| self.entries.iter().flat_map(|e| &e.lines).any(|l| l.role == **r))
+            .map(|r| {
+                let balance = self.balance(*r);
+                if balance >= 0.0 { (*r, balance, 0.0) } else { (*r, 0.0, -balance) }
+            })
+            .collect()
+    }
+}
+
+pub fn journal_report(journal: &Journal, chart: &ChartOfAccounts) -> Report {
+    let mut report = Report::new("Journal", &["Entry", "Posted", "Account", "Debit", "Credit"]);
+    for e in journal.entries() {
+        report.add_row(vec![Value::Integer(e.id as i64), Value::Text(clock::format_date(e.posted_at)), Value::text(&e.reference)]);
+        for l in &e.lines {
+            let account = chart.account(l.role);
+            let amount = |a: f64| if a > 0.0 { Value::Money(a) } else { Value::Empty };
+            report.add_row(vec![
+                Value::Empty,
+                Value::Empty,
+                Value::Text(format!("  {} {}", account.code, account.name)),
+                amount(l.debit),
+                amount(l.credit),
+            ]);
+        }
+    }
+    report
+}
+
+pub fn report_journal(journal: &Journal, chart: &ChartOfAccounts) -> String {
+    journal_report(journal, chart).render(ReportFormat::Text)
+}
+
+pub fn trial_balance_report(journal: &Journal, chart: &ChartOfAccounts) -> Report {
+    let mut report = Report::new("Trial balance", &["Code", "Account", "Debit", "Credit"]);
+    let (mut debits, mut credits) = (0.0, 0.0);
+    for (role, debit, credit) in journal.trial_balance() {
+        let account = chart.account(role);
+        debits += debit;
+        credits += credit;
+        report.add_row(vec![
+            Value::text(&account.code),
+            Value::text(&account.name),
+            Value::Money(debit),
+            Value::Money(credit),
+        ]);
+    }
+    report.add_row(vec![Value::Empty, Value::text("Total"), Value::Money(debits), Value::Money(credits)]);
+    let status = if tax::round_cents(debits - credits) == 0.0 { "Balanced" } else { "OUT OF BALANCE" };
+    report.add_total("Status", Value::text(status));
+    report
+}
+
+pub fn report_trial_balance(journal: &Journal, chart: &ChartOfAccounts) -> String {
+    trial_balance_report(journal, chart).render(ReportFormat::Text)
+}
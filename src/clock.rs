@@ -0,0 +1,71 @@
+#![allow(dead_code)]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Seconds since the Unix epoch.
+pub type Timestamp = u64;
+
+pub fn now() -> Timestamp {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+pub const DAY: Timestamp = 86_400;
+
+/// Calendar date (year, month, day) of `at`, in UTC.
+pub fn date(at: Timestamp) -> (i64, u32, u32) {
+    // days-to-civil, counting in 400-year eras that start on 1 March
+    let z = (at / DAY) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Start of the given calendar day, in UTC. `None` before 1970.
+pub fn from_date(year: i64, month: u32, day: u32) -> Option<Timestamp> {
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let mp = if month > 2 { month - 3 } else { month + 9 } as i64;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+    let at = u64::try_from(days).ok()? * DAY;
+    (date(at) == (year, month, day)).then_some(at)
+}
+
+/// `at` as YYYY-MM-DD.
+pub fn format_date(at: Timestamp) -> String {
+    let (y, m, d) = date(at);
+    format!("{y:04}-{m:02}-{d:02}")
+}
+
+/// Parses YYYY-MM-DD to the start of that day.
+pub fn parse_date(text: &str) -> Option<Timestamp> {
+    let mut parts = text.trim().splitn(3, '-');
+    let year = parts.next()?.parse().ok()?;
+    let month = parts.next()?.parse().ok()?;
+    let day = parts.next()?.parse().ok()?;
+    from_date(year, month, day)
+}
+
+/// Day of the week of `at`, 0 for Monday through 6 for Sunday.
+pub fn weekday(at: Timestamp) -> u32 {
+    // 1 January 1970 was a Thursday
+    ((at / DAY + 3) % 7) as u32
+}
+
+/// Hour of the day of `at`, 0 to 23.
+pub fn hour(at: Timestamp) -> u32 {
+    (at % DAY / 3600) as u32
+}
@@ -0,0 +1,144 @@
+#![allow(dead_code)]
+use crate::currency::Money;
+use crate::report::{Report, ReportFormat, Value};
+use crate::tax;
+use crate::transactions::Transactions;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChargeKind {
+    Freight,
+    Duty,
+    Handling,
+    Other,
+}
+
+/// How stock on hand and goods sold are costed.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum CostingMethod {
+    /// Every unit at the average landed cost of all purchases.
+    #[default]
+    Average,
+    /// First in, first out: sales use up the oldest units bought, so the
+    /// stock left is costed at the latest purchases.
+    Fifo,
+}
+
+/// How a charge is shared between the purchase lines it covers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AllocationBasis {
+    /// In proportion to each line's cost.
+    Value,
+    /// In proportion to the units on each line.
+    Quantity,
+    /// In proportion to each line's total weight (units x product weight).
+    Weight,
+}
+
+/// A cost of getting stock into the store on top of the supplier's price,
+/// spread over one or more purchases.
+pub struct LandedCharge {
+    id: u32,
+    kind: ChargeKind,
+    /// The charge as billed, possibly in a foreign currency.
+    billed: Money,
+    /// The charge in the base currency.
+    amount: f64,
+    basis: AllocationBasis,
+    /// Purchase id and the part of `amount` it carries.
+    allocations: Vec<(u32, f64)>,
+}
+
+impl LandedCharge {
+    pub(crate) fn new(
+        id: u32,
+        kind: ChargeKind,
+        billed: Money,
+        amount: f64,
+        basis: AllocationBasis,
+        allocations: Vec<(u32, f64)>,
+    ) -> Self {
+        Self {
+            id,
+            kind,
+            billed,
+            amount,
+            basis,
+            allocations,
+        }
+    }
+
+    pub fn id(&self) -> u32 { self.id }
+    pub fn kind(&self) -> ChargeKind { self.kind }
+    pub fn billed(&self) -> &Money { &self.billed }
+    pub fn amount(&self) -> f64 { self.amount }
+    pub fn basis(&self) -> AllocationBasis { self.basis }
+    pub fn allocations(&self) -> &[(u32, f64)] { &self.allocations }
+}
+
+/// Splits `amount` pro rata over `weights` in whole cents. The last share
+/// takes the rounding so the shares always add up to `amount`.
+pub(crate) fn split(amount: f64, weights: &[f64]) -> Vec<f64> {
+    let total: f64 = weights.iter().sum();
+    let mut shares = Vec::with_capacity(weights.len());
+    let mut left = tax::round_cents(amount);
+    for (i, w) in weights.iter().enumerate() {
+        let share = if i + 1 == weights.len() {
+            left
+        } else {
+            tax::round_cents(amount * w / total)
+        };
+        left = tax::round_cents(left - share);
+        shares.push(share);
+    }
+    shares
+}
+
+/// Revenue, cost of goods sold and gross margin per product, net of
+/// returns. Cost is what each sale posted to cost of goods sold, less what
+/// returns took back; products sold without a known cost are left out of
+/// the margin totals.
+pub fn margins_report(tx: &Transactions) -> Report {
+    let mut rows: Vec<(String, i64, f64, Option<f64>)> = Vec::new();
+    for s in tx.get_sales() {
+        match rows.iter_mut().find(|(p, _, _, _)| p == s.product_name()) {
+            Some((_, qty, revenue, cost)) => {
+                *qty += s.quantity() as i64;
+                *revenue += s.net_amount();
+                *cost = cost.zip(s.cost()).map(|(a, b)| a + b);
+            }
+            None => rows.push((s.product_name().to_string(), s.quantity() as i64, s.net_amount(), s.cost())),
+        }
+    }
+    for r in tx.get_returns() {
+        if let Some((_, qty, revenue, cost)) = rows.iter_mut().find(|(p, _, _, _)| p == r.product_name()) {
+            *qty -= r.quantity() as i64;
+            *revenue -= r.refund() - r.tax();
+            *cost = cost.map(|c| c - r.cost());
+        }
+    }
+
+    let mut report = Report::new("Margins", &["Product", "Qty", "Revenue", "COGS", "Margin", "Margin %"]);
+    let (mut total_revenue, mut total_cogs) = (0.0, 0.0);
+    for (product, qty, revenue, cost) in rows {
+        let mut row = vec![Value::Text(product), Value::Integer(qty), Value::Money(revenue)];
+        if let Some(cogs) = cost {
+            let cogs = tax::round_cents(cogs);
+            let margin = revenue - cogs;
+            total_revenue += revenue;
+            total_cogs += cogs;
+            row.push(Value::Money(cogs));
+            row.push(Value::Money(margin));
+            row.push(if revenue != 0.0 { Value::Percent(margin / revenue * 100.0) } else { Value::Empty });
+        }
+        report.add_row(row);
+    }
+    report.add_total("Revenue with a known cost", Value::Money(total_revenue));
+    report.add_total("Cost of goods sold", Value::Money(total_cogs));
+    report.add_total("Gross margin", Value::Money(total_revenue - total_cogs));
+    report
+}
+
+/// The margins as text.
+pub fn report_margins(tx: &Transactions) -> String {
+    margins_report(tx).render(ReportFormat::Text)
+}
@@ -0,0 +1,148 @@
+#![allow(dead_code)]
+use std::fmt;
+
+use crate::clock::{self, Timestamp};
+use crate::report::{Report, ReportFormat, Value};
+use crate::transactions::StoreError;
+
+/// An amount together with the currency it is in, e.g. 12.50 EUR.
+///
+/// Only amounts a supplier can bill in their own currency carry one: the
+/// supplier prices of purchases, supplier returns, landed charges and
+/// purchase order and invoice lines. Shelf prices, sales, tenders, stock
+/// values and the journal are plain `f64` amounts in the base currency.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Money {
+    amount: f64,
+    currency: String,
+}
+
+impl Money {
+    /// `currency` is a code such as "EUR"; it is stored upper-case.
+    pub fn new(amount: f64, currency: &str) -> Self {
+        Self {
+            amount,
+            currency: currency.trim().to_uppercase(),
+        }
+    }
+
+    pub fn amount(&self) -> f64 { self.amount }
+    pub fn currency(&self) -> &str { &self.currency }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.2} {}", self.amount, self.currency)
+    }
+}
+
+/// Value of one unit of `currency` in the base currency, from `effective_from` on.
+#[derive(Debug, Clone)]
+pub struct ExchangeRate {
+    currency: String,
+    rate: f64,
+    effective_from: Timestamp,
+}
+
+impl ExchangeRate {
+    pub fn currency(&self) -> &str { &self.currency }
+    pub fn rate(&self) -> f64 { self.rate }
+    pub fn effective_from(&self) -> Timestamp { self.effective_from }
+}
+
+/// The store's base currency and dated rates for every other currency it
+/// deals in. All stock values and totals are kept in the base currency.
+pub struct ExchangeRates {
+    base: String,
+    rates: Vec<ExchangeRate>,
+}
+
+impl Default for ExchangeRates {
+    fn default() -> Self {
+        Self::new("USD")
+    }
+}
+
+impl ExchangeRates {
+    pub fn new(base: &str) -> Self {
+        Self {
+            base: base.trim().to_uppercase(),
+            rates: Vec::new(),
+        }
+    }
+
+    pub fn base(&self) -> &str {
+        &self.base
+    }
+
+    /// Records that one unit of `currency` is worth `rate` in the base
+    /// currency from `effective_from` on. A rate for the same moment is replaced.
+    pub fn set_rate(&mut self, currency: &str, rate: f64, effective_from: Timestamp) -> Result<(), StoreError> {
+        let currency = currency.trim().to_uppercase();
+        if currency.is_empty() || currency == self.base {
+            return Err(StoreError::InvalidInput(format!("No rate needed for '{currency}'")));
+        }
+        if rate <= 0.0 {
+            return Err(StoreError::InvalidInput("Exchange rate must be > 0".into()));
+        }
+        self.rates.retain(|r| r.currency != currency || r.effective_from != effective_from);
+        let pos = self.rates.partition_point(|r| r.effective_from <= effective_from);
+        self.rates.insert(
+            pos,
+            ExchangeRate {
+                currency,
+                rate,
+                effective_from,
+            },
+        );
+        Ok(())
+    }
+
+    /// Rate in effect for `currency` at `at`; always 1 for the base currency.
+    pub fn rate_at(&self, currency: &str, at: Timestamp) -> Option<f64> {
+        let currency = currency.trim().to_uppercase();
+        if currency == self.base {
+            return Some(1.0);
+        }
+        self.rates
+            .iter()
+            .rev()
+            .find(|r| r.currency == currency && r.effective_from <= at)
+            .map(|r| r.rate)
+    }
+
+    /// Converts `money` to the base currency at the rate in effect at `at`.
+    pub fn to_base(&self, money: &Money, at: Timestamp) -> Result<Money, StoreError> {
+        let rate = self
+            .rate_at(money.currency(), at)
+            .ok_or_else(|| StoreError::NotFound(format!("exchange rate for {} at {at}", money.currency())))?;
+        Ok(Money::new(money.amount() * rate, &self.base))
+    }
+
+    /// Every rate recorded for `currency`, oldest first.
+    pub fn history(&self, currency: &str) -> Vec<&ExchangeRate> {
+        let currency = currency.trim().to_uppercase();
+        self.rates.iter().filter(|r| r.currency == currency).collect()
+    }
+
+    pub fn all(&self) -> &[ExchangeRate] {
+        &self.rates
+    }
+}
+
+pub fn rates_report(rates: &ExchangeRates) -> Report {
+    let title = format!("Exchange rates (base {})", rates.base());
+    let mut report = Report::new(&title, &["Currency", "Rate", "Effective from"]);
+    for r in rates.all() {
+        report.add_row(vec![
+            Value::text(&r.currency),
+            Value::Text(format!("{:.4}", r.rate)),
+            Value::Text(clock::format_date(r.effective_from)),
+        ]);
+    }
+    report
+}
+
+pub fn report_rates(rates: &ExchangeRates) -> String {
+    rates_report(rates).render(ReportFormat::Text)
+}
@@ -0,0 +1,131 @@
+#![allow(dead_code)]
+use crate::report::{Report, ReportFormat, Value};
+use crate::transactions::{StoreError, Transactions};
+
+/// Someone we sell to: either a client user of the app, or a walk-in
+/// customer identified by phone and/or email.
+pub struct Customer {
+    id: u32,
+    name: String,
+    username: Option<String>,
+    phone: Option<String>,
+    email: Option<String>,
+}
+
+impl Customer {
+    pub fn id(&self) -> u32 { self.id }
+    pub fn name(&self) -> &str { &self.name }
+    pub fn username(&self) -> Option<&str> { self.username.as_deref() }
+    pub fn phone(&self) -> Option<&str> { self.phone.as_deref() }
+    pub fn email(&self) -> Option<&str> { self.email.as_deref() }
+}
+
+pub struct Customers {
+    customers: Vec<Customer>,
+    next_id: u32,
+}
+
+impl Default for Customers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Customers {
+    pub fn new() -> Self {
+        Self {
+            customers: Vec::new(),
+            next_id: 1,
+        }
+    }
+
+    /// Returns the customer linked to a client user, creating it on first use.
+    pub fn for_user(&mut self, username: &str) -> &Customer {
+        if let Some(pos) = self.customers.iter().position(|c| c.username.as_deref() == Some(username)) {
+            return &self.customers[pos];
+        }
+        self.push(username, Some(username), None, None)
+    }
+
+    pub fn add_walk_in(
+        &mut self,
+        name: &str,
+        phone: Option<&str>,
+        email: Option<&str>,
+    ) -> Result<&Customer, StoreError> {
+        let phone = phone.filter(|p| !p.is_empty());
+        let email = email.filter(|e| !e.is_empty());
+        if phone.is_none() && email.is_none() {
+            return Err(StoreError::InvalidInput("A walk-in customer needs a phone or email".into()));
+        }
+        if let Some(contact) = phone.or(email)
+            && self.find_by_contact(contact).is_some()
+        {
+            return Err(StoreError::InvalidInput(format!("{contact} is already registered")));
+        }
+        Ok(self.push(name, None, phone, email))
+    }
+
+    fn push(&mut self, name: &str, username: Option<&str>, phone: Option<&str>, email: Option<&str>) -> &Customer {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.customers.push(Customer {
+            id,
+            name: name.to_string(),
+            username: username.map(str::to_string),
+            phone: phone.map(str::to_string),
+            email: email.map(str::to_string),
+        });
+        self.customers.last().unwrap()
+    }
+
+    pub fn find(&self, id: u32) -> Option<&Customer> {
+        self.customers.iter().find(|c| c.id == id)
+    }
+
+    pub fn find_by_user(&self, username: &str) -> Option<&Customer> {
+        self.customers.iter().find(|c| c.username.as_deref() == Some(username))
+    }
+
+    /// Looks a customer up by phone number or email address.
+    pub fn find_by_contact(&self, contact: &str) -> Option<&Customer> {
+        self.customers
+            .iter()
+            .find(|c| c.phone.as_deref() == Some(contact) || c.email.as_deref() == Some(contact))
+    }
+
+    /// Resolves free text typed at a prompt: a customer id, username, phone or email.
+    pub fn lookup(&self, query: &str) -> Option<&Customer> {
+        query
+            .parse()
+            .ok()
+            .and_then(|id| self.find(id))
+            .or_else(|| self.find_by_user(query))
+            .or_else(|| self.find_by_contact(query))
+    }
+
+    pub fn all(&self) -> &[Customer] {
+        &self.customers
+    }
+}
+
+pub fn customer_history_report(tx: &Transactions, customer: &Customer) -> Report {
+    let title = format!("Customer #{}: {}", customer.id, customer.name);
+    let mut report = Report::new(&title, &["No", "Product", "Qty", "Unit", "Total"]);
+    let sales = tx.sales_for(customer.id);
+    for (i, s) in sales.iter().enumerate() {
+        report.add_row(vec![
+            Value::Integer(i as i64 + 1),
+            Value::text(s.product_name()),
+            Value::Integer(s.quantity() as i64),
+            Value::Money(s.unit_price()),
+            Value::Money(s.total_price()),
+        ]);
+    }
+    report.add_total("Total spent", Value::Money(sales.iter().map(|s| s.total_price()).sum()));
+    report
+}
+
+pub fn report_customer_history(tx: &Transactions, customer: &Customer) -> String {
+    customer_history_report(tx, customer).render(ReportFormat::Text)
+}
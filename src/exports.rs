@@ -0,0 +1,242 @@
+#![allow(dead_code)]
+use std::ops::Range;
+
+use crate::accounting::{AccountRole, ChartOfAccounts, EntryKind, Journal};
+use crate::clock::{self, Timestamp};
+use crate::escape::{csv_field, escape_html};
+use crate::tax;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    /// One row per journal line: every account, debit and credit.
+    CsvJournal,
+    /// Quicken interchange; one account's register with splits.
+    Qif,
+    /// Open Financial Exchange statement for one account.
+    Ofx,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::CsvJournal => "csv",
+            ExportFormat::Qif => "qif",
+            ExportFormat::Ofx => "ofx",
+        }
+    }
+}
+
+/// One transaction as handed to the accounting package. The id only
+/// depends on what it was made from, so exporting the same period again
+/// gives the same ids and importers can skip what they already have.
+#[derive(Debug, Clone)]
+pub struct ExportTransaction {
+    id: String,
+    date: Timestamp,
+    memo: String,
+    /// Account, debit and credit.
+    lines: Vec<(AccountRole, f64, f64)>,
+}
+
+impl ExportTransaction {
+    pub fn id(&self) -> &str { &self.id }
+    pub fn date(&self) -> Timestamp { self.date }
+    pub fn memo(&self) -> &str { &self.memo }
+    pub fn lines(&self) -> &[(AccountRole, f64, f64)] { &self.lines }
+
+    /// Debits less credits on `role` in this transaction.
+    pub fn amount_on(&self, role: AccountRole) -> f64 {
+        let sum: f64 = self.lines.iter().filter(|(r, _, _)| *r == role).map(|(_, d, c)| d - c).sum();
+        tax::round_cents(sum)
+    }
+}
+
+/// Journal entries posted from `from` up to but not including `to`.
+pub fn journal_transactions(journal: &Journal, from: Timestamp, to: Timestamp) -> Vec<ExportTransaction> {
+    journal
+        .entries()
+        .iter()
+        .filter(|e| e.posted_at() >= from && e.posted_at() < to)
+        .map(|e| ExportTransaction {
+            id: format!("JE-{:06}", e.id()),
+            date: e.posted_at(),
+            memo: e.reference().to_string(),
+            lines: e.lines().iter().map(|l| (l.role(), l.debit_amount(), l.credit_amount())).collect(),
+        })
+        .collect()
+}
+
+/// One sales and one purchases transaction per day in the range, each
+/// netting that day's entries per account. Sales cover sales and customer
+/// returns; purchases cover purchases, landed charges and supplier returns.
+/// Only days wholly inside the range are summarised, so an id always stands
+/// for the same whole day however the range was drawn.
+pub fn daily_summaries(journal: &Journal, from: Timestamp, to: Timestamp) -> Vec<ExportTransaction> {
+    let from = from.div_ceil(clock::DAY) * clock::DAY;
+    let to = to / clock::DAY * clock::DAY;
+    let groups: [(&str, &str, &[EntryKind]); 2] = [
+        ("SALES", "Sales", &[EntryKind::Sale, EntryKind::Return]),
+        (
+            "PURCH",
+            "Purchases",
+            &[EntryKind::Purchase, EntryKind::LandedCharge, EntryKind::SupplierReturn],
+        ),
+    ];
+    let mut days: Vec<Timestamp> = journal
+        .entries()
+        .iter()
+        .filter(|e| e.posted_at() >= from && e.posted_at() < to)
+        .map(|e| e.posted_at() / clock::DAY * clock::DAY)
+        .collect();
+    days.sort();
+    days.dedup();
+
+    let mut out = Vec::new();
+    for day in days {
+        for (prefix, label, kinds) in &groups {
+            let mut totals: Vec<(AccountRole, f64)> = Vec::new();
+            let entries = journal
+                .entries()
+                .iter()
+                .filter(|e| e.posted_at() >= day && e.posted_at() < day + clock::DAY)
+                .filter(|e| kinds.contains(&e.kind()));
+            for l in entries.flat_map(|e| e.lines()) {
+                match totals.iter_mut().find(|(r, _)| *r == l.role()) {
+                    Some((_, t)) => *t += l.debit_amount() - l.credit_amount(),
+                    None => totals.push((l.role(), l.debit_amount() - l.credit_amount())),
+                }
+            }
+            let lines: Vec<(AccountRole, f64, f64)> = AccountRole::ALL
+                .iter()
+                .filter_map(|r| totals.iter().find(|(t, _)| t == r))
+                .map(|(r, t)| (*r, tax::round_cents(*t)))
+                .filter(|(_, t)| *t != 0.0)
+                .map(|(r, t)| if t > 0.0 { (r, t, 0.0) } else { (r, 0.0, -t) })
+                .collect();
+            if lines.is_empty() {
+                continue;
+            }
+            let date = clock::format_date(day);
+            out.push(ExportTransaction {
+                id: format!("{prefix}-{}", date.replace('-', "")),
+                date: day,
+                memo: format!("{label} {date}"),
+                lines,
+            });
+        }
+    }
+    out
+}
+
+/// Writes `transactions` in `format`. QIF and OFX describe the register of
+/// one account, `register`, and leave out transactions that do not touch it.
+/// OFX covers `period`, the range the transactions were taken from, and
+/// states `closing`, the register's balance at its end, as the ledger balance.
+pub fn render_export(
+    transactions: &[ExportTransaction],
+    chart: &ChartOfAccounts,
+    register: AccountRole,
+    period: Range<Timestamp>,
+    closing: f64,
+    currency: &str,
+    format: ExportFormat,
+) -> String {
+    match format {
+        ExportFormat::CsvJournal => csv_journal(transactions, chart),
+        ExportFormat::Qif => qif(transactions, chart, register),
+        ExportFormat::Ofx => ofx(transactions, chart, register, period, closing, currency),
+    }
+}
+
+fn csv_journal(transactions: &[ExportTransaction], chart: &ChartOfAccounts) -> String {
+    let mut out = String::from("transaction_id,date,memo,account_code,account_name,debit,credit\n");
+    for t in transactions {
+        for (role, debit, credit) in &t.lines {
+            let account = chart.account(*role);
+            out.push_str(&format!(
+                "{},{},{},{},{},{:.2},{:.2}\n",
+                t.id,
+                clock::format_date(t.date),
+                csv_field(&t.memo),
+                csv_field(account.code()),
+                csv_field(account.name()),
+                debit,
+                credit
+            ));
+        }
+    }
+    out
+}
+
+fn qif(transactions: &[ExportTransaction], chart: &ChartOfAccounts, register: AccountRole) -> String {
+    let mut out = String::from("!Type:Bank\n");
+    for t in transactions.iter().filter(|t| t.lines.iter().any(|(r, _, _)| *r == register)) {
+        let (y, m, d) = clock::date(t.date);
+        out.push_str(&format!("D{m:02}/{d:02}/{y:04}\n"));
+        out.push_str(&format!("T{:.2}\n", t.amount_on(register)));
+        out.push_str(&format!("N{}\n", t.id));
+        out.push_str(&format!("P{}\n", t.memo));
+        // the other side of the entry, as splits
+        for (role, debit, credit) in t.lines.iter().filter(|(r, _, _)| *r != register) {
+            let account = chart.account(*role);
+            out.push_str(&format!("S{} {}\n", account.code(), account.name()));
+            out.push_str(&format!("${:.2}\n", credit - debit));
+        }
+        out.push_str("^\n");
+    }
+    out
+}
+
+fn ofx_date(at: Timestamp) -> String {
+    let (y, m, d) = clock::date(at);
+    let secs = at % clock::DAY;
+    format!("{y:04}{m:02}{d:02}{:02}{:02}{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+fn ofx(
+    transactions: &[ExportTransaction],
+    chart: &ChartOfAccounts,
+    register: AccountRole,
+    period: Range<Timestamp>,
+    closing: f64,
+    currency: &str,
+) -> String {
+    let rows: Vec<&ExportTransaction> = transactions
+        .iter()
+        .filter(|t| t.lines.iter().any(|(r, _, _)| *r == register))
+        .collect();
+
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str("<?OFX OFXHEADER=\"200\" VERSION=\"220\" SECURITY=\"NONE\" OLDFILEUID=\"NONE\" NEWFILEUID=\"NONE\"?>\n");
+    out.push_str("<OFX>\n<BANKMSGSRSV1>\n<STMTTRNRS>\n<TRNUID>0</TRNUID>\n");
+    out.push_str("<STATUS><CODE>0</CODE><SEVERITY>INFO</SEVERITY></STATUS>\n<STMTRS>\n");
+    out.push_str(&format!("<CURDEF>{}</CURDEF>\n", escape_html(currency)));
+    out.push_str(&format!(
+        "<BANKACCTFROM><BANKID>STORE</BANKID><ACCTID>{}</ACCTID><ACCTTYPE>CHECKING</ACCTTYPE></BANKACCTFROM>\n",
+        escape_html(chart.account(register).code())
+    ));
+    out.push_str(&format!(
+        "<BANKTRANLIST>\n<DTSTART>{}</DTSTART>\n<DTEND>{}</DTEND>\n",
+        ofx_date(period.start),
+        ofx_date(period.end)
+    ));
+    for t in rows {
+        let amount = t.amount_on(register);
+        out.push_str("<STMTTRN>\n");
+        out.push_str(&format!("<TRNTYPE>{}</TRNTYPE>\n", if amount < 0.0 { "DEBIT" } else { "CREDIT" }));
+        out.push_str(&format!("<DTPOSTED>{}</DTPOSTED>\n", ofx_date(t.date)));
+        out.push_str(&format!("<TRNAMT>{:.2}</TRNAMT>\n", amount));
+        out.push_str(&format!("<FITID>{}</FITID>\n", t.id));
+        out.push_str(&format!("<NAME>{}</NAME>\n", escape_html(&t.memo)));
+        out.push_str("</STMTTRN>\n");
+    }
+    out.push_str("</BANKTRANLIST>\n");
+    out.push_str(&format!(
+        "<LEDGERBAL><BALAMT>{:.2}</BALAMT><DTASOF>{}</DTASOF></LEDGERBAL>\n",
+        closing,
+        ofx_date(period.end)
+    ));
+    out.push_str("</STMTRS>\n</STMTTRNRS>\n</BANKMSGSRSV1>\n</OFX>\n");
+    out
+}
@@ -1,20 +1,69 @@
 #![allow(dead_code)]
+use crate::clock::{self, Timestamp};
+use crate::query::{ListKeys, ListQuery};
+use crate::report::{Report, ReportFormat, Value};
+use crate::tax::TaxClass;
+use crate::transactions::StoreError;
+
+/// A shelf price and the moment it takes effect. Changes can be recorded
+/// ahead of time; they apply once `effective_from` is reached.
+#[derive(Debug, Clone)]
+pub struct PriceChange {
+    price: f64,
+    effective_from: Timestamp,
+    recorded_at: Timestamp,
+}
+
+impl PriceChange {
+    pub fn price(&self) -> f64 { self.price }
+    pub fn effective_from(&self) -> Timestamp { self.effective_from }
+    pub fn recorded_at(&self) -> Timestamp { self.recorded_at }
+}
+
 pub struct Product {
     name: String,
     description: String,
-    price: f64,
+    /// Every price the product has had or is scheduled to have, ordered by
+    /// `effective_from`. The first entry is the price it was created with.
+    prices: Vec<PriceChange>,
     quantity: u32,
+    /// Bill of materials for a kit: component product name and units per kit.
+    /// Kits keep no stock of their own.
+    components: Vec<(String, u32)>,
+    sku: String,
+    /// Attribute names (size, colour, flavour...) a parent product varies by.
+    /// Parents keep no stock of their own; their variants do.
+    variant_attributes: Vec<String>,
+    /// Name of the parent product when this product is a variant.
+    parent: Option<String>,
+    /// Attribute values of a variant, in the parent's attribute order.
+    attributes: Vec<(String, String)>,
+    category: String,
+    tax_class: TaxClass,
+    /// Weight of one unit, used to spread freight by weight.
+    weight: f64,
 }
 
 impl Product {
     pub fn new(name: &str, description: &str, price: f64, quantity: u32) -> Self {
         let price = if price < 0.0 { 0.0 } else { price };
-        let quantity = quantity.max(0);
         Self {
             name: name.to_string(),
             description: description.to_string(),
-            price,
+            prices: vec![PriceChange {
+                price,
+                effective_from: 0,
+                recorded_at: clock::now(),
+            }],
             quantity,
+            components: Vec::new(),
+            sku: String::new(),
+            variant_attributes: Vec::new(),
+            parent: None,
+            attributes: Vec::new(),
+            category: String::new(),
+            tax_class: TaxClass::Standard,
+            weight: 0.0,
         }
     }
 
@@ -25,21 +74,99 @@ impl Product {
     pub fn description(&self) -> &str {
         &self.description
     }
+    /// Current shelf price.
     pub fn price(&self) -> f64 {
-        self.price
+        self.price_at(clock::now())
+    }
+    /// Shelf price in effect at `at`. The price a product was created with
+    /// counts as in effect from the start.
+    pub fn price_at(&self, at: Timestamp) -> f64 {
+        self.prices
+            .iter()
+            .rev()
+            .find(|c| c.effective_from <= at)
+            .map_or(0.0, |c| c.price)
+    }
+    pub fn price_history(&self) -> &[PriceChange] {
+        &self.prices
     }
     pub fn quantity(&self) -> u32 {
         self.quantity
     }
+    pub fn components(&self) -> &[(String, u32)] {
+        &self.components
+    }
+    pub fn is_kit(&self) -> bool {
+        !self.components.is_empty()
+    }
+    pub fn sku(&self) -> &str {
+        &self.sku
+    }
+    pub fn variant_attributes(&self) -> &[String] {
+        &self.variant_attributes
+    }
+    pub fn is_parent(&self) -> bool {
+        !self.variant_attributes.is_empty()
+    }
+    pub fn parent(&self) -> Option<&str> {
+        self.parent.as_deref()
+    }
+    pub fn attributes(&self) -> &[(String, String)] {
+        &self.attributes
+    }
+    pub fn category(&self) -> &str {
+        &self.category
+    }
+    pub fn tax_class(&self) -> TaxClass {
+        self.tax_class
+    }
+    pub fn weight(&self) -> f64 {
+        self.weight
+    }
 
     // ---- SETTERS ----
     pub fn set_description(&mut self, description: &str) {
         self.description = description.to_string();
     }
+    /// Changes the price from now on; the old price stays in the history.
     pub fn set_price(&mut self, price: f64) {
-        if price >= 0.0 {
-            self.price = price;
+        self.schedule_price(price, clock::now());
+    }
+    /// Records a price that takes effect at `effective_from`, which may be
+    /// in the future. A change already scheduled for that moment is replaced.
+    pub fn schedule_price(&mut self, price: f64, effective_from: Timestamp) {
+        if price < 0.0 {
+            return;
         }
+        self.prices.retain(|c| c.effective_from != effective_from);
+        let pos = self.prices.partition_point(|c| c.effective_from <= effective_from);
+        self.prices.insert(
+            pos,
+            PriceChange {
+                price,
+                effective_from,
+                recorded_at: clock::now(),
+            },
+        );
+    }
+    /// Drops a change that has not taken effect yet. Returns false if there
+    /// was no such pending change.
+    pub fn cancel_price_change(&mut self, effective_from: Timestamp, now: Timestamp) -> bool {
+        let before = self.prices.len();
+        self.prices.retain(|c| c.effective_from != effective_from || c.effective_from <= now);
+        self.prices.len() != before
+    }
+    pub fn set_category(&mut self, category: &str) {
+        self.category = category.to_string();
+    }
+    pub fn set_tax_class(&mut self, tax_class: TaxClass) {
+        self.tax_class = tax_class;
+    }
+    pub fn set_weight(&mut self, weight: f64) {
+        self.weight = weight.max(0.0);
+    }
+    pub fn set_sku(&mut self, sku: &str) {
+        self.sku = sku.to_string();
     }
     pub fn set_quantity(&mut self, quantity: u32) {
         self.quantity = quantity;
@@ -56,26 +183,154 @@ impl Product {
     }
 }
 
+/// Stock held back for a customer (phone order, pending cart) until it is
+/// sold, cancelled or the hold expires.
+pub struct Reservation {
+    id: u32,
+    product_name: String,
+    owner: String,
+    quantity: u32,
+    expires_at: Timestamp,
+}
+
+impl Reservation {
+    pub fn id(&self) -> u32 { self.id }
+    pub fn product_name(&self) -> &str { &self.product_name }
+    pub fn owner(&self) -> &str { &self.owner }
+    pub fn quantity(&self) -> u32 { self.quantity }
+    pub fn expires_at(&self) -> Timestamp { self.expires_at }
+    pub fn is_expired(&self, now: Timestamp) -> bool { self.expires_at <= now }
+}
+
 pub struct Inventory {
     products: Vec<Product>,
+    reservations: Vec<Reservation>,
+    next_reservation_id: u32,
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Inventory {
     pub fn new() -> Self {
         Inventory {
             products: Vec::new(),
+            reservations: Vec::new(),
+            next_reservation_id: 1,
         }
     }
     pub fn add_product(&mut self, name: &str, description: &str, price: f64, quantity: u32) {
-        let product = Product {
-            name: name.to_string(),
-            description: description.to_string(),
-            price,
-            quantity,
-        };
-        self.products.push(product);
+        self.products.push(Product::new(name, description, price, quantity));
+    }
+
+    /// Adds a parent product that only groups variants differing by `attributes`.
+    pub fn add_parent(&mut self, name: &str, description: &str, attributes: &[&str]) -> Result<(), StoreError> {
+        if attributes.is_empty() {
+            return Err(StoreError::InvalidInput("A parent product needs at least one attribute".into()));
+        }
+        let mut parent = Product::new(name, description, 0.0, 0);
+        parent.variant_attributes = attributes.iter().map(|a| a.to_string()).collect();
+        self.products.push(parent);
+        Ok(())
+    }
+
+    /// Adds a variant of `parent` with its own SKU, price and stock. The
+    /// variant is named after the parent followed by its attribute values,
+    /// e.g. "Cola 1.5L".
+    pub fn add_variant(
+        &mut self,
+        parent: &str,
+        sku: &str,
+        attributes: &[(&str, &str)],
+        price: f64,
+        quantity: u32,
+    ) -> Result<&Product, StoreError> {
+        let p = self.find(parent).ok_or_else(|| StoreError::NotFound(parent.to_string()))?;
+        if !p.is_parent() {
+            return Err(StoreError::InvalidInput(format!("{parent} has no variant attributes")));
+        }
+        let mut values = Vec::new();
+        for attr in &p.variant_attributes {
+            match attributes.iter().find(|(a, _)| a == attr) {
+                Some((_, v)) if !v.is_empty() => values.push((attr.clone(), v.to_string())),
+                _ => return Err(StoreError::InvalidInput(format!("Missing value for {attr}"))),
+            }
+        }
+        if attributes.len() != values.len() {
+            return Err(StoreError::InvalidInput(format!("Unknown attribute for {parent}")));
+        }
+        if sku.is_empty() || self.find_by_sku(sku).is_some() {
+            return Err(StoreError::InvalidInput(format!("SKU '{sku}' is empty or already used")));
+        }
+
+        let name = std::iter::once(parent)
+            .chain(values.iter().map(|(_, v)| v.as_str()))
+            .collect::<Vec<_>>()
+            .join(" ");
+        if self.find(&name).is_some() {
+            return Err(StoreError::InvalidInput(format!("{name} already exists")));
+        }
+        let mut variant = Product::new(&name, &p.description, price, quantity);
+        variant.sku = sku.to_string();
+        variant.category = p.category.clone();
+        variant.tax_class = p.tax_class;
+        variant.parent = Some(parent.to_string());
+        variant.attributes = values;
+        self.products.push(variant);
+        Ok(self.products.last().unwrap())
+    }
+
+    /// Adds a kit sold at its own price and built from existing products.
+    pub fn add_kit(
+        &mut self,
+        name: &str,
+        description: &str,
+        price: f64,
+        components: &[(&str, u32)],
+    ) -> Result<(), StoreError> {
+        if components.is_empty() {
+            return Err(StoreError::InvalidInput("A kit needs at least one component".into()));
+        }
+        for (component, per_kit) in components {
+            if *per_kit == 0 {
+                return Err(StoreError::InvalidInput("Component quantity must be > 0".into()));
+            }
+            match self.find(component) {
+                Some(p) if p.is_kit() || p.is_parent() => {
+                    return Err(StoreError::InvalidInput(format!("{component} cannot be a kit component")));
+                }
+                Some(_) => {}
+                None => return Err(StoreError::NotFound(component.to_string())),
+            }
+        }
+
+        let mut kit = Product::new(name, description, price, 0);
+        kit.components = components.iter().map(|(c, q)| (c.to_string(), *q)).collect();
+        self.products.push(kit);
+        Ok(())
     }
 
+    /// Schedules a price change for a product; kits and variants have their
+    /// own prices, parents have none.
+    pub fn schedule_price(&mut self, name: &str, price: f64, effective_from: Timestamp) -> Result<(), StoreError> {
+        if price < 0.0 {
+            return Err(StoreError::InvalidInput("Price must be >= 0".into()));
+        }
+        let product = self.find_mut(name).ok_or_else(|| StoreError::NotFound(name.to_string()))?;
+        if product.is_parent() {
+            return Err(StoreError::InvalidInput(format!("{name} has variants; price those instead")));
+        }
+        product.schedule_price(price, effective_from);
+        Ok(())
+    }
+
+    /// Edits a product. A new name goes through `rename_product`; if it is
+    /// empty or taken the product keeps its old name, and use
+    /// `Transactions::edit_product` to have the error and a rename of the
+    /// sales and purchase history too.
     pub fn edit_product(
         &mut self,
         current_name: &str,
@@ -84,25 +339,81 @@ impl Inventory {
         price: Option<f64>,
         quantity: Option<u32>,
     ) {
-        if let Some(product) = self.find_mut(current_name) {
-            if let Some(name) = name {
-                product.name = name.to_string();
-            }
+        let renamed = name.and_then(|n| self.rename_product(current_name, n).ok());
+        let current_name = match renamed.as_ref().and_then(|r| r.first()) {
+            Some((_, new)) => new.clone(),
+            None => current_name.to_string(),
+        };
+        if let Some(product) = self.find_mut(&current_name) {
             if let Some(description) = description {
                 product.description = description.to_string();
             }
             if let Some(price) = price {
-                product.price = price;
+                product.set_price(price);
             }
-            if let Some(quantity) = quantity {
+            if let Some(quantity) = quantity
+                && !product.is_kit()
+                && !product.is_parent()
+            {
                 product.quantity = quantity;
             }
         }
     }
 
+    /// Renames a product. The new name must be free; kits, variants and
+    /// reservations follow the rename, and the variants of a renamed parent
+    /// are renamed with it. Returns every `(old, new)` name that changed.
+    pub fn rename_product(&mut self, current_name: &str, name: &str) -> Result<Vec<(String, String)>, StoreError> {
+        if self.find(current_name).is_none() {
+            return Err(StoreError::NotFound(current_name.to_string()));
+        }
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(StoreError::InvalidInput("Name must not be empty".into()));
+        }
+        if name == current_name {
+            return Ok(Vec::new());
+        }
+        let mut renames = vec![(current_name.to_string(), name.to_string())];
+        for v in self.variants(current_name) {
+            if let Some(rest) = v.name.strip_prefix(current_name) {
+                renames.push((v.name.clone(), format!("{name}{rest}")));
+            }
+        }
+        if let Some((_, taken)) = renames.iter().find(|(_, new)| self.find(new).is_some()) {
+            return Err(StoreError::InvalidInput(format!("{taken} already exists")));
+        }
+        for (old, new) in &renames {
+            self.rename(old, new);
+        }
+        Ok(renames)
+    }
+
+    // Points the product and everything that refers to it by name at `new`.
+    fn rename(&mut self, old: &str, new: &str) {
+        for p in self.products.iter_mut() {
+            if p.name == old {
+                p.name = new.to_string();
+            }
+            for (component, _) in p.components.iter_mut() {
+                if component == old {
+                    *component = new.to_string();
+                }
+            }
+            if p.parent.as_deref() == Some(old) {
+                p.parent = Some(new.to_string());
+            }
+        }
+        for r in self.reservations.iter_mut().filter(|r| r.product_name == old) {
+            r.product_name = new.to_string();
+        }
+    }
+
+    /// Removes a product; removing a parent also removes its variants.
     pub fn del_product(&mut self, name: &str) {
         if let Some(pos) = self.products.iter().position(|p| p.name() == name) {
             self.products.remove(pos);
+            self.products.retain(|p| p.parent.as_deref() != Some(name));
         }
     }
 
@@ -110,21 +421,238 @@ impl Inventory {
         &self.products
     }
 
+    pub fn find(&self, name: &str) -> Option<&Product> {
+        self.products.iter().find(|p| p.name() == name)
+    }
+
     pub fn find_mut(&mut self, name: &str) -> Option<&mut Product> {
         self.products.iter_mut().find(|p| p.name() == name)
     }
+
+    pub fn find_by_sku(&self, sku: &str) -> Option<&Product> {
+        self.products.iter().find(|p| !p.sku.is_empty() && p.sku == sku)
+    }
+
+    pub fn variants(&self, parent: &str) -> Vec<&Product> {
+        self.products
+            .iter()
+            .filter(|p| p.parent.as_deref() == Some(parent))
+            .collect()
+    }
+
+    // ---- STOCK LEVELS ----
+    /// Physical stock on the shelf, including reserved units. For a kit this
+    /// is the number of kits that could be built from its components.
+    pub fn on_hand(&self, name: &str) -> Option<u32> {
+        let p = self.find(name)?;
+        if p.is_kit() {
+            Some(self.buildable(p, |c| self.find(c).map_or(0, |cp| cp.quantity)))
+        } else if p.is_parent() {
+            Some(self.roll_up(name, |v| self.on_hand(v)))
+        } else {
+            Some(p.quantity)
+        }
+    }
+
+    /// Units held by reservations that have not expired yet. Reserved kits
+    /// also count against each of their components.
+    pub fn reserved(&self, name: &str) -> u32 {
+        if self.find(name).is_some_and(|p| p.is_parent()) {
+            return self.roll_up(name, |v| Some(self.reserved(v)));
+        }
+        let now = clock::now();
+        self.reservations
+            .iter()
+            .filter(|r| !r.is_expired(now))
+            .map(|r| {
+                if r.product_name == name {
+                    return r.quantity;
+                }
+                self.find(&r.product_name)
+                    .and_then(|kit| kit.components.iter().find(|(c, _)| c == name))
+                    .map_or(0, |(_, per_kit)| r.quantity * per_kit)
+            })
+            .sum()
+    }
+
+    /// Units that can still be sold or reserved: on hand minus reserved.
+    pub fn available(&self, name: &str) -> Option<u32> {
+        let p = self.find(name)?;
+        if p.is_kit() {
+            Some(self.buildable(p, |c| self.available(c).unwrap_or(0)))
+        } else if p.is_parent() {
+            Some(self.roll_up(name, |v| self.available(v)))
+        } else {
+            Some(p.quantity.saturating_sub(self.reserved(name)))
+        }
+    }
+
+    fn roll_up(&self, parent: &str, stock_of: impl Fn(&str) -> Option<u32>) -> u32 {
+        self.variants(parent)
+            .iter()
+            .map(|v| stock_of(&v.name).unwrap_or(0))
+            .sum()
+    }
+
+    fn buildable(&self, kit: &Product, stock_of: impl Fn(&str) -> u32) -> u32 {
+        kit.components
+            .iter()
+            .map(|(c, per_kit)| stock_of(c) / per_kit)
+            .min()
+            .unwrap_or(0)
+    }
+
+    // ---- RESERVATIONS ----
+    pub fn reserve(
+        &mut self,
+        product_name: &str,
+        owner: &str,
+        quantity: u32,
+        expires_at: Timestamp,
+    ) -> Result<&Reservation, StoreError> {
+        if quantity == 0 {
+            return Err(StoreError::InvalidInput("Quantity must be > 0".into()));
+        }
+        if self.find(product_name).is_some_and(|p| p.is_parent()) {
+            return Err(StoreError::InvalidInput(format!("{product_name} has variants; choose one")));
+        }
+        self.release_expired(clock::now());
+
+        let available = self
+            .available(product_name)
+            .ok_or_else(|| StoreError::NotFound(product_name.to_string()))?;
+        if available < quantity {
+            return Err(StoreError::OutOfStock {
+                product: product_name.to_string(),
+                requested: quantity,
+                available,
+            });
+        }
+
+        let id = self.next_reservation_id;
+        self.next_reservation_id += 1;
+        self.reservations.push(Reservation {
+            id,
+            product_name: product_name.to_string(),
+            owner: owner.to_string(),
+            quantity,
+            expires_at,
+        });
+        Ok(self.reservations.last().unwrap())
+    }
+
+    /// Cancels a reservation, returning its units to available stock.
+    pub fn release_reservation(&mut self, id: u32) -> Result<Reservation, StoreError> {
+        match self.reservations.iter().position(|r| r.id == id) {
+            Some(pos) => Ok(self.reservations.remove(pos)),
+            None => Err(StoreError::NotFound(format!("reservation #{id}"))),
+        }
+    }
+
+    /// Puts back reservations released for a sale that then failed.
+    pub(crate) fn restore_reservations(&mut self, released: Vec<Reservation>) {
+        self.reservations.extend(released);
+        self.reservations.sort_by_key(|r| r.id);
+    }
+
+    /// Drops every reservation that expired at or before `now`.
+    pub fn release_expired(&mut self, now: Timestamp) -> Vec<Reservation> {
+        let (expired, active) = self
+            .reservations
+            .drain(..)
+            .partition(|r| r.is_expired(now));
+        self.reservations = active;
+        expired
+    }
+
+    pub fn reservations(&self) -> &[Reservation] {
+        &self.reservations
+    }
+}
+
+/// Every product with its stock; variants are listed right under their
+/// parent, which shows the roll-up.
+pub fn inventory_report(inv: &Inventory) -> Report {
+    inventory_report_for(inv, &ListQuery::new())
+}
+
+/// The products `query` selects. Once sorted, variants no longer sit under
+/// their parent and lose their indent.
+pub fn inventory_report_for(inv: &Inventory, query: &ListQuery) -> Report {
+    let mut rows: Vec<(&Product, bool)> = Vec::new();
+    for p in inv.all().iter().filter(|p| p.parent.is_none()) {
+        rows.push((p, false));
+        rows.extend(inv.variants(&p.name).into_iter().map(|v| (v, true)));
+    }
+    let page = query.apply(rows, |(p, _)| ListKeys {
+        name: &p.name,
+        text: &p.description,
+        price: (!p.is_parent()).then(|| p.price()),
+        quantity: inv.on_hand(&p.name).unwrap_or(0),
+        value: stock_value(inv, p),
+    });
+
+    let mut report = Report::new("Inventory", &["Name", "Description", "Price", "Qty", "Rsvd", "Avail"]);
+    for (p, is_variant) in page.items() {
+        let label = match (is_variant, query.is_sorted()) {
+            (false, _) => p.name.clone(),
+            (true, false) => format!("  {} [{}]", p.name, p.sku),
+            (true, true) => format!("{} [{}]", p.name, p.sku),
+        };
+        let price = if p.is_parent() { Value::Empty } else { Value::Money(p.price()) };
+        report.add_row(inventory_row(inv, p, &label, price));
+    }
+    if query.page_size().is_some() {
+        report.set_paging(page.page(), page.pages(), page.total());
+    }
+    report
+}
+
+/// Shelf value of the stock on hand; for a parent, of all its variants.
+fn stock_value(inv: &Inventory, p: &Product) -> f64 {
+    if p.is_parent() {
+        inv.variants(&p.name).iter().map(|v| stock_value(inv, v)).sum()
+    } else {
+        p.price() * inv.on_hand(&p.name).unwrap_or(0) as f64
+    }
 }
 
 pub fn report_inventory(inv: &Inventory) -> String {
+    inventory_report(inv).render(ReportFormat::Text)
+}
+
+/// Every price a product has had or is scheduled to have.
+pub fn report_price_history(p: &Product, now: Timestamp) -> String {
     let mut out = String::new();
-    out.push_str("Name                 | Description              | Price   | Qty\n");
-    out.push_str("---------------------------------------------------------------\n");
+    out.push_str(&format!("Price history for {}\n", p.name));
+    out.push_str("Effective from | Price   | Recorded at | Status\n");
+    out.push_str("---------------------------------------------------\n");
 
-    for p in inv.all() {
+    let current = p.prices.iter().rposition(|c| c.effective_from <= now);
+    for (i, c) in p.prices.iter().enumerate() {
+        let status = match current {
+            Some(cur) if i == cur => "Current",
+            Some(cur) if i < cur => "Past",
+            _ => "Scheduled",
+        };
         out.push_str(&format!(
-            "{:<20} | {:<24} | {:>7.2} | {:>3}\n",
-            p.name, p.description, p.price, p.quantity
+            "{:<14} | {:>7.2} | {:<11} | {}\n",
+            clock::format_date(c.effective_from),
+            c.price,
+            clock::format_date(c.recorded_at),
+            status
         ));
     }
     out
 }
+
+fn inventory_row(inv: &Inventory, p: &Product, label: &str, price: Value) -> Vec<Value> {
+    vec![
+        Value::text(label),
+        Value::text(&p.description),
+        price,
+        Value::Integer(inv.on_hand(&p.name).unwrap_or(0) as i64),
+        Value::Integer(inv.reserved(&p.name) as i64),
+        Value::Integer(inv.available(&p.name).unwrap_or(0) as i64),
+    ]
+}
@@ -1,6 +1,46 @@
+pub mod accounting;
+pub mod clock;
+pub mod costing;
+pub mod currency;
+pub mod customers;
+mod escape;
+pub mod exports;
 pub mod inventory;
+pub mod loyalty;
+pub mod payments;
+pub mod price_lists;
+pub mod procurement;
+pub mod promotions;
 pub mod transactions;
+pub mod query;
+pub mod receipts;
+pub mod report;
+pub mod sales_reports;
+pub mod slow_movers;
 pub mod security;
+pub mod shifts;
+pub mod stored_value;
+pub mod tax;
+pub mod valuation;
 
-pub use inventory::{Inventory, report_inventory};
-pub use transactions::{Transactions, report_sales, report_purchases, StoreError};
\ No newline at end of file
+pub use inventory::{Inventory, Product, PriceChange, Reservation, inventory_report, inventory_report_for, report_inventory, report_price_history};
+pub use transactions::{Transactions, Cart, CartLine, Order, Sale, SaleComponent, SaleReturn, Purchase, SupplierReturn, StockCount, Backorder, sales_report, sales_report_for, report_sales, sales_exploded_report, report_sales_exploded, purchases_report, purchases_report_for, report_purchases, backorders_report, report_backorders, returns_report, report_returns, report_debit_note, StoreError};
+pub use customers::{Customer, Customers, customer_history_report, report_customer_history};
+pub use loyalty::{Loyalty, LoyaltyRules, PointsEntry, PointsKind, points_report, report_points};
+pub use promotions::{Promotion, PromotionKind, PromotionTarget, Promotions, AppliedPromotion};
+pub use tax::{TaxClass, TaxSettings, PricingMode, TaxRounding, TaxLine};
+pub use payments::{Tender, TenderKind, Payment};
+pub use shifts::{Shift, expected_by_tender, report_shift};
+pub use receipts::{StoreHeader, ReceiptFormat, render_receipt};
+pub use stored_value::{CardKind, CardEntry, CardEntryKind, StoredValue, StoredValueCard, liability_report, report_liability};
+pub use price_lists::{PriceList, PriceLists};
+pub use currency::{Money, ExchangeRate, ExchangeRates, rates_report, report_rates};
+pub use costing::{CostingMethod, ChargeKind, AllocationBasis, LandedCharge, margins_report, report_margins};
+pub use procurement::{DocumentLine, PurchaseOrder, SupplierInvoice, InvoiceStatus, Discrepancy, MatchTolerances, Procurement, invoice_matching_report, report_invoice_matching};
+pub use accounting::{AccountRole, Account, ChartOfAccounts, EntryKind, JournalLine, JournalEntry, Journal, journal_report, report_journal, trial_balance_report, report_trial_balance};
+pub use exports::{ExportFormat, ExportTransaction, journal_transactions, daily_summaries, render_export};
+pub use sales_reports::{Period, SalesFilter, SalesTotals, cashier_of, sales_between, totals, by_period, by_hour, by_weekday, by_product, sales_summary_report, report_sales_summary};
+pub use report::{Value, Report, ReportFormatter, ReportFormat, TextFormatter, CsvFormatter, JsonFormatter, MarkdownFormatter, HtmlFormatter};
+pub use query::{SortKey, ListKeys, ListQuery, Page};
+pub use valuation::{ValuationLine, stock_as_of, valuation, valuation_report, report_valuation};
+pub use slow_movers::{StockMovement, MovementSort, stock_movement, sort_movement, slow_movers_report, report_slow_movers};
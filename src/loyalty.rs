@@ -0,0 +1,147 @@
+#![allow(dead_code)]
+use crate::report::{Report, ReportFormat, Value};
+
+/// How many points a sale earns and what a point is worth when redeemed.
+pub struct LoyaltyRules {
+    points_per_unit: f64,
+    category_multipliers: Vec<(String, f64)>,
+    point_value: f64,
+}
+
+impl Default for LoyaltyRules {
+    /// 1 point per currency unit spent, 100 points redeem for 1.00.
+    fn default() -> Self {
+        Self::new(1.0, 0.01)
+    }
+}
+
+impl LoyaltyRules {
+    pub fn new(points_per_unit: f64, point_value: f64) -> Self {
+        Self {
+            points_per_unit: points_per_unit.max(0.0),
+            category_multipliers: Vec::new(),
+            point_value: point_value.max(0.0),
+        }
+    }
+
+    /// Multiplies the points earned on products in `category`, e.g. 2.0 for double points.
+    pub fn set_category_multiplier(&mut self, category: &str, multiplier: f64) {
+        self.category_multipliers.retain(|(c, _)| c != category);
+        self.category_multipliers.push((category.to_string(), multiplier.max(0.0)));
+    }
+
+    pub fn points_per_unit(&self) -> f64 { self.points_per_unit }
+    pub fn point_value(&self) -> f64 { self.point_value }
+
+    pub fn multiplier(&self, category: &str) -> f64 {
+        self.category_multipliers
+            .iter()
+            .find(|(c, _)| c == category)
+            .map_or(1.0, |(_, m)| *m)
+    }
+
+    /// Points earned for spending `amount` on a product in `category`.
+    pub fn points_for(&self, amount: f64, category: &str) -> i64 {
+        (amount * self.points_per_unit * self.multiplier(category)).floor() as i64
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PointsKind {
+    Earned,
+    Redeemed,
+    ClawedBack,
+    Restored,
+}
+
+/// One movement on a customer's points balance.
+pub struct PointsEntry {
+    customer_id: u32,
+    sale_id: u32,
+    kind: PointsKind,
+    points: i64,
+}
+
+impl PointsEntry {
+    pub fn customer_id(&self) -> u32 { self.customer_id }
+    pub fn sale_id(&self) -> u32 { self.sale_id }
+    pub fn kind(&self) -> PointsKind { self.kind }
+    /// Signed change to the balance.
+    pub fn points(&self) -> i64 { self.points }
+}
+
+/// Points ledger; balances are always derived from the entries.
+pub struct Loyalty {
+    rules: LoyaltyRules,
+    entries: Vec<PointsEntry>,
+}
+
+impl Default for Loyalty {
+    fn default() -> Self {
+        Self::new(LoyaltyRules::default())
+    }
+}
+
+impl Loyalty {
+    pub fn new(rules: LoyaltyRules) -> Self {
+        Self {
+            rules,
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn rules(&self) -> &LoyaltyRules {
+        &self.rules
+    }
+
+    pub fn set_rules(&mut self, rules: LoyaltyRules) {
+        self.rules = rules;
+    }
+
+    pub fn balance(&self, customer_id: u32) -> i64 {
+        self.history(customer_id).iter().map(|e| e.points).sum()
+    }
+
+    pub fn history(&self, customer_id: u32) -> Vec<&PointsEntry> {
+        self.entries.iter().filter(|e| e.customer_id == customer_id).collect()
+    }
+
+    /// Net points a sale has moved for `kind` (positive for earned/restored,
+    /// negative for redeemed/clawed back).
+    pub fn points_for_sale(&self, sale_id: u32, kind: PointsKind) -> i64 {
+        self.entries
+            .iter()
+            .filter(|e| e.sale_id == sale_id && e.kind == kind)
+            .map(|e| e.points)
+            .sum()
+    }
+
+    pub(crate) fn post(&mut self, customer_id: u32, sale_id: u32, kind: PointsKind, points: i64) {
+        if points != 0 {
+            self.entries.push(PointsEntry {
+                customer_id,
+                sale_id,
+                kind,
+                points,
+            });
+        }
+    }
+}
+
+pub fn points_report(loyalty: &Loyalty, customer_id: u32) -> Report {
+    let title = format!("Points for customer #{customer_id}");
+    let mut report = Report::new(&title, &["Sale", "Kind", "Points"]);
+    for e in loyalty.history(customer_id) {
+        report.add_row(vec![
+            Value::Integer(e.sale_id as i64),
+            Value::Text(format!("{:?}", e.kind)),
+            Value::Integer(e.points),
+        ]);
+    }
+    report.add_total("Balance", Value::Integer(loyalty.balance(customer_id)));
+    report
+}
+
+pub fn report_points(loyalty: &Loyalty, customer_id: u32) -> String {
+    points_report(loyalty, customer_id).render(ReportFormat::Text)
+}
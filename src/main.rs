@@ -1,12 +1,52 @@
 #![allow(unused_imports)]
+mod accounting;
+mod clock;
+mod costing;
+mod currency;
+mod customers;
+mod escape;
+mod exports;
 mod inventory;
+mod loyalty;
+mod payments;
+mod price_lists;
+mod procurement;
+mod promotions;
 mod transactions;
+mod query;
+mod receipts;
+mod report;
+mod sales_reports;
+mod slow_movers;
 mod security;
+mod shifts;
+mod stored_value;
+mod tax;
+mod valuation;
 
+use std::fs;
 use std::io::{self, Write};
 
-use inventory::{Inventory, report_inventory};
-use transactions::{Transactions, report_sales, report_purchases};
+use inventory::{Inventory, inventory_report, inventory_report_for, report_inventory, report_price_history};
+use payments::{Tender, TenderKind};
+use transactions::{Transactions, Cart, StoreError, sales_report, sales_report_for, purchases_report, purchases_report_for, report_sales, sales_exploded_report, report_sales_exploded, report_purchases, backorders_report, report_backorders, returns_report, report_returns, report_debit_note};
+use loyalty::report_points;
+use shifts::report_shift;
+use receipts::{StoreHeader, ReceiptFormat, render_receipt};
+use stored_value::{CardKind, StoredValue, liability_report, report_liability};
+use price_lists::PriceList;
+use currency::{Money, report_rates};
+use costing::{AllocationBasis, ChargeKind, CostingMethod, margins_report, report_margins};
+use accounting::{AccountRole, journal_report, report_journal, trial_balance_report, report_trial_balance};
+use clock::{DAY, format_date, parse_date};
+use exports::{ExportFormat, daily_summaries, journal_transactions, render_export};
+use sales_reports::{Period, SalesFilter, report_sales_summary};
+use report::{Report, ReportFormat};
+use query::{ListQuery, SortKey};
+use valuation::{report_valuation, valuation_report};
+use slow_movers::{MovementSort, report_slow_movers};
+use procurement::{DocumentLine, MatchTolerances, invoice_matching_report, report_invoice_matching};
+use customers::{Customers, report_customer_history};
 use security::{login, signup, change_password, Role, User};
 
 fn prompt(label: &str) -> String {
@@ -24,36 +64,214 @@ fn seed_inventory(inv: &mut Inventory) {
     inv.add_product("Bread", "Whole grain", 18.0, 10);
 }
 
-fn client_menu(inv: &mut Inventory, tx: &mut Transactions, user: &User) {
+fn client_menu(inv: &mut Inventory, tx: &mut Transactions, customers: &mut Customers, user: &User) {
+    let customer_id = customers.for_user(&user.username).id();
     loop {
         println!("\n== Client Menu ==");
         println!("1) View products");
         println!("2) Purchase");
-        println!("3) Change password");
+        println!("3) My backorders");
+        println!("4) My purchases");
+        println!("5) My points");
+        println!("6) Change password");
         println!("0) Logout");
         match prompt("> ").as_str() {
-            "1" => println!("{}", report_inventory(inv)),
+            "1" => browse(|q| inventory_report_for(inv, q)),
             "2" => {
                 let name = prompt("Product name: ");
                 let qty: u32 = prompt("Quantity: ").parse().unwrap_or(0);
-                let unit: f64 = prompt("Unit price: ").parse().unwrap_or(0.0);
+                let shelf = inv.find(&name).map_or(0.0, |p| p.price());
+                let unit_in = prompt(&format!("Unit price (leave empty for {:.2}): ", shelf));
+                let unit: f64 = if unit_in.is_empty() { shelf } else { unit_in.parse().unwrap_or(0.0) };
                 if qty == 0 || unit <= 0.0 {
                     println!("Invalid quantity or price.");
                     continue;
                 }
-                match tx.record_sale(&name, qty, unit, inv) {
+                let coupon = prompt("Coupon code (optional): ");
+                let mut cart = Cart::for_customer(customer_id);
+                cart.add_line(&name, qty, unit);
+                if !coupon.is_empty() {
+                    cart.add_coupon(&coupon);
+                }
+                let due = match tx.quote(&cart, inv) {
+                    Ok(due) => due,
+                    Err(StoreError::OutOfStock { available, .. }) => {
+                        offer_backorder(inv, tx, customer_id, &cart, available);
+                        continue;
+                    }
+                    Err(e) => {
+                        println!("Error: {:?}", e);
+                        continue;
+                    }
+                };
+                pay_and_checkout(inv, tx, &mut cart, due, customer_id);
+            }
+            "3" => {
+                let mine = tx.backorders_for(customer_id);
+                if mine.is_empty() {
+                    println!("No backorders.");
+                    continue;
+                }
+                for b in &mine {
+                    let status = if b.is_fulfilled() { "picked up" } else if b.is_ready() { "ready for pickup" } else { "waiting" };
+                    println!("#{} {} x{} ({}/{} arrived) - {}", b.id(), b.product_name(), b.quantity(), b.allocated(), b.quantity(), status);
+                }
+                if !mine.iter().any(|b| b.is_ready()) {
+                    continue;
+                }
+                let id: u32 = prompt("Backorder to pick up (empty to skip): ").parse().unwrap_or(0);
+                if id == 0 {
+                    continue;
+                }
+                if !mine.iter().any(|b| b.id() == id) {
+                    println!("Not your backorder.");
+                    continue;
+                }
+                match tx.fulfil_backorder(id, inv) {
                     Ok(s) => println!("Sale: {} x{} = {}", s.product_name(), s.quantity(), s.total_price()),
                     Err(e) => println!("Error: {:?}", e),
                 }
             }
-            "3" => change_password(user),
+            "4" => {
+                if let Some(c) = customers.find(customer_id) {
+                    println!("{}", report_customer_history(tx, c));
+                }
+            }
+            "5" => println!("{}", report_points(tx.loyalty(), customer_id)),
+            "6" => change_password(user),
             "0" => break,
             _ => println!("Invalid choice."),
         }
     }
 }
 
-fn manager_menu(inv: &mut Inventory, tx: &mut Transactions, user: &User, with_reports: bool) {
+/// Takes payment for `cart` and checks it out. Returns false if the
+/// customer cancels or the checkout fails.
+fn pay_and_checkout(inv: &mut Inventory, tx: &mut Transactions, cart: &mut Cart, due: f64, customer_id: u32) -> bool {
+    let points = tx.loyalty().balance(customer_id);
+    if !take_tenders(cart, due, points, tx.loyalty().rules().point_value(), tx.stored_value()) {
+        println!("Purchase cancelled.");
+        return false;
+    }
+    match tx.checkout(cart, inv) {
+        Ok(o) => {
+            let id = o.id();
+            if let Ok(receipt) = render_receipt(tx, &store_header(), id, ReceiptFormat::Text) {
+                println!("{receipt}");
+            }
+            true
+        }
+        Err(e) => {
+            println!("Error: {:?}", e);
+            false
+        }
+    }
+}
+
+/// Offers to backorder what `cart`'s one line is short of, selling what is
+/// in stock first.
+fn offer_backorder(inv: &mut Inventory, tx: &mut Transactions, customer_id: u32, cart: &Cart, available: u32) {
+    let line = &cart.lines()[0];
+    let (name, unit) = (line.product_name(), line.unit_price());
+    let missing = line.quantity().saturating_sub(available);
+    println!("Only {available} in stock.");
+    if let Err(e) = tx.check_backorder(name, missing, unit, inv) {
+        println!("Cannot backorder: {:?}", e);
+        return;
+    }
+    if prompt(&format!("Backorder the missing {missing}? (y/n): ")) != "y" {
+        return;
+    }
+    // what is in stock goes through the usual checkout, coupon included
+    if available > 0 {
+        let mut in_stock = Cart::for_customer(customer_id);
+        in_stock.add_line(name, available, unit);
+        for coupon in cart.coupons() {
+            in_stock.add_coupon(coupon);
+        }
+        let due = match tx.quote(&in_stock, inv) {
+            Ok(due) => due,
+            Err(e) => {
+                println!("Error: {:?}", e);
+                return;
+            }
+        };
+        if !pay_and_checkout(inv, tx, &mut in_stock, due, customer_id) {
+            return;
+        }
+    }
+    match tx.record_backorder(customer_id, name, missing, unit, inv) {
+        Ok(b) => println!("Backorder #{}: {} x{}", b.id(), b.product_name(), b.quantity()),
+        Err(e) => println!("Error: {:?}", e),
+    }
+}
+
+/// Asks for tenders until `due` is covered. Returns false if the customer cancels.
+fn take_tenders(cart: &mut Cart, due: f64, mut points: i64, point_value: f64, cards: &StoredValue) -> bool {
+    let mut paid = 0.0;
+    while paid + 1e-9 < due {
+        println!("Due: {:.2}", due - paid);
+        println!("1) Cash");
+        println!("2) Card");
+        if points > 0 {
+            println!("3) Loyalty points ({points} available)");
+        }
+        println!("4) Gift card / store credit");
+        println!("0) Cancel");
+        match prompt("> ").as_str() {
+            choice @ ("1" | "2") => {
+                let amount: f64 = prompt("Amount: ").parse().unwrap_or(0.0);
+                if amount <= 0.0 {
+                    println!("Invalid amount.");
+                    continue;
+                }
+                paid += amount;
+                cart.add_tender(if choice == "1" { Tender::Cash(amount) } else { Tender::Card(amount) });
+            }
+            "3" if points > 0 => {
+                let p: u32 = prompt("Points: ").parse().unwrap_or(0);
+                if p == 0 || p as i64 > points {
+                    println!("Invalid points.");
+                    continue;
+                }
+                points -= p as i64;
+                paid += p as f64 * point_value;
+                cart.add_tender(Tender::LoyaltyPoints(p));
+            }
+            "4" => {
+                let number = prompt("Card number: ");
+                let Some(card) = cards.find(&number) else {
+                    println!("No such card.");
+                    continue;
+                };
+                let balance = card.available(clock::now());
+                println!("Balance: {:.2}", balance);
+                let amount: f64 = prompt("Amount: ").parse().unwrap_or(0.0);
+                if amount <= 0.0 || amount > balance {
+                    println!("Invalid amount.");
+                    continue;
+                }
+                paid += amount;
+                let number = card.number().to_string();
+                cart.add_tender(match card.kind() {
+                    CardKind::GiftCard => Tender::GiftCard { number, amount },
+                    CardKind::StoreCredit => Tender::StoreCredit { number, amount },
+                });
+            }
+            "0" => return false,
+            _ => println!("Invalid choice."),
+        }
+    }
+    true
+}
+
+fn manager_menu(
+    inv: &mut Inventory,
+    tx: &mut Transactions,
+    customers: &mut Customers,
+    user: &User,
+    with_reports: bool,
+) {
     loop {
         println!("\n== Manager Menu ==");
         println!("1) View products");
@@ -61,17 +279,31 @@ fn manager_menu(inv: &mut Inventory, tx: &mut Transactions, user: &User, with_re
         println!("3) Edit product");
         println!("4) Delete product");
         println!("5) Record supplier purchase");
+        println!("6) Customer history");
+        println!("7) Add walk-in customer");
+        println!("8) Record customer return");
+        println!("9) Till shift");
+        println!("10) Reprint receipt");
+        println!("11) Gift cards & store credit");
+        println!("12) Prices & price lists");
+        println!("13) Exchange rates");
+        println!("14) Add landed charge (freight, duty...)");
+        println!("15) Return stock to supplier");
+        println!("16) Purchase orders & invoices");
+        println!("17) Accounting");
         if with_reports {
-            println!("6) View reports");
-            println!("7) Change password");
+            println!("18) View reports");
+            println!("19) Sales analysis");
+            println!("20) Slow movers & dead stock");
+            println!("21) Change password");
         } else {
-            println!("6) Change password");
+            println!("18) Change password");
         }
         println!("0) Logout");
 
         let choice = prompt("> ");
         match choice.as_str() {
-            "1" => println!("{}", report_inventory(inv)),
+            "1" => browse(|q| inventory_report_for(inv, q)),
             "2" => {
                 let name = prompt("Name: ");
                 let desc = prompt("Description: ");
@@ -90,8 +322,10 @@ fn manager_menu(inv: &mut Inventory, tx: &mut Transactions, user: &User, with_re
                 let desc_opt = if desc_in.is_empty() { None } else { Some(desc_in.as_str()) };
                 let price_opt = if price_in.is_empty() { None } else { price_in.parse().ok() };
                 let qty_opt = if qty_in.is_empty() { None } else { qty_in.parse().ok() };
-                inv.edit_product(&current, name_opt, desc_opt, price_opt, qty_opt);
-                println!("Updated (if product existed).");
+                match tx.edit_product(&current, name_opt, desc_opt, price_opt, qty_opt, inv) {
+                    Ok(()) => println!("Updated."),
+                    Err(e) => println!("Error: {:?}", e),
+                }
             }
             "4" => {
                 let name = prompt("Product to delete: ");
@@ -102,24 +336,661 @@ fn manager_menu(inv: &mut Inventory, tx: &mut Transactions, user: &User, with_re
                 let name = prompt("Product: ");
                 let qty: u32 = prompt("Quantity: ").parse().unwrap_or(0);
                 let unit: f64 = prompt("Unit price: ").parse().unwrap_or(0.0);
-                match tx.record_purchase(&name, qty, unit, inv) {
-                    Ok(p) => println!("Purchase: {} x{} = {}", p.product_name(), p.quantity(), p.total_cost()),
+                let base = tx.exchange_rates().base().to_string();
+                let currency = prompt(&format!("Currency (leave empty for {base}): "));
+                let currency = if currency.is_empty() { base } else { currency };
+                match tx.record_purchase_in(&name, qty, Money::new(unit, &currency), inv) {
+                    Ok(p) => println!(
+                        "Purchase: {} x{} = {:.2} ({})",
+                        p.product_name(), p.quantity(), p.total_cost(), p.supplier_total()
+                    ),
                     Err(e) => println!("Error: {:?}", e),
                 }
             }
-            "6" if with_reports => {
-                println!("-- Inventory --\n{}", report_inventory(inv));
-                println!("-- Sales --\n{}", report_sales(tx));
-                println!("-- Purchases --\n{}", report_purchases(tx));
+            "6" => {
+                let query = prompt("Customer (id, username, phone or email): ");
+                match customers.lookup(&query) {
+                    Some(c) => println!("{}", report_customer_history(tx, c)),
+                    None => println!("No such customer."),
+                }
+            }
+            "7" => {
+                let name = prompt("Name: ");
+                let phone = prompt("Phone (optional): ");
+                let email = prompt("Email (optional): ");
+                match customers.add_walk_in(&name, Some(&phone), Some(&email)) {
+                    Ok(c) => println!("Customer #{} added.", c.id()),
+                    Err(e) => println!("Error: {:?}", e),
+                }
+            }
+            "8" => {
+                let sale_id: u32 = prompt("Sale number: ").parse().unwrap_or(0);
+                let qty: u32 = prompt("Quantity returned: ").parse().unwrap_or(0);
+                let to_credit = prompt("Refund as store credit? (y/N): ").eq_ignore_ascii_case("y");
+                let result = if to_credit {
+                    tx.record_return_for_credit(sale_id, qty, inv)
+                } else {
+                    tx.record_return(sale_id, qty, inv)
+                };
+                match result {
+                    Ok(r) => match r.credit_card() {
+                        Some(card) => println!(
+                            "Return: {} x{}, store credit {} ({} points restored)",
+                            r.product_name(), r.quantity(), card, r.points_restored()
+                        ),
+                        None => println!(
+                            "Return: {} x{}, refund {:.2} ({} points restored)",
+                            r.product_name(), r.quantity(), r.cash_refund(), r.points_restored()
+                        ),
+                    },
+                    Err(e) => println!("Error: {:?}", e),
+                }
+            }
+            "9" => shift_menu(tx, user),
+            "10" => reprint_receipt(tx),
+            "11" => gift_card_menu(inv, tx, customers),
+            "12" => price_menu(inv, tx, customers),
+            "13" => exchange_rate_menu(tx),
+            "14" => add_landed_charge(inv, tx),
+            "15" => {
+                let purchase_id: u32 = prompt("Purchase number: ").parse().unwrap_or(0);
+                let qty: u32 = prompt("Quantity to return: ").parse().unwrap_or(0);
+                let reason = prompt("Reason: ");
+                let id = match tx.record_supplier_return(purchase_id, qty, &reason, inv) {
+                    Ok(r) => r.id(),
+                    Err(e) => {
+                        println!("Error: {:?}", e);
+                        continue;
+                    }
+                };
+                if let Ok(note) = report_debit_note(tx, id) {
+                    println!("{note}");
+                }
             }
-            "7" if with_reports => change_password(user),
-            "6" if !with_reports => change_password(user),
+            "16" => procurement_menu(inv, tx),
+            "17" => accounting_menu(inv, tx),
+            "18" if with_reports => {
+                let format = choose_report_format();
+                if format != ReportFormat::Text {
+                    save_reports(inv, tx, format);
+                    continue;
+                }
+                match prompt("1) All reports  2) Browse sales  3) Browse purchases  4) Valuation on a past date\n> ").as_str() {
+                    "2" => {
+                        browse(|q| sales_report_for(tx, q));
+                        continue;
+                    }
+                    "3" => {
+                        browse(|q| purchases_report_for(tx, q));
+                        continue;
+                    }
+                    "4" => {
+                        match parse_date(&prompt("As of (YYYY-MM-DD): ")) {
+                            // the end of that day
+                            Some(day) => println!("{}", report_valuation(tx, inv, Some(day + DAY - 1))),
+                            None => println!("Invalid date."),
+                        }
+                        continue;
+                    }
+                    _ => {}
+                }
+                println!("{}", report_inventory(inv));
+                println!("{}", report_sales(tx));
+                println!("{}", report_sales_exploded(tx));
+                println!("{}", report_purchases(tx));
+                println!("{}", report_valuation(tx, inv, None));
+                println!("{}", report_margins(tx));
+                println!("{}", report_invoice_matching(tx.procurement()));
+                println!("{}", report_trial_balance(tx.journal(), tx.chart()));
+                println!("{}", report_returns(tx));
+                println!("{}", report_backorders(tx));
+                println!("{}", report_liability(tx.stored_value(), clock::now()));
+            }
+            "19" if with_reports => sales_analysis(inv, tx, customers),
+            "20" if with_reports => slow_movers(inv, tx),
+            "21" if with_reports => change_password(user),
+            "18" if !with_reports => change_password(user),
             "0" => break,
             _ => println!("Invalid choice."),
         }
     }
 }
 
+fn gift_card_menu(inv: &mut Inventory, tx: &mut Transactions, customers: &Customers) {
+    println!("\n1) Sell gift card");
+    println!("2) Issue store credit");
+    println!("3) Check balance");
+    println!("4) Outstanding balances");
+    println!("5) Write off expired cards");
+    println!("0) Back");
+
+    match prompt("> ").as_str() {
+        "1" => {
+            let amount: f64 = prompt("Amount to load: ").parse().unwrap_or(0.0);
+            let mut cart = Cart::new();
+            cart.add_gift_card(amount);
+            cart.add_tender(Tender::Cash(amount));
+            match tx.checkout(&cart, inv) {
+                Ok(o) => println!("Gift card {} sold (order #{}).", o.gift_cards()[0], o.id()),
+                Err(e) => println!("Error: {:?}", e),
+            }
+        }
+        "2" => {
+            let amount: f64 = prompt("Amount: ").parse().unwrap_or(0.0);
+            let query = prompt("Customer (optional): ");
+            let customer_id = customers.lookup(&query).map(|c| c.id());
+            match tx.stored_value_mut().issue(CardKind::StoreCredit, amount, customer_id, clock::now()) {
+                Ok(card) => println!("Store credit {} issued for {:.2}.", card.number(), card.balance()),
+                Err(e) => println!("Error: {:?}", e),
+            }
+        }
+        "3" => {
+            let number = prompt("Card number: ");
+            match tx.stored_value().balance(&number, clock::now()) {
+                Ok(balance) => println!("Balance: {:.2}", balance),
+                Err(e) => println!("Error: {:?}", e),
+            }
+        }
+        "4" => println!("{}", report_liability(tx.stored_value(), clock::now())),
+        "5" => {
+            match tx.expire_cards(clock::now()) {
+                Ok(expired) => println!("{} card(s) written off.", expired.len()),
+                Err(e) => println!("Error: {:?}", e),
+            }
+        }
+        _ => {}
+    }
+}
+
+fn price_menu(inv: &mut Inventory, tx: &mut Transactions, customers: &Customers) {
+    for list in tx.price_lists().all() {
+        println!("{} ({:+.1}%, {} product prices)", list.name(), list.adjustment(), list.prices().len());
+    }
+    println!("1) Add price list");
+    println!("2) Set product price on a list");
+    println!("3) Set category rule on a list");
+    println!("4) Map customer group to a list");
+    println!("5) Put customer in a group");
+    println!("6) Schedule a price change");
+    println!("7) Price history");
+    println!("0) Back");
+
+    let choice = prompt("> ");
+    match choice.as_str() {
+        "6" => {
+            let name = prompt("Product: ");
+            let price: f64 = prompt("New price: ").parse().unwrap_or(-1.0);
+            let days: u64 = prompt("Takes effect in how many days (0 = now): ").parse().unwrap_or(0);
+            match inv.schedule_price(&name, price, clock::now() + days * DAY) {
+                Ok(()) => println!("Saved."),
+                Err(e) => println!("Error: {:?}", e),
+            }
+            return;
+        }
+        "7" => {
+            let name = prompt("Product: ");
+            match inv.find(&name) {
+                Some(p) => println!("{}", report_price_history(p, clock::now())),
+                None => println!("No such product."),
+            }
+            return;
+        }
+        _ => {}
+    }
+
+    let lists = tx.price_lists_mut();
+    let result = match choice.as_str() {
+        "1" => {
+            let mut list = PriceList::new(&prompt("Name: "));
+            list.set_adjustment(prompt("Percent on shelf price (e.g. -10): ").parse().unwrap_or(0.0));
+            lists.add(list)
+        }
+        "2" => {
+            let name = prompt("List: ");
+            let product = prompt("Product: ");
+            let price: f64 = prompt("Price: ").parse().unwrap_or(0.0);
+            match lists.find_mut(&name) {
+                Some(list) => list.set_price(&product, price),
+                None => Err(StoreError::NotFound(format!("price list {name}"))),
+            }
+        }
+        "3" => {
+            let name = prompt("List: ");
+            let category = prompt("Category: ");
+            let percent: f64 = prompt("Percent on shelf price: ").parse().unwrap_or(0.0);
+            match lists.find_mut(&name) {
+                Some(list) => {
+                    list.set_category_adjustment(&category, percent);
+                    Ok(())
+                }
+                None => Err(StoreError::NotFound(format!("price list {name}"))),
+            }
+        }
+        "4" => {
+            let group = prompt("Group: ");
+            let name = prompt("List: ");
+            lists.set_group_list(&group, &name)
+        }
+        "5" => {
+            let query = prompt("Customer (id, username, phone or email): ");
+            match customers.lookup(&query) {
+                Some(c) => {
+                    lists.assign(c.id(), &prompt("Group (leave empty to remove): "));
+                    Ok(())
+                }
+                None => Err(StoreError::NotFound(format!("customer {query}"))),
+            }
+        }
+        _ => return,
+    };
+    match result {
+        Ok(()) => println!("Saved."),
+        Err(e) => println!("Error: {:?}", e),
+    }
+}
+
+fn exchange_rate_menu(tx: &mut Transactions) {
+    println!("{}", report_rates(tx.exchange_rates()));
+    if prompt("Set a rate? (y/N): ").eq_ignore_ascii_case("y") {
+        let currency = prompt("Currency: ");
+        let rate: f64 = prompt(&format!("Value of 1 {} in {}: ", currency, tx.exchange_rates().base()))
+            .parse()
+            .unwrap_or(0.0);
+        match tx.exchange_rates_mut().set_rate(&currency, rate, clock::now()) {
+            Ok(()) => println!("Saved."),
+            Err(e) => println!("Error: {:?}", e),
+        }
+    }
+}
+
+fn add_landed_charge(inv: &Inventory, tx: &mut Transactions) {
+    println!("{}", report_purchases(tx));
+    let ids: Vec<u32> = prompt("Purchase numbers (comma separated): ")
+        .split(',')
+        .filter_map(|id| id.trim().parse().ok())
+        .collect();
+    let kind = match prompt("Kind (1 freight, 2 duty, 3 handling, 4 other): ").as_str() {
+        "1" => ChargeKind::Freight,
+        "2" => ChargeKind::Duty,
+        "3" => ChargeKind::Handling,
+        _ => ChargeKind::Other,
+    };
+    let amount: f64 = prompt("Amount: ").parse().unwrap_or(0.0);
+    let base = tx.exchange_rates().base().to_string();
+    let currency = prompt(&format!("Currency (leave empty for {base}): "));
+    let currency = if currency.is_empty() { base } else { currency };
+    let basis = match prompt("Allocate by (1 value, 2 quantity, 3 weight): ").as_str() {
+        "2" => AllocationBasis::Quantity,
+        "3" => AllocationBasis::Weight,
+        _ => AllocationBasis::Value,
+    };
+    match tx.add_landed_charge(&ids, kind, Money::new(amount, &currency), basis, inv) {
+        Ok(c) => {
+            for (id, share) in c.allocations() {
+                println!("Purchase #{id}: +{:.2}", share);
+            }
+        }
+        Err(e) => println!("Error: {:?}", e),
+    }
+}
+
+fn read_document_lines(currency: &str) -> Vec<DocumentLine> {
+    let mut lines = Vec::new();
+    loop {
+        let name = prompt("Product (leave empty to finish): ");
+        if name.is_empty() {
+            break;
+        }
+        let qty: u32 = prompt("Quantity: ").parse().unwrap_or(0);
+        let unit: f64 = prompt("Unit price: ").parse().unwrap_or(0.0);
+        lines.push(DocumentLine::new(&name, qty, Money::new(unit, currency)));
+    }
+    lines
+}
+
+fn procurement_menu(inv: &mut Inventory, tx: &mut Transactions) {
+    println!("\n1) Create purchase order");
+    println!("2) Receive goods against an order");
+    println!("3) Capture supplier invoice");
+    println!("4) Dispute invoice");
+    println!("5) Set matching tolerances");
+    println!("6) Invoice matching report");
+    println!("0) Back");
+
+    match prompt("> ").as_str() {
+        "1" => {
+            let supplier = prompt("Supplier: ");
+            let base = tx.exchange_rates().base().to_string();
+            let currency = prompt(&format!("Currency (leave empty for {base}): "));
+            let currency = if currency.is_empty() { base } else { currency };
+            let lines = read_document_lines(&currency);
+            match tx.create_purchase_order(&supplier, lines, inv) {
+                Ok(po) => println!("Purchase order #{} created.", po.id()),
+                Err(e) => println!("Error: {:?}", e),
+            }
+        }
+        "2" => {
+            let po_id: u32 = prompt("Purchase order number: ").parse().unwrap_or(0);
+            let name = prompt("Product: ");
+            let qty: u32 = prompt("Quantity received: ").parse().unwrap_or(0);
+            match tx.receive_purchase_order(po_id, &name, qty, inv) {
+                Ok(p) => println!("Purchase #{}: {} x{} received.", p.id(), p.product_name(), p.quantity()),
+                Err(e) => println!("Error: {:?}", e),
+            }
+            tx.match_invoices();
+        }
+        "3" => {
+            let po_id: u32 = prompt("Purchase order number: ").parse().unwrap_or(0);
+            let number = prompt("Supplier's invoice number: ");
+            let currency = match tx.procurement().find_order(po_id).and_then(|po| po.lines().first()) {
+                Some(line) => line.unit_price().currency().to_string(),
+                None => tx.exchange_rates().base().to_string(),
+            };
+            let lines = read_document_lines(&currency);
+            match tx.capture_invoice(po_id, &number, lines) {
+                Ok(i) => println!("Invoice #{}: {:?}", i.id(), i.status()),
+                Err(e) => println!("Error: {:?}", e),
+            }
+        }
+        "4" => {
+            let id: u32 = prompt("Invoice: ").parse().unwrap_or(0);
+            let note = prompt("Note: ");
+            if let Err(e) = tx.dispute_invoice(id, &note) {
+                println!("Error: {:?}", e);
+            }
+        }
+        "5" => {
+            let qty: f64 = prompt("Quantity tolerance %: ").parse().unwrap_or(0.0);
+            let price: f64 = prompt("Price tolerance %: ").parse().unwrap_or(0.0);
+            tx.procurement_mut().set_tolerances(MatchTolerances::new(qty, price));
+            tx.match_invoices();
+            println!("Saved.");
+        }
+        "6" => println!("{}", report_invoice_matching(tx.procurement())),
+        _ => {}
+    }
+}
+
+fn accounting_menu(inv: &mut Inventory, tx: &mut Transactions) {
+    println!("\n1) Record stock count");
+    println!("2) Journal");
+    println!("3) Trial balance");
+    println!("4) Chart of accounts");
+    println!("5) Export for accounting");
+    println!("6) Costing method ({:?})", tx.costing_method());
+    println!("0) Back");
+
+    match prompt("> ").as_str() {
+        "1" => {
+            let name = prompt("Product: ");
+            let counted: u32 = match prompt("Counted quantity: ").parse() {
+                Ok(n) => n,
+                Err(_) => {
+                    println!("Invalid quantity.");
+                    return;
+                }
+            };
+            let reason = prompt("Reason (optional): ");
+            match tx.record_stock_count(&name, counted, &reason, inv) {
+                Ok(diff) => println!("Adjusted by {:+}.", diff),
+                Err(e) => println!("Error: {:?}", e),
+            }
+        }
+        "2" => println!("{}", report_journal(tx.journal(), tx.chart())),
+        "3" => println!("{}", report_trial_balance(tx.journal(), tx.chart())),
+        "4" => {
+            for (i, role) in AccountRole::ALL.iter().enumerate() {
+                let a = tx.chart().account(*role);
+                println!("{}) {} {} ({:?})", i + 1, a.code(), a.name(), role);
+            }
+            let pick: usize = prompt("Account to change (leave empty to go back): ").parse().unwrap_or(0);
+            let Some(role) = pick.checked_sub(1).and_then(|i| AccountRole::ALL.get(i)) else { return };
+            let code = prompt("Code: ");
+            let name = prompt("Name: ");
+            match tx.chart_mut().set_account(*role, &code, &name) {
+                Ok(()) => println!("Saved."),
+                Err(e) => println!("Error: {:?}", e),
+            }
+        }
+        "5" => export_accounts(tx),
+        "6" => {
+            let method = match prompt("1) Average cost  2) FIFO\n> ").as_str() {
+                "1" => CostingMethod::Average,
+                "2" => CostingMethod::Fifo,
+                _ => return,
+            };
+            tx.set_costing_method(method);
+            println!("Saved.");
+        }
+        _ => {}
+    }
+}
+
+fn export_accounts(tx: &Transactions) {
+    let (Some(from), Some(to)) = (parse_date(&prompt("From (YYYY-MM-DD): ")), parse_date(&prompt("To (YYYY-MM-DD): "))) else {
+        println!("Invalid date.");
+        return;
+    };
+    let to = to + DAY;
+    let transactions = match prompt("Export (1 journal entries, 2 daily sales & purchase summaries): ").as_str() {
+        "2" => daily_summaries(tx.journal(), from, to),
+        _ => journal_transactions(tx.journal(), from, to),
+    };
+    let format = match prompt("Format (1 CSV journal, 2 QIF, 3 OFX): ").as_str() {
+        "2" => ExportFormat::Qif,
+        "3" => ExportFormat::Ofx,
+        _ => ExportFormat::CsvJournal,
+    };
+    let register = match format {
+        ExportFormat::CsvJournal => AccountRole::Cash,
+        _ => match prompt("Account (1 cash, 2 card clearing, 3 accounts payable): ").as_str() {
+            "2" => AccountRole::CardClearing,
+            "3" => AccountRole::AccountsPayable,
+            _ => AccountRole::Cash,
+        },
+    };
+    let closing = tx.journal().balance_at(register, to);
+    let text = render_export(&transactions, tx.chart(), register, from..to, closing, tx.exchange_rates().base(), format);
+    let path = format!("accounts-{}-{}.{}", format_date(from), format_date(to - DAY), format.extension());
+    match fs::write(&path, text) {
+        Ok(()) => println!("Saved {path} ({} transactions)", transactions.len()),
+        Err(e) => println!("Error: {e}"),
+    }
+}
+
+fn sales_analysis(inv: &Inventory, tx: &Transactions, customers: &Customers) {
+    let (Some(from), Some(to)) = (parse_date(&prompt("From (YYYY-MM-DD): ")), parse_date(&prompt("To (YYYY-MM-DD): "))) else {
+        println!("Invalid date.");
+        return;
+    };
+    let period = match prompt("Group by (1 day, 2 week, 3 month): ").as_str() {
+        "2" => Period::Week,
+        "3" => Period::Month,
+        _ => Period::Day,
+    };
+    let optional = |label: &str| {
+        let value = prompt(label);
+        if value.is_empty() { None } else { Some(value) }
+    };
+    let mut filter = SalesFilter::new();
+    filter.set_product(optional("Product (leave empty for all): ").as_deref());
+    filter.set_category(optional("Category (leave empty for all): ").as_deref());
+    filter.set_cashier(optional("Cashier (leave empty for all): ").as_deref());
+    if let Some(query) = optional("Customer (leave empty for all): ") {
+        match customers.lookup(&query) {
+            Some(c) => filter.set_customer(Some(c.id())),
+            None => {
+                println!("No such customer.");
+                return;
+            }
+        }
+    }
+    println!("{}", report_sales_summary(tx, inv, from, to + DAY, period, &filter));
+}
+
+fn slow_movers(inv: &Inventory, tx: &Transactions) {
+    let days: u32 = prompt("No sales in how many days (default 90): ").parse().unwrap_or(90);
+    let slow_days: u32 = prompt("Slow above how many days of supply (default 180): ").parse().unwrap_or(180);
+    let sort = match prompt("Sort by (1 name, 2 days since sale, 3 days of supply, 4 turnover, 5 value): ").as_str() {
+        "1" => MovementSort::Name,
+        "2" => MovementSort::DaysSinceSale,
+        "3" => MovementSort::DaysOfSupply,
+        "4" => MovementSort::Turnover,
+        _ => MovementSort::Value,
+    };
+    println!("{}", report_slow_movers(tx, inv, days.max(1), slow_days, sort, clock::now()));
+}
+
+const PAGE_SIZE: usize = 20;
+
+fn read_bound<T: std::str::FromStr>(label: &str) -> Option<T> {
+    prompt(label).parse().ok()
+}
+
+/// Shows a listing a page at a time and lets the user sort and filter it.
+fn browse(render: impl Fn(&ListQuery) -> Report) {
+    let mut query = ListQuery::new();
+    query.set_page_size(Some(PAGE_SIZE));
+    loop {
+        let report = render(&query);
+        print!("{}", report.render(ReportFormat::Text));
+        let (page, pages, _) = report.paging().unwrap_or((1, 1, 0));
+        query.set_page(page);
+        match prompt("[n]ext, [p]revious, [s]ort, [f]ilter, [q]uit: ").as_str() {
+            "n" if page < pages => query.set_page(page + 1),
+            "p" if page > 1 => query.set_page(page - 1),
+            "n" | "p" => println!("No more pages."),
+            "s" => {
+                let key = match prompt("Sort by (1 name, 2 price, 3 quantity, 4 value, empty for none): ").as_str() {
+                    "1" => Some(SortKey::Name),
+                    "2" => Some(SortKey::Price),
+                    "3" => Some(SortKey::Quantity),
+                    "4" => Some(SortKey::Value),
+                    _ => None,
+                };
+                let descending = key.is_some() && prompt("Descending? (y/N): ").eq_ignore_ascii_case("y");
+                query.set_sort(key, descending);
+                query.set_page(1);
+            }
+            "f" => {
+                let text = prompt("Text (leave empty for any): ");
+                query.set_text(Some(&text));
+                query.set_price_range(read_bound("Min price: "), read_bound("Max price: "));
+                query.set_quantity_range(read_bound("Min quantity: "), read_bound("Max quantity: "));
+                query.set_page(1);
+            }
+            _ => break,
+        }
+    }
+}
+
+fn choose_report_format() -> ReportFormat {
+    match prompt("Format (1 text, 2 CSV, 3 JSON, 4 Markdown, 5 HTML): ").as_str() {
+        "2" => ReportFormat::Csv,
+        "3" => ReportFormat::Json,
+        "4" => ReportFormat::Markdown,
+        "5" => ReportFormat::Html,
+        _ => ReportFormat::Text,
+    }
+}
+
+fn save_reports(inv: &Inventory, tx: &Transactions, format: ReportFormat) {
+    let reports = [
+        ("inventory", inventory_report(inv)),
+        ("sales", sales_report(tx)),
+        ("purchases", purchases_report(tx)),
+        ("valuation", valuation_report(tx, inv, None)),
+        ("margins", margins_report(tx)),
+        ("sales-exploded", sales_exploded_report(tx)),
+        ("returns", returns_report(tx)),
+        ("backorders", backorders_report(tx)),
+        ("supplier-invoices", invoice_matching_report(tx.procurement())),
+        ("journal", journal_report(tx.journal(), tx.chart())),
+        ("trial-balance", trial_balance_report(tx.journal(), tx.chart())),
+        ("gift-cards", liability_report(tx.stored_value(), clock::now())),
+    ];
+    for (name, report) in reports {
+        let path = format!("{name}.{}", format.extension());
+        match fs::write(&path, report.render(format)) {
+            Ok(()) => println!("Saved {path}"),
+            Err(e) => println!("Error: {e}"),
+        }
+    }
+}
+
+fn store_header() -> StoreHeader {
+    StoreHeader::new("Store Inventory Management System", "", "")
+}
+
+fn reprint_receipt(tx: &Transactions) {
+    let order_id: u32 = prompt("Order number: ").parse().unwrap_or(0);
+    let format = match prompt("Format (1 text, 2 HTML, 3 PDF): ").as_str() {
+        "2" => ReceiptFormat::Html,
+        "3" => ReceiptFormat::Pdf,
+        _ => ReceiptFormat::Text,
+    };
+    match render_receipt(tx, &store_header(), order_id, format) {
+        Ok(receipt) if format == ReceiptFormat::Text => println!("{receipt}"),
+        Ok(receipt) => {
+            let path = format!("receipt-{}.{}", order_id, format.extension());
+            match fs::write(&path, receipt) {
+                Ok(()) => println!("Saved {path}"),
+                Err(e) => println!("Error: {e}"),
+            }
+        }
+        Err(e) => println!("Error: {:?}", e),
+    }
+}
+
+fn shift_menu(tx: &mut Transactions, user: &User) {
+    match tx.current_shift() {
+        Some(shift) => {
+            let at = shift.opened_at();
+            println!("\nShift #{} open since {} {:02}:{:02}", shift.id(), format_date(at), clock::hour(at), at % 3600 / 60);
+        }
+        None => println!("\nNo shift open."),
+    }
+    println!("1) Open shift");
+    println!("2) X report");
+    println!("3) Close shift (Z report)");
+    println!("0) Back");
+
+    match prompt("> ").as_str() {
+        "1" => {
+            let float: f64 = prompt("Opening float: ").parse().unwrap_or(0.0);
+            match tx.open_shift(&user.username, float) {
+                Ok(shift) => println!("Shift #{} opened.", shift.id()),
+                Err(e) => println!("Error: {:?}", e),
+            }
+        }
+        "2" => match tx.current_shift() {
+            Some(shift) => println!("{}", report_shift(tx, shift)),
+            None => println!("No shift open."),
+        },
+        "3" => {
+            if tx.current_shift().is_none() {
+                println!("No shift open.");
+                return;
+            }
+            let mut counted = Vec::new();
+            for kind in [TenderKind::Cash, TenderKind::Card] {
+                let entry = prompt(&format!("Counted {:?} (leave empty to skip): ", kind));
+                if let Ok(amount) = entry.parse::<f64>() {
+                    counted.push((kind, amount));
+                }
+            }
+            match tx.close_shift(&counted) {
+                Ok(shift) => {
+                    let id = shift.id();
+                    println!("{}", report_shift(tx, tx.find_shift(id).unwrap()));
+                }
+                Err(e) => println!("Error: {:?}", e),
+            }
+        }
+        _ => {}
+    }
+}
+
 fn prelogin() -> Option<User> {
     loop {
         println!("\n== Welcome ==");
@@ -148,12 +1019,13 @@ fn main() {
     let mut inv = Inventory::new();
     seed_inventory(&mut inv);
     let mut tx = Transactions::new();
+    let mut customers = Customers::new();
 
     // Route by role
     match user.role {
-        Role::Client  => client_menu(&mut inv, &mut tx, &user),
-        Role::Manager => manager_menu(&mut inv, &mut tx, &user, false),
-        Role::Admin   => manager_menu(&mut inv, &mut tx, &user, true),
+        Role::Client  => client_menu(&mut inv, &mut tx, &mut customers, &user),
+        Role::Manager => manager_menu(&mut inv, &mut tx, &mut customers, &user, false),
+        Role::Admin   => manager_menu(&mut inv, &mut tx, &mut customers, &user, true),
     }
 
     println!("Goodbye!");
@@ -0,0 +1,72 @@
+#![allow(dead_code)]
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TenderKind {
+    Cash,
+    Card,
+    StoreCredit,
+    GiftCard,
+    LoyaltyPoints,
+}
+
+/// A way the customer offers to pay at checkout.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tender {
+    Cash(f64),
+    Card(f64),
+    /// Store credit account number and the amount to take from it.
+    StoreCredit { number: String, amount: f64 },
+    /// Gift card number and the amount to take from it.
+    GiftCard { number: String, amount: f64 },
+    /// Points to redeem; their value comes from the loyalty rules.
+    LoyaltyPoints(u32),
+}
+
+impl Tender {
+    pub fn kind(&self) -> TenderKind {
+        match self {
+            Tender::Cash(_) => TenderKind::Cash,
+            Tender::Card(_) => TenderKind::Card,
+            Tender::StoreCredit { .. } => TenderKind::StoreCredit,
+            Tender::GiftCard { .. } => TenderKind::GiftCard,
+            Tender::LoyaltyPoints(_) => TenderKind::LoyaltyPoints,
+        }
+    }
+}
+
+/// A tender as recorded on a completed order.
+#[derive(Debug, Clone)]
+pub struct Payment {
+    kind: TenderKind,
+    /// Card number for stored-value tenders, points count for loyalty points.
+    reference: String,
+    amount: f64,
+}
+
+impl Payment {
+    pub(crate) fn new(kind: TenderKind, reference: &str, amount: f64) -> Self {
+        Self {
+            kind,
+            reference: reference.to_string(),
+            amount,
+        }
+    }
+
+    pub fn kind(&self) -> TenderKind { self.kind }
+    pub fn reference(&self) -> &str { &self.reference }
+    /// Amount tendered; for cash this includes any change handed back.
+    pub fn amount(&self) -> f64 { self.amount }
+}
+
+/// Total tendered per kind across `payments`, in `TenderKind` order.
+pub fn totals_by_kind<'a>(payments: impl IntoIterator<Item = &'a Payment>) -> Vec<(TenderKind, f64)> {
+    let mut totals: Vec<(TenderKind, f64)> = Vec::new();
+    for p in payments {
+        match totals.iter_mut().find(|(k, _)| *k == p.kind) {
+            Some((_, total)) => *total += p.amount,
+            None => totals.push((p.kind, p.amount)),
+        }
+    }
+    totals.sort_by_key(|(k, _)| *k as u8);
+    totals
+}
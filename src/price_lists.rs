@@ -0,0 +1,162 @@
+#![allow(dead_code)]
+use crate::clock::Timestamp;
+use crate::inventory::Product;
+use crate::tax;
+use crate::transactions::StoreError;
+
+/// A named set of selling prices, e.g. "Wholesale" or "Staff". A product
+/// gets its own price on the list if one is set; otherwise the list's
+/// percentage rule for its category, or for the whole list, is applied to
+/// the product's shelf price.
+pub struct PriceList {
+    name: String,
+    /// Percent added to the shelf price, e.g. -10.0 for 10% below it.
+    adjustment: f64,
+    category_adjustments: Vec<(String, f64)>,
+    prices: Vec<(String, f64)>,
+}
+
+impl PriceList {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            adjustment: 0.0,
+            category_adjustments: Vec::new(),
+            prices: Vec::new(),
+        }
+    }
+
+    pub fn name(&self) -> &str { &self.name }
+    pub fn adjustment(&self) -> f64 { self.adjustment }
+    pub fn prices(&self) -> &[(String, f64)] { &self.prices }
+
+    /// Percent added to every shelf price on this list; negative for a discount.
+    pub fn set_adjustment(&mut self, percent: f64) {
+        self.adjustment = percent.max(-100.0);
+    }
+
+    pub fn set_category_adjustment(&mut self, category: &str, percent: f64) {
+        self.category_adjustments.retain(|(c, _)| c != category);
+        self.category_adjustments.push((category.to_string(), percent.max(-100.0)));
+    }
+
+    pub fn set_price(&mut self, product_name: &str, price: f64) -> Result<(), StoreError> {
+        if price <= 0.0 {
+            return Err(StoreError::InvalidInput("Price must be > 0".into()));
+        }
+        self.prices.retain(|(p, _)| p != product_name);
+        self.prices.push((product_name.to_string(), price));
+        Ok(())
+    }
+
+    pub fn remove_price(&mut self, product_name: &str) {
+        self.prices.retain(|(p, _)| p != product_name);
+    }
+
+    /// Unit price of `product` on this list at `now`; rules apply to the
+    /// shelf price in effect then.
+    pub fn unit_price(&self, product: &Product, now: Timestamp) -> f64 {
+        if let Some((_, price)) = self.prices.iter().find(|(p, _)| p == product.name()) {
+            return *price;
+        }
+        let percent = self
+            .category_adjustments
+            .iter()
+            .find(|(c, _)| c == product.category())
+            .map_or(self.adjustment, |(_, pct)| *pct);
+        tax::round_cents(product.price_at(now) * (1.0 + percent / 100.0))
+    }
+}
+
+/// The store's price lists and which customers buy from which list. Each
+/// customer group is mapped to one list; customers outside any group pay the
+/// price given at the till.
+pub struct PriceLists {
+    lists: Vec<PriceList>,
+    groups: Vec<(String, String)>,
+    members: Vec<(u32, String)>,
+}
+
+impl Default for PriceLists {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PriceLists {
+    pub fn new() -> Self {
+        Self {
+            lists: Vec::new(),
+            groups: Vec::new(),
+            members: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, list: PriceList) -> Result<(), StoreError> {
+        if list.name.trim().is_empty() {
+            return Err(StoreError::InvalidInput("Price list needs a name".into()));
+        }
+        if self.find(&list.name).is_some() {
+            return Err(StoreError::InvalidInput(format!("Price list {} already exists", list.name)));
+        }
+        self.lists.push(list);
+        Ok(())
+    }
+
+    /// Moves every list's own price for `old` over to the product's new name.
+    pub(crate) fn rename_product(&mut self, old: &str, new: &str) {
+        for (p, _) in self.lists.iter_mut().flat_map(|l| l.prices.iter_mut()) {
+            if p == old {
+                *p = new.to_string();
+            }
+        }
+    }
+
+    /// Removes a list and unmaps any groups that used it.
+    pub fn remove(&mut self, name: &str) {
+        self.lists.retain(|l| l.name != name);
+        self.groups.retain(|(_, l)| l != name);
+    }
+
+    pub fn all(&self) -> &[PriceList] {
+        &self.lists
+    }
+
+    pub fn find(&self, name: &str) -> Option<&PriceList> {
+        self.lists.iter().find(|l| l.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn find_mut(&mut self, name: &str) -> Option<&mut PriceList> {
+        self.lists.iter_mut().find(|l| l.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Makes customers in `group` buy from the list called `list_name`.
+    pub fn set_group_list(&mut self, group: &str, list_name: &str) -> Result<(), StoreError> {
+        let Some(list) = self.find(list_name) else {
+            return Err(StoreError::NotFound(format!("price list {list_name}")));
+        };
+        let list_name = list.name.clone();
+        self.groups.retain(|(g, _)| g != group);
+        self.groups.push((group.to_string(), list_name));
+        Ok(())
+    }
+
+    /// Puts a customer in `group`, replacing any group they were in.
+    pub fn assign(&mut self, customer_id: u32, group: &str) {
+        self.members.retain(|(c, _)| *c != customer_id);
+        if !group.is_empty() {
+            self.members.push((customer_id, group.to_string()));
+        }
+    }
+
+    pub fn group_of(&self, customer_id: u32) -> Option<&str> {
+        self.members.iter().find(|(c, _)| *c == customer_id).map(|(_, g)| g.as_str())
+    }
+
+    /// The list a customer buys from, through their group.
+    pub fn list_for(&self, customer_id: u32) -> Option<&PriceList> {
+        let group = self.group_of(customer_id)?;
+        let (_, list) = self.groups.iter().find(|(g, _)| g == group)?;
+        self.find(list)
+    }
+}
@@ -0,0 +1,319 @@
+#![allow(dead_code)]
+use crate::clock::Timestamp;
+use crate::currency::Money;
+use crate::report::{Report, ReportFormat, Value};
+
+/// A product, quantity and agreed unit price on an order or invoice.
+#[derive(Debug, Clone)]
+pub struct DocumentLine {
+    product_name: String,
+    quantity: u32,
+    unit_price: Money,
+}
+
+impl DocumentLine {
+    pub fn new(product_name: &str, quantity: u32, unit_price: Money) -> Self {
+        Self {
+            product_name: product_name.to_string(),
+            quantity,
+            unit_price,
+        }
+    }
+
+    pub fn product_name(&self) -> &str { &self.product_name }
+    pub fn quantity(&self) -> u32 { self.quantity }
+    pub fn unit_price(&self) -> &Money { &self.unit_price }
+}
+
+/// What we asked a supplier for. Deliveries against it are recorded as
+/// `Purchase`s carrying the order's id.
+pub struct PurchaseOrder {
+    id: u32,
+    supplier: String,
+    lines: Vec<DocumentLine>,
+    created_at: Timestamp,
+}
+
+impl PurchaseOrder {
+    pub(crate) fn new(id: u32, supplier: &str, lines: Vec<DocumentLine>, created_at: Timestamp) -> Self {
+        Self {
+            id,
+            supplier: supplier.to_string(),
+            lines,
+            created_at,
+        }
+    }
+
+    pub fn id(&self) -> u32 { self.id }
+    pub fn supplier(&self) -> &str { &self.supplier }
+    pub fn lines(&self) -> &[DocumentLine] { &self.lines }
+    pub fn created_at(&self) -> Timestamp { self.created_at }
+
+    pub fn line(&self, product_name: &str) -> Option<&DocumentLine> {
+        self.lines.iter().find(|l| l.product_name == product_name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InvoiceStatus {
+    /// Agrees with the order and what was received; fine to pay.
+    Matched,
+    /// Has discrepancies; held until they clear (e.g. goods arrive) or the
+    /// bookkeeper disputes it.
+    OnHold,
+    /// Raised with the supplier; left alone by re-matching.
+    Disputed,
+}
+
+/// Why an invoice line does not match.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Discrepancy {
+    /// Invoiced for a product that is not on the order.
+    NotOrdered { product: String },
+    /// More invoiced (over all invoices for the order) than was ordered.
+    OverOrdered { product: String, ordered: u32, invoiced: u32 },
+    /// More invoiced than has been received so far.
+    NotReceived { product: String, received: u32, invoiced: u32 },
+    /// Unit price differs from the order by more than the tolerance.
+    Price { product: String, ordered: Money, invoiced: Money },
+}
+
+/// How far an invoice may stray from the order before it is held, as
+/// percentages of the ordered quantity and unit price.
+#[derive(Debug, Clone, Copy)]
+pub struct MatchTolerances {
+    quantity_percent: f64,
+    price_percent: f64,
+}
+
+impl Default for MatchTolerances {
+    /// Exact match on both.
+    fn default() -> Self {
+        Self::new(0.0, 0.0)
+    }
+}
+
+impl MatchTolerances {
+    pub fn new(quantity_percent: f64, price_percent: f64) -> Self {
+        Self {
+            quantity_percent: quantity_percent.max(0.0),
+            price_percent: price_percent.max(0.0),
+        }
+    }
+
+    pub fn quantity_percent(&self) -> f64 { self.quantity_percent }
+    pub fn price_percent(&self) -> f64 { self.price_percent }
+
+    fn quantity_ok(&self, actual: u32, limit: u32) -> bool {
+        actual as f64 <= limit as f64 * (1.0 + self.quantity_percent / 100.0) + 1e-9
+    }
+
+    fn price_ok(&self, invoiced: &Money, ordered: &Money) -> bool {
+        invoiced.currency() == ordered.currency()
+            && (invoiced.amount() - ordered.amount()).abs() <= ordered.amount() * self.price_percent / 100.0 + 1e-9
+    }
+}
+
+/// A bill from a supplier, captured against one purchase order.
+pub struct SupplierInvoice {
+    id: u32,
+    number: String,
+    po_id: u32,
+    lines: Vec<DocumentLine>,
+    status: InvoiceStatus,
+    discrepancies: Vec<Discrepancy>,
+    note: String,
+}
+
+impl SupplierInvoice {
+    pub(crate) fn new(id: u32, number: &str, po_id: u32, lines: Vec<DocumentLine>) -> Self {
+        Self {
+            id,
+            number: number.to_string(),
+            po_id,
+            lines,
+            status: InvoiceStatus::OnHold,
+            discrepancies: Vec::new(),
+            note: String::new(),
+        }
+    }
+
+    pub fn id(&self) -> u32 { self.id }
+    /// The supplier's own invoice number.
+    pub fn number(&self) -> &str { &self.number }
+    pub fn po_id(&self) -> u32 { self.po_id }
+    pub fn lines(&self) -> &[DocumentLine] { &self.lines }
+    pub fn status(&self) -> InvoiceStatus { self.status }
+    pub fn discrepancies(&self) -> &[Discrepancy] { &self.discrepancies }
+    /// Why the invoice was disputed.
+    pub fn note(&self) -> &str { &self.note }
+
+    pub fn total(&self) -> Option<Money> {
+        let currency = self.lines.first()?.unit_price.currency();
+        let amount = self.lines.iter().map(|l| l.quantity as f64 * l.unit_price.amount()).sum();
+        Some(Money::new(amount, currency))
+    }
+
+    pub(crate) fn set_result(&mut self, discrepancies: Vec<Discrepancy>) {
+        self.status = if discrepancies.is_empty() { InvoiceStatus::Matched } else { InvoiceStatus::OnHold };
+        self.discrepancies = discrepancies;
+    }
+
+    pub(crate) fn dispute(&mut self, note: &str) {
+        self.status = InvoiceStatus::Disputed;
+        self.note = note.to_string();
+    }
+}
+
+/// Three-way match of one invoice: each line against the order line and the
+/// quantity received so far. `invoiced_before` is what earlier invoices for
+/// the same order already billed for a product.
+pub fn match_invoice(
+    invoice: &SupplierInvoice,
+    po: &PurchaseOrder,
+    received: impl Fn(&str) -> u32,
+    invoiced_before: impl Fn(&str) -> u32,
+    tolerances: &MatchTolerances,
+) -> Vec<Discrepancy> {
+    let mut found = Vec::new();
+    for line in &invoice.lines {
+        let product = line.product_name.clone();
+        let Some(ordered) = po.line(&line.product_name) else {
+            found.push(Discrepancy::NotOrdered { product });
+            continue;
+        };
+        let invoiced = invoiced_before(&line.product_name) + line.quantity;
+        if !tolerances.quantity_ok(invoiced, ordered.quantity) {
+            found.push(Discrepancy::OverOrdered {
+                product: product.clone(),
+                ordered: ordered.quantity,
+                invoiced,
+            });
+        }
+        let received = received(&line.product_name);
+        if !tolerances.quantity_ok(invoiced, received) {
+            found.push(Discrepancy::NotReceived {
+                product: product.clone(),
+                received,
+                invoiced,
+            });
+        }
+        if !tolerances.price_ok(&line.unit_price, &ordered.unit_price) {
+            found.push(Discrepancy::Price {
+                product,
+                ordered: ordered.unit_price.clone(),
+                invoiced: line.unit_price.clone(),
+            });
+        }
+    }
+    found
+}
+
+/// Purchase orders, supplier invoices and the matching tolerances.
+pub struct Procurement {
+    orders: Vec<PurchaseOrder>,
+    invoices: Vec<SupplierInvoice>,
+    tolerances: MatchTolerances,
+}
+
+impl Default for Procurement {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Procurement {
+    pub fn new() -> Self {
+        Self {
+            orders: Vec::new(),
+            invoices: Vec::new(),
+            tolerances: MatchTolerances::default(),
+        }
+    }
+
+    pub fn tolerances(&self) -> &MatchTolerances { &self.tolerances }
+
+    /// Points purchase order and invoice lines for `old` at its new name.
+    pub(crate) fn rename_product(&mut self, old: &str, new: &str) {
+        let orders = self.orders.iter_mut().flat_map(|o| o.lines.iter_mut());
+        let invoices = self.invoices.iter_mut().flat_map(|i| i.lines.iter_mut());
+        for line in orders.chain(invoices).filter(|l| l.product_name == old) {
+            line.product_name = new.to_string();
+        }
+        for d in self.invoices.iter_mut().flat_map(|i| i.discrepancies.iter_mut()) {
+            let (Discrepancy::NotOrdered { product }
+            | Discrepancy::OverOrdered { product, .. }
+            | Discrepancy::NotReceived { product, .. }
+            | Discrepancy::Price { product, .. }) = d;
+            if product == old {
+                *product = new.to_string();
+            }
+        }
+    }
+
+    /// New tolerances apply the next time invoices are matched.
+    pub fn set_tolerances(&mut self, tolerances: MatchTolerances) {
+        self.tolerances = tolerances;
+    }
+
+    pub fn orders(&self) -> &[PurchaseOrder] { &self.orders }
+    pub fn invoices(&self) -> &[SupplierInvoice] { &self.invoices }
+
+    pub fn find_order(&self, id: u32) -> Option<&PurchaseOrder> {
+        self.orders.iter().find(|o| o.id == id)
+    }
+
+    pub fn find_invoice(&self, id: u32) -> Option<&SupplierInvoice> {
+        self.invoices.iter().find(|i| i.id == id)
+    }
+
+    pub(crate) fn push_order(&mut self, order: PurchaseOrder) -> &PurchaseOrder {
+        self.orders.push(order);
+        self.orders.last().unwrap()
+    }
+
+    pub(crate) fn push_invoice(&mut self, invoice: SupplierInvoice) {
+        self.invoices.push(invoice);
+    }
+
+    pub(crate) fn invoices_mut(&mut self) -> &mut [SupplierInvoice] {
+        &mut self.invoices
+    }
+}
+
+pub fn invoice_matching_report(procurement: &Procurement) -> Report {
+    let mut report = Report::new("Supplier invoices", &["Inv", "Supplier no.", "PO", "Total", "Status"]);
+    for i in procurement.invoices() {
+        let total = i.total().map(|t| t.to_string()).unwrap_or_default();
+        report.add_row(vec![
+            Value::Integer(i.id as i64),
+            Value::text(&i.number),
+            Value::Integer(i.po_id as i64),
+            Value::Text(total),
+            Value::Text(format!("{:?}", i.status)),
+        ]);
+        for d in &i.discrepancies {
+            let text = match d {
+                Discrepancy::NotOrdered { product } => format!("{product}: not on the order"),
+                Discrepancy::OverOrdered { product, ordered, invoiced } => {
+                    format!("{product}: invoiced {invoiced}, ordered {ordered}")
+                }
+                Discrepancy::NotReceived { product, received, invoiced } => {
+                    format!("{product}: invoiced {invoiced}, received {received}")
+                }
+                Discrepancy::Price { product, ordered, invoiced } => {
+                    format!("{product}: price {invoiced}, ordered at {ordered}")
+                }
+            };
+            report.add_row(vec![Value::Empty, Value::Text(format!("  - {text}"))]);
+        }
+        if !i.note.is_empty() {
+            report.add_row(vec![Value::Empty, Value::Text(format!("  Dispute: {}", i.note))]);
+        }
+    }
+    report
+}
+
+pub fn report_invoice_matching(procurement: &Procurement) -> String {
+    invoice_matching_report(procurement).render(ReportFormat::Text)
+}
@@ -0,0 +1,206 @@
+#![allow(dead_code)]
+use crate::clock::Timestamp;
+use crate::transactions::StoreError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PromotionKind {
+    /// Percentage off the line, e.g. 15.0 for 15% off.
+    PercentOff(f64),
+    /// Fixed amount off every unit.
+    AmountOff(f64),
+    /// For every `buy` units paid, `get` more are free.
+    BuyXGetY { buy: u32, get: u32 },
+    /// `quantity` units for a fixed `price`, e.g. 3 for 20.
+    MultiBuy { quantity: u32, price: f64 },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PromotionTarget {
+    All,
+    Product(String),
+    Category(String),
+}
+
+pub struct Promotion {
+    name: String,
+    kind: PromotionKind,
+    target: PromotionTarget,
+    starts_at: Option<Timestamp>,
+    ends_at: Option<Timestamp>,
+    coupon: Option<String>,
+}
+
+impl Promotion {
+    pub fn new(name: &str, kind: PromotionKind, target: PromotionTarget) -> Self {
+        Self {
+            name: name.to_string(),
+            kind,
+            target,
+            starts_at: None,
+            ends_at: None,
+            coupon: None,
+        }
+    }
+
+    pub fn name(&self) -> &str { &self.name }
+    pub fn kind(&self) -> &PromotionKind { &self.kind }
+    pub fn target(&self) -> &PromotionTarget { &self.target }
+    pub fn coupon(&self) -> Option<&str> { self.coupon.as_deref() }
+
+    /// Limits the promotion to `[starts_at, ends_at)`; `None` leaves that side open.
+    pub fn set_window(&mut self, starts_at: Option<Timestamp>, ends_at: Option<Timestamp>) {
+        self.starts_at = starts_at;
+        self.ends_at = ends_at;
+    }
+
+    /// Makes the promotion apply only when the coupon code is presented.
+    pub fn set_coupon(&mut self, code: &str) {
+        self.coupon = Some(code.to_uppercase());
+    }
+
+    pub fn is_active(&self, now: Timestamp) -> bool {
+        self.starts_at.is_none_or(|s| s <= now) && self.ends_at.is_none_or(|e| now < e)
+    }
+
+    fn targets(&self, product_name: &str, category: &str) -> bool {
+        match &self.target {
+            PromotionTarget::All => true,
+            PromotionTarget::Product(p) => p == product_name,
+            PromotionTarget::Category(c) => c == category,
+        }
+    }
+
+    /// Discount this promotion gives on `quantity` units at `unit_price`.
+    pub fn discount(&self, quantity: u32, unit_price: f64) -> f64 {
+        let gross = quantity as f64 * unit_price;
+        let discount = match self.kind {
+            PromotionKind::PercentOff(pct) => gross * pct / 100.0,
+            PromotionKind::AmountOff(amount) => quantity as f64 * amount.min(unit_price),
+            PromotionKind::BuyXGetY { buy, get } => {
+                let free = quantity / (buy + get) * get;
+                free as f64 * unit_price
+            }
+            PromotionKind::MultiBuy { quantity: n, price } => {
+                let groups = quantity / n;
+                groups as f64 * (n as f64 * unit_price - price).max(0.0)
+            }
+        };
+        discount.clamp(0.0, gross)
+    }
+}
+
+/// A promotion applied to a sale line and the amount it took off.
+#[derive(Debug, Clone)]
+pub struct AppliedPromotion {
+    name: String,
+    discount: f64,
+}
+
+impl AppliedPromotion {
+    pub fn name(&self) -> &str { &self.name }
+    pub fn discount(&self) -> f64 { self.discount }
+}
+
+pub struct Promotions {
+    promotions: Vec<Promotion>,
+}
+
+impl Default for Promotions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Promotions {
+    pub fn new() -> Self {
+        Self { promotions: Vec::new() }
+    }
+
+    /// Points promotions on the product `old` at its new name.
+    pub(crate) fn rename_product(&mut self, old: &str, new: &str) {
+        for p in self.promotions.iter_mut() {
+            if let PromotionTarget::Product(name) = &mut p.target
+                && name == old
+            {
+                *name = new.to_string();
+            }
+        }
+    }
+
+    pub fn add(&mut self, promotion: Promotion) -> Result<(), StoreError> {
+        let valid = match promotion.kind {
+            PromotionKind::PercentOff(pct) => pct > 0.0 && pct <= 100.0,
+            PromotionKind::AmountOff(amount) => amount > 0.0,
+            PromotionKind::BuyXGetY { buy, get } => buy > 0 && get > 0,
+            PromotionKind::MultiBuy { quantity, price } => quantity >= 2 && price > 0.0,
+        };
+        if !valid {
+            return Err(StoreError::InvalidInput(format!("Invalid terms for promotion {}", promotion.name)));
+        }
+        if let Some(code) = &promotion.coupon
+            && self.promotions.iter().any(|p| p.coupon.as_ref() == Some(code))
+        {
+            return Err(StoreError::InvalidInput(format!("Coupon {code} already exists")));
+        }
+        self.promotions.push(promotion);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.promotions.retain(|p| p.name != name);
+    }
+
+    pub fn all(&self) -> &[Promotion] {
+        &self.promotions
+    }
+
+    pub fn is_coupon(&self, code: &str) -> bool {
+        let code = code.to_uppercase();
+        self.promotions.iter().any(|p| p.coupon.as_ref() == Some(&code))
+    }
+
+    /// Works out the promotions for one sale line. Of the automatic
+    /// promotions only the one giving the biggest discount applies (the
+    /// earliest defined wins a tie); matching coupons then apply in the order
+    /// they were defined, each on what is left of the line.
+    pub fn apply(
+        &self,
+        product_name: &str,
+        category: &str,
+        quantity: u32,
+        unit_price: f64,
+        now: Timestamp,
+        coupons: &[String],
+    ) -> Vec<AppliedPromotion> {
+        let eligible = |p: &&Promotion| p.is_active(now) && p.targets(product_name, category);
+
+        let mut applied = Vec::new();
+        let mut remaining = quantity as f64 * unit_price;
+
+        let mut best: Option<(&Promotion, f64)> = None;
+        for p in self.promotions.iter().filter(eligible).filter(|p| p.coupon.is_none()) {
+            let d = p.discount(quantity, unit_price);
+            if d > 0.0 && best.is_none_or(|(_, b)| d > b) {
+                best = Some((p, d));
+            }
+        }
+        if let Some((p, d)) = best {
+            remaining -= d;
+            applied.push(AppliedPromotion { name: p.name.clone(), discount: d });
+        }
+
+        for p in self.promotions.iter().filter(eligible) {
+            let Some(code) = &p.coupon else { continue };
+            if !coupons.iter().any(|c| c.to_uppercase() == *code) {
+                continue;
+            }
+            // coupons work on the already-discounted unit price
+            let d = p.discount(quantity, remaining / quantity as f64).min(remaining);
+            if d > 0.0 {
+                remaining -= d;
+                applied.push(AppliedPromotion { name: p.name.clone(), discount: d });
+            }
+        }
+        applied
+    }
+}
@@ -0,0 +1,147 @@
+#![allow(dead_code)]
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortKey {
+    Name,
+    Price,
+    Quantity,
+    /// Price times quantity: stock value, or a sale's or purchase's total.
+    Value,
+}
+
+/// What a listing can be sorted and filtered on, for one row.
+pub struct ListKeys<'a> {
+    pub name: &'a str,
+    /// Searched by the text filter, along with the name.
+    pub text: &'a str,
+    /// `None` for rows without a price of their own, such as parent products.
+    pub price: Option<f64>,
+    pub quantity: u32,
+    pub value: f64,
+}
+
+/// Sorting, filtering and paging for a listing. The default lists every
+/// row in its natural order on one page.
+#[derive(Debug, Clone, Default)]
+pub struct ListQuery {
+    sort: Option<(SortKey, bool)>,
+    min_price: Option<f64>,
+    max_price: Option<f64>,
+    min_quantity: Option<u32>,
+    max_quantity: Option<u32>,
+    text: Option<String>,
+    page_size: Option<usize>,
+    page: usize,
+}
+
+impl ListQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn sort(&self) -> Option<(SortKey, bool)> { self.sort }
+    pub fn text(&self) -> Option<&str> { self.text.as_deref() }
+    pub fn page_size(&self) -> Option<usize> { self.page_size }
+    /// The page asked for, counting from 1.
+    pub fn page(&self) -> usize { self.page.max(1) }
+
+    pub fn set_sort(&mut self, key: Option<SortKey>, descending: bool) {
+        self.sort = key.map(|k| (k, descending));
+    }
+
+    /// Rows without a price are left out while either bound is set.
+    pub fn set_price_range(&mut self, min: Option<f64>, max: Option<f64>) {
+        self.min_price = min;
+        self.max_price = max;
+    }
+
+    pub fn set_quantity_range(&mut self, min: Option<u32>, max: Option<u32>) {
+        self.min_quantity = min;
+        self.max_quantity = max;
+    }
+
+    /// Case-insensitive search in the name and description.
+    pub fn set_text(&mut self, text: Option<&str>) {
+        self.text = text.map(|t| t.trim().to_lowercase()).filter(|t| !t.is_empty());
+    }
+
+    /// Splits the listing into pages of `size` rows; `None` shows all rows.
+    pub fn set_page_size(&mut self, size: Option<usize>) {
+        self.page_size = size.filter(|s| *s > 0);
+        self.page = 1;
+    }
+
+    pub fn set_page(&mut self, page: usize) {
+        self.page = page.max(1);
+    }
+
+    pub fn is_sorted(&self) -> bool {
+        self.sort.is_some()
+    }
+
+    fn matches(&self, keys: &ListKeys) -> bool {
+        let price_ok = match keys.price {
+            Some(p) => self.min_price.is_none_or(|m| p >= m) && self.max_price.is_none_or(|m| p <= m),
+            None => self.min_price.is_none() && self.max_price.is_none(),
+        };
+        price_ok
+            && self.min_quantity.is_none_or(|m| keys.quantity >= m)
+            && self.max_quantity.is_none_or(|m| keys.quantity <= m)
+            && self.text.as_ref().is_none_or(|t| {
+                keys.name.to_lowercase().contains(t) || keys.text.to_lowercase().contains(t)
+            })
+    }
+
+    /// Filters, sorts and pages `items`. Sorting is stable, so rows that tie
+    /// keep their natural order.
+    pub fn apply<T>(&self, items: Vec<T>, keys: impl Fn(&T) -> ListKeys) -> Page<T> {
+        let mut items: Vec<T> = items.into_iter().filter(|i| self.matches(&keys(i))).collect();
+        if let Some((key, descending)) = self.sort {
+            items.sort_by(|a, b| {
+                let (a, b) = (keys(a), keys(b));
+                let order = match key {
+                    SortKey::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+                    SortKey::Price => a.price.unwrap_or(0.0).total_cmp(&b.price.unwrap_or(0.0)),
+                    SortKey::Quantity => a.quantity.cmp(&b.quantity),
+                    SortKey::Value => a.value.total_cmp(&b.value),
+                };
+                if descending { order.reverse() } else { order }
+            });
+        }
+
+        let total = items.len();
+        let Some(size) = self.page_size else {
+            return Page {
+                items,
+                page: 1,
+                pages: 1,
+                total,
+            };
+        };
+        let pages = total.div_ceil(size).max(1);
+        let page = self.page().min(pages);
+        let items = items.into_iter().skip((page - 1) * size).take(size).collect();
+        Page { items, page, pages, total }
+    }
+}
+
+/// One page of a listing.
+#[derive(Debug)]
+pub struct Page<T> {
+    items: Vec<T>,
+    page: usize,
+    pages: usize,
+    /// Rows that passed the filters, over all pages.
+    total: usize,
+}
+
+impl<T> Page<T> {
+    pub fn items(&self) -> &[T] { &self.items }
+    pub fn page(&self) -> usize { self.page }
+    pub fn pages(&self) -> usize { self.pages }
+    pub fn total(&self) -> usize { self.total }
+
+    pub fn into_items(self) -> Vec<T> {
+        self.items
+    }
+}
@@ -0,0 +1,259 @@
+#![allow(dead_code)]
+use crate::escape::escape_html;
+use crate::payments::TenderKind;
+use crate::tax::PricingMode;
+use crate::transactions::{Order, StoreError, Transactions};
+
+/// Width of a printed text receipt, in characters.
+const RECEIPT_WIDTH: usize = 40;
+
+/// Store details printed at the top of every receipt.
+pub struct StoreHeader {
+    name: String,
+    address: String,
+    phone: String,
+}
+
+impl StoreHeader {
+    pub fn new(name: &str, address: &str, phone: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            address: address.to_string(),
+            phone: phone.to_string(),
+        }
+    }
+
+    pub fn name(&self) -> &str { &self.name }
+    pub fn address(&self) -> &str { &self.address }
+    pub fn phone(&self) -> &str { &self.phone }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReceiptFormat {
+    Text,
+    Html,
+    Pdf,
+}
+
+impl ReceiptFormat {
+    /// File extension used when a receipt is saved to disk.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ReceiptFormat::Text => "txt",
+            ReceiptFormat::Html => "html",
+            ReceiptFormat::Pdf => "pdf",
+        }
+    }
+}
+
+/// One printed row: a label with an optional amount on the right.
+enum Row {
+    Center(String),
+    Rule,
+    Item(String, Option<f64>),
+    Total(String, f64),
+}
+
+/// Renders the receipt for `order_id`. Orders are kept, so any receipt can be
+/// printed again later. PDF output is plain ASCII and can be written to a
+/// file as-is.
+pub fn render_receipt(
+    tx: &Transactions,
+    header: &StoreHeader,
+    order_id: u32,
+    format: ReceiptFormat,
+) -> Result<String, StoreError> {
+    let Some(order) = tx.find_order(order_id) else {
+        return Err(StoreError::NotFound(format!("order #{order_id}")));
+    };
+    let rows = receipt_rows(tx, header, order);
+    Ok(match format {
+        ReceiptFormat::Text => text(&rows),
+        ReceiptFormat::Html => html(&rows, order.id()),
+        ReceiptFormat::Pdf => pdf(&text(&rows).lines().collect::<Vec<_>>()),
+    })
+}
+
+fn receipt_rows(tx: &Transactions, header: &StoreHeader, order: &Order) -> Vec<Row> {
+    let mut rows = vec![Row::Center(header.name.clone())];
+    for extra in [&header.address, &header.phone] {
+        if !extra.is_empty() {
+            rows.push(Row::Center(extra.clone()));
+        }
+    }
+    rows.push(Row::Rule);
+    rows.push(Row::Item(format!("Order #{}", order.id()), None));
+    if let Some(customer_id) = order.customer_id() {
+        rows.push(Row::Item(format!("Customer #{customer_id}"), None));
+    }
+    if let Some(list) = tx.order_lines(order.id()).iter().find_map(|s| s.price_list()) {
+        rows.push(Row::Item(format!("Prices: {list}"), None));
+    }
+    rows.push(Row::Rule);
+
+    let mut subtotal = 0.0;
+    for s in tx.order_lines(order.id()) {
+        rows.push(Row::Item(
+            format!("{} x{} @ {:.2}", s.product_name(), s.quantity(), s.unit_price()),
+            Some(s.quantity() as f64 * s.unit_price()),
+        ));
+        for d in s.discounts() {
+            rows.push(Row::Item(format!("  {}", d.name()), Some(-d.discount())));
+        }
+        subtotal += s.total_price();
+    }
+    for number in order.gift_cards() {
+        let amount = tx.stored_value().find(number).map_or(0.0, |c| c.issued_amount());
+        rows.push(Row::Item(format!("Gift card {number}"), Some(amount)));
+        subtotal += amount;
+    }
+    rows.push(Row::Rule);
+    rows.push(Row::Item("Subtotal".into(), Some(subtotal)));
+
+    let inclusive = tx.tax_settings().mode() == PricingMode::TaxInclusive;
+    for l in order.tax_lines().iter().filter(|l| l.tax() != 0.0) {
+        let label = if inclusive { "incl. tax" } else { "Tax" };
+        rows.push(Row::Item(format!("{label} {:.1}% on {:.2}", l.rate() * 100.0, l.net()), Some(l.tax())));
+    }
+    rows.push(Row::Total("TOTAL".into(), order.total()));
+
+    for p in order.payments() {
+        let label = match p.kind() {
+            TenderKind::LoyaltyPoints => format!("Points ({})", p.reference()),
+            kind if p.reference().is_empty() => format!("{:?}", kind),
+            kind => format!("{:?} {}", kind, p.reference()),
+        };
+        rows.push(Row::Item(label, Some(p.amount())));
+    }
+    if order.change() > 0.0 {
+        rows.push(Row::Item("Change".into(), Some(order.change())));
+    }
+    rows.push(Row::Rule);
+    rows.push(Row::Center("Thank you!".into()));
+    rows
+}
+
+/// Label on the left, amount right-aligned; long labels are cut so the
+/// amount always fits on the line.
+fn text_line(label: &str, amount: Option<f64>) -> String {
+    let Some(amount) = amount else {
+        return label.chars().take(RECEIPT_WIDTH).collect();
+    };
+    let amount = format!("{:.2}", amount);
+    let room = RECEIPT_WIDTH - amount.len() - 1;
+    let label: String = label.chars().take(room).collect();
+    format!("{:<room$} {}", label, amount)
+}
+
+fn text(rows: &[Row]) -> String {
+    let mut out = String::new();
+    for row in rows {
+        let line = match row {
+            Row::Center(s) => format!("{:^RECEIPT_WIDTH$}", s).trim_end().to_string(),
+            Row::Rule => "-".repeat(RECEIPT_WIDTH),
+            Row::Item(label, amount) => text_line(label, *amount),
+            Row::Total(label, amount) => text_line(label, Some(*amount)),
+        };
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out
+}
+
+fn html(rows: &[Row], order_id: u32) -> String {
+    let mut out = String::new();
+    out.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    out.push_str(&format!("<title>Receipt #{order_id}</title>\n"));
+    out.push_str("<style>body{font-family:monospace;width:24em} td.amount{text-align:right} .center{text-align:center} .total{font-weight:bold}</style>\n");
+    out.push_str("</head>\n<body>\n<table>\n");
+    for row in rows {
+        let line = match row {
+            Row::Center(s) => format!("<tr><td colspan=\"2\" class=\"center\">{}</td></tr>", escape_html(s)),
+            Row::Rule => String::from("<tr><td colspan=\"2\"><hr></td></tr>"),
+            Row::Item(label, None) => format!("<tr><td colspan=\"2\">{}</td></tr>", escape_html(label)),
+            Row::Item(label, Some(amount)) => format!(
+                "<tr><td>{}</td><td class=\"amount\">{:.2}</td></tr>",
+                escape_html(label),
+                amount
+            ),
+            Row::Total(label, amount) => format!(
+                "<tr class=\"total\"><td>{}</td><td class=\"amount\">{:.2}</td></tr>",
+                escape_html(label),
+                amount
+            ),
+        };
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out.push_str("</table>\n</body>\n</html>\n");
+    out
+}
+
+/// Lines of monospaced text laid out on A4 pages as a minimal PDF 1.4
+/// document using the built-in Courier font.
+pub fn pdf(lines: &[&str]) -> String {
+    const LINES_PER_PAGE: usize = 64;
+    const FONT_SIZE: u32 = 10;
+    const LEADING: u32 = 12;
+
+    let pages: Vec<&[&str]> = if lines.is_empty() {
+        vec![&[]]
+    } else {
+        lines.chunks(LINES_PER_PAGE).collect()
+    };
+
+    // 1 catalog, 2 page tree, 3 font, then a page and its content stream per page
+    let mut objects: Vec<String> = Vec::new();
+    objects.push(String::from("<< /Type /Catalog /Pages 2 0 R >>"));
+    let kids: Vec<String> = (0..pages.len()).map(|i| format!("{} 0 R", 4 + i * 2)).collect();
+    objects.push(format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), pages.len()));
+    objects.push(String::from("<< /Type /Font /Subtype /Type1 /BaseFont /Courier >>"));
+
+    for (i, page) in pages.iter().enumerate() {
+        let mut stream = format!("BT\n/F1 {FONT_SIZE} Tf\n{LEADING} TL\n40 800 Td\n");
+        for line in page.iter() {
+            stream.push_str(&format!("({}) '\n", escape_pdf(line)));
+        }
+        stream.push_str("ET");
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 595 842] /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+            5 + i * 2
+        ));
+        objects.push(format!("<< /Length {} >>\nstream\n{}\nendstream", stream.len(), stream));
+    }
+
+    let mut out = String::from("%PDF-1.4\n");
+    let mut offsets = Vec::new();
+    for (i, obj) in objects.iter().enumerate() {
+        offsets.push(out.len());
+        out.push_str(&format!("{} 0 obj\n{}\nendobj\n", i + 1, obj));
+    }
+    let xref = out.len();
+    out.push_str(&format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1));
+    for off in offsets {
+        out.push_str(&format!("{:010} 00000 n \n", off));
+    }
+    out.push_str(&format!(
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        xref
+    ));
+    out
+}
+
+/// Escapes a line for a PDF string literal. Courier only covers ASCII, so
+/// anything else prints as '?'.
+fn escape_pdf(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        match c {
+            '(' | ')' | '\\' => {
+                out.push('\\');
+                out.push(c);
+            }
+            c if c.is_ascii() && !c.is_ascii_control() => out.push(c),
+            _ => out.push('?'),
+        }
+    }
+    out
+}
@@ -0,0 +1,375 @@
+#![allow(dead_code)]
+use crate::escape::{csv_field, escape_html};
+
+/// One cell of a report. Numbers keep their type so that CSV and JSON get
+/// plain numbers and the text layout can right-align them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Text(String),
+    Integer(i64),
+    /// An amount, shown with two decimals.
+    Money(f64),
+    /// A percentage, e.g. 20.0 for 20%.
+    Percent(f64),
+    Empty,
+}
+
+impl Value {
+    pub fn text(s: &str) -> Self {
+        Value::Text(s.to_string())
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, Value::Integer(_) | Value::Money(_) | Value::Percent(_))
+    }
+
+    /// The value as shown to people.
+    pub fn display(&self) -> String {
+        match self {
+            Value::Text(s) => s.clone(),
+            Value::Integer(n) => n.to_string(),
+            Value::Money(m) => format!("{m:.2}"),
+            Value::Percent(p) => format!("{p:.1}%"),
+            Value::Empty => String::new(),
+        }
+    }
+
+    /// The value as a bare number or string, for machine-readable formats.
+    fn raw(&self) -> String {
+        match self {
+            Value::Money(m) => format!("{m:.2}"),
+            Value::Percent(p) => format!("{p:.1}"),
+            other => other.display(),
+        }
+    }
+
+    fn json(&self) -> String {
+        match self {
+            Value::Text(s) => json_string(s),
+            Value::Empty => String::from("null"),
+            other => other.raw(),
+        }
+    }
+}
+
+/// A titled table: columns, rows of values, summary totals, and further
+/// tables shown after it (e.g. tax by rate under the sales list).
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    title: String,
+    columns: Vec<String>,
+    rows: Vec<Vec<Value>>,
+    totals: Vec<(String, Value)>,
+    sections: Vec<Report>,
+    /// Page shown, page count and rows over all pages, for a paged listing.
+    paging: Option<(usize, usize, usize)>,
+}
+
+impl Report {
+    pub fn new(title: &str, columns: &[&str]) -> Self {
+        Self {
+            title: title.to_string(),
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            ..Self::default()
+        }
+    }
+
+    pub fn title(&self) -> &str { &self.title }
+    pub fn columns(&self) -> &[String] { &self.columns }
+    pub fn rows(&self) -> &[Vec<Value>] { &self.rows }
+    pub fn totals(&self) -> &[(String, Value)] { &self.totals }
+    pub fn sections(&self) -> &[Report] { &self.sections }
+    pub fn paging(&self) -> Option<(usize, usize, usize)> { self.paging }
+
+    /// Adds a row, padded with empty cells or cut to the number of columns.
+    pub fn add_row(&mut self, mut row: Vec<Value>) {
+        row.resize(self.columns.len(), Value::Empty);
+        self.rows.push(row);
+    }
+
+    pub fn add_total(&mut self, label: &str, value: Value) {
+        self.totals.push((label.to_string(), value));
+    }
+
+    pub fn add_section(&mut self, section: Report) {
+        self.sections.push(section);
+    }
+
+    pub fn set_paging(&mut self, page: usize, pages: usize, rows: usize) {
+        self.paging = Some((page, pages, rows));
+    }
+
+    fn paging_line(&self) -> Option<String> {
+        self.paging.map(|(page, pages, rows)| format!("Page {page} of {pages} ({rows} rows)"))
+    }
+
+    pub fn render(&self, format: ReportFormat) -> String {
+        format.formatter().render(self)
+    }
+}
+
+/// Turns a report into text in some format. Implement it to add a format
+/// of your own; `ReportFormat` lists the built-in ones.
+pub trait ReportFormatter {
+    fn render(&self, report: &Report) -> String;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReportFormat {
+    Text,
+    Csv,
+    Json,
+    Markdown,
+    Html,
+}
+
+impl ReportFormat {
+    pub const ALL: [ReportFormat; 5] = [
+        ReportFormat::Text,
+        ReportFormat::Csv,
+        ReportFormat::Json,
+        ReportFormat::Markdown,
+        ReportFormat::Html,
+    ];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ReportFormat::Text => "txt",
+            ReportFormat::Csv => "csv",
+            ReportFormat::Json => "json",
+            ReportFormat::Markdown => "md",
+            ReportFormat::Html => "html",
+        }
+    }
+
+    pub fn formatter(&self) -> Box<dyn ReportFormatter> {
+        match self {
+            ReportFormat::Text => Box::new(TextFormatter),
+            ReportFormat::Csv => Box::new(CsvFormatter),
+            ReportFormat::Json => Box::new(JsonFormatter),
+            ReportFormat::Markdown => Box::new(MarkdownFormatter),
+            ReportFormat::Html => Box::new(HtmlFormatter),
+        }
+    }
+}
+
+/// The title, then columns as wide as their widest cell, numbers
+/// right-aligned.
+pub struct TextFormatter;
+
+impl TextFormatter {
+    fn table(&self, report: &Report) -> String {
+        let cells: Vec<Vec<String>> = report.rows.iter().map(|r| r.iter().map(Value::display).collect()).collect();
+        let widths: Vec<usize> = report
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, c)| cells.iter().map(|r| r[i].chars().count()).max().unwrap_or(0).max(c.chars().count()))
+            .collect();
+        // a column holding any number is right-aligned, header included
+        let numeric: Vec<bool> = (0..report.columns.len())
+            .map(|i| report.rows.iter().any(|r| r[i].is_numeric()))
+            .collect();
+        let line = |texts: &[String]| -> String {
+            let padded: Vec<String> = texts
+                .iter()
+                .enumerate()
+                .map(|(i, t)| if numeric[i] { format!("{t:>w$}", w = widths[i]) } else { format!("{t:<w$}", w = widths[i]) })
+                .collect();
+            format!("{}\n", padded.join(" | ").trim_end())
+        };
+
+        let mut out = String::new();
+        out.push_str(&line(&report.columns));
+        let rule = widths.iter().sum::<usize>() + 3 * widths.len().saturating_sub(1);
+        out.push_str(&format!("{}\n", "-".repeat(rule)));
+        for row in &cells {
+            out.push_str(&line(row));
+        }
+        for (label, value) in &report.totals {
+            out.push_str(&format!("{label}: {}\n", value.display()));
+        }
+        if let Some(line) = report.paging_line() {
+            out.push_str(&format!("{line}\n"));
+        }
+        for s in &report.sections {
+            out.push_str(&format!("\n{}\n", s.title));
+            out.push_str(&self.table(s));
+        }
+        out
+    }
+}
+
+impl ReportFormatter for TextFormatter {
+    fn render(&self, report: &Report) -> String {
+        format!("{}\n{}", report.title, self.table(report))
+    }
+}
+
+/// Header and rows; totals and sections follow after a blank line each.
+pub struct CsvFormatter;
+
+impl ReportFormatter for CsvFormatter {
+    fn render(&self, report: &Report) -> String {
+        let mut out = String::new();
+        let fields: Vec<String> = report.columns.iter().map(|c| csv_field(c)).collect();
+        out.push_str(&format!("{}\n", fields.join(",")));
+        for row in &report.rows {
+            let fields: Vec<String> = row.iter().map(|v| csv_field(v.raw().trim())).collect();
+            out.push_str(&format!("{}\n", fields.join(",")));
+        }
+        if !report.totals.is_empty() {
+            out.push('\n');
+            for (label, value) in &report.totals {
+                out.push_str(&format!("{},{}\n", csv_field(label), csv_field(&value.raw())));
+            }
+        }
+        for s in &report.sections {
+            out.push('\n');
+            out.push_str(&self.render(s));
+        }
+        out
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// An object with the title, one object per row keyed by column, totals
+/// keyed by label, and nested sections.
+pub struct JsonFormatter;
+
+impl ReportFormatter for JsonFormatter {
+    fn render(&self, report: &Report) -> String {
+        let rows: Vec<String> = report
+            .rows
+            .iter()
+            .map(|row| {
+                let fields: Vec<String> = report
+                    .columns
+                    .iter()
+                    .zip(row)
+                    .map(|(c, v)| format!("{}: {}", json_string(c), v.json()))
+                    .collect();
+                format!("{{{}}}", fields.join(", "))
+            })
+            .collect();
+        let totals: Vec<String> = report
+            .totals
+            .iter()
+            .map(|(label, v)| format!("{}: {}", json_string(label), v.json()))
+            .collect();
+        let sections: Vec<String> = report.sections.iter().map(|s| self.render(s)).collect();
+        let paging = match report.paging {
+            Some((page, pages, rows)) => format!(", \"page\": {page}, \"pages\": {pages}, \"total_rows\": {rows}"),
+            None => String::new(),
+        };
+        format!(
+            "{{\"title\": {}, \"rows\": [{}], \"totals\": {{{}}}, \"sections\": [{}]{}}}",
+            json_string(&report.title),
+            rows.join(", "),
+            totals.join(", "),
+            sections.join(", "),
+            paging
+        )
+    }
+}
+
+fn markdown_cell(text: &str) -> String {
+    text.trim().replace('|', "\\|")
+}
+
+pub struct MarkdownFormatter;
+
+impl MarkdownFormatter {
+    fn render_at(&self, report: &Report, level: usize) -> String {
+        let mut out = format!("{} {}\n\n", "#".repeat(level), report.title);
+        let numeric: Vec<bool> = (0..report.columns.len())
+            .map(|i| report.rows.iter().any(|r| r[i].is_numeric()))
+            .collect();
+        let header: Vec<String> = report.columns.iter().map(|c| markdown_cell(c)).collect();
+        let rule: Vec<&str> = numeric.iter().map(|n| if *n { "---:" } else { "---" }).collect();
+        out.push_str(&format!("| {} |\n| {} |\n", header.join(" | "), rule.join(" | ")));
+        for row in &report.rows {
+            let cells: Vec<String> = row.iter().map(|v| markdown_cell(&v.display())).collect();
+            out.push_str(&format!("| {} |\n", cells.join(" | ")));
+        }
+        if !report.totals.is_empty() {
+            out.push('\n');
+            for (label, value) in &report.totals {
+                out.push_str(&format!("**{}:** {}  \n", markdown_cell(label), value.display()));
+            }
+        }
+        if let Some(line) = report.paging_line() {
+            out.push_str(&format!("\n_{line}_\n"));
+        }
+        for s in &report.sections {
+            out.push('\n');
+            out.push_str(&self.render_at(s, level + 1));
+        }
+        out
+    }
+}
+
+impl ReportFormatter for MarkdownFormatter {
+    fn render(&self, report: &Report) -> String {
+        self.render_at(report, 2)
+    }
+}
+
+/// A standalone page with one table per report and section.
+pub struct HtmlFormatter;
+
+impl HtmlFormatter {
+    fn table(&self, report: &Report, level: usize) -> String {
+        let mut out = format!("<h{level}>{}</h{level}>\n<table>\n<thead><tr>", escape_html(&report.title));
+        for c in &report.columns {
+            out.push_str(&format!("<th>{}</th>", escape_html(c)));
+        }
+        out.push_str("</tr></thead>\n<tbody>\n");
+        for row in &report.rows {
+            out.push_str("<tr>");
+            for v in row {
+                let class = if v.is_numeric() { " class=\"num\"" } else { "" };
+                out.push_str(&format!("<td{class}>{}</td>", escape_html(v.display().trim())));
+            }
+            out.push_str("</tr>\n");
+        }
+        out.push_str("</tbody>\n</table>\n");
+        for (label, value) in &report.totals {
+            out.push_str(&format!("<p><b>{}:</b> {}</p>\n", escape_html(label), escape_html(&value.display())));
+        }
+        if let Some(line) = report.paging_line() {
+            out.push_str(&format!("<p>{line}</p>\n"));
+        }
+        for s in &report.sections {
+            out.push_str(&self.table(s, level + 1));
+        }
+        out
+    }
+}
+
+impl ReportFormatter for HtmlFormatter {
+    fn render(&self, report: &Report) -> String {
+        let mut out = String::new();
+        out.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+        out.push_str(&format!("<title>{}</title>\n", escape_html(&report.title)));
+        out.push_str("<style>table{border-collapse:collapse} th,td{border:1px solid #ccc;padding:2px 6px} td.num{text-align:right}</style>\n");
+        out.push_str("</head>\n<body>\n");
+        out.push_str(&self.table(report, 1));
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+}
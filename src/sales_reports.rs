@@ -0,0 +1,312 @@
+#![allow(dead_code)]
+use crate::clock::{self, DAY, Timestamp};
+use crate::inventory::Inventory;
+use crate::report::{Report, ReportFormat, Value};
+use crate::transactions::{Sale, Transactions};
+
+const WEEKDAYS: [&str; 7] = ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"];
+
+/// How sales are bucketed over time. Weeks start on Monday; all dates are UTC.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Period {
+    Day,
+    Week,
+    Month,
+}
+
+impl Period {
+    /// Start of the period that `at` falls in.
+    pub fn start(&self, at: Timestamp) -> Timestamp {
+        let day = at / DAY * DAY;
+        match self {
+            Period::Day => day,
+            Period::Week => day - clock::weekday(at) as u64 * DAY,
+            Period::Month => {
+                let (y, m, _) = clock::date(at);
+                clock::from_date(y, m, 1).unwrap_or(day)
+            }
+        }
+    }
+
+    /// Start of the period after the one starting at `start`.
+    pub fn next(&self, start: Timestamp) -> Timestamp {
+        match self {
+            Period::Day => start + DAY,
+            Period::Week => start + 7 * DAY,
+            Period::Month => {
+                let (y, m, _) = clock::date(start);
+                let (y, m) = if m == 12 { (y + 1, 1) } else { (y, m + 1) };
+                clock::from_date(y, m, 1).unwrap_or(start + 31 * DAY)
+            }
+        }
+    }
+
+    pub fn label(&self, start: Timestamp) -> String {
+        match self {
+            Period::Day => clock::format_date(start),
+            Period::Week => format!("Week of {}", clock::format_date(start)),
+            Period::Month => clock::format_date(start)[..7].to_string(),
+        }
+    }
+}
+
+/// Narrows a sales report down. Empty fields match everything.
+#[derive(Debug, Clone, Default)]
+pub struct SalesFilter {
+    product: Option<String>,
+    category: Option<String>,
+    cashier: Option<String>,
+    customer_id: Option<u32>,
+}
+
+impl SalesFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn product(&self) -> Option<&str> { self.product.as_deref() }
+    pub fn category(&self) -> Option<&str> { self.category.as_deref() }
+    pub fn cashier(&self) -> Option<&str> { self.cashier.as_deref() }
+    pub fn customer_id(&self) -> Option<u32> { self.customer_id }
+
+    pub fn set_product(&mut self, product: Option<&str>) {
+        self.product = product.map(str::to_string);
+    }
+
+    /// Matches on the product's current category.
+    pub fn set_category(&mut self, category: Option<&str>) {
+        self.category = category.map(str::to_string);
+    }
+
+    /// Matches the user who opened the till shift the sale was rung up in.
+    pub fn set_cashier(&mut self, cashier: Option<&str>) {
+        self.cashier = cashier.map(str::to_string);
+    }
+
+    pub fn set_customer(&mut self, customer_id: Option<u32>) {
+        self.customer_id = customer_id;
+    }
+
+    pub fn matches(&self, sale: &Sale, tx: &Transactions, inv: &Inventory) -> bool {
+        self.product.as_ref().is_none_or(|p| p == sale.product_name())
+            && self
+                .category
+                .as_ref()
+                .is_none_or(|c| inv.find(sale.product_name()).is_some_and(|p| p.category() == c))
+            && self.cashier.as_ref().is_none_or(|c| cashier_of(tx, sale) == Some(c.as_str()))
+            && self.customer_id.is_none_or(|c| sale.customer_id() == Some(c))
+    }
+
+    fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(p) = &self.product {
+            parts.push(format!("product {p}"));
+        }
+        if let Some(c) = &self.category {
+            parts.push(format!("category {c}"));
+        }
+        if let Some(c) = &self.cashier {
+            parts.push(format!("cashier {c}"));
+        }
+        if let Some(c) = self.customer_id {
+            parts.push(format!("customer #{c}"));
+        }
+        parts.join(", ")
+    }
+}
+
+/// Who rang the sale up: the user who opened its till shift.
+pub fn cashier_of<'a>(tx: &'a Transactions, sale: &Sale) -> Option<&'a str> {
+    let shift_id = tx.find_order(sale.order_id())?.shift_id()?;
+    tx.find_shift(shift_id).map(|s| s.opened_by())
+}
+
+/// Orders, units and amounts over a set of sales.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SalesTotals {
+    order_ids: Vec<u32>,
+    units: u32,
+    net: f64,
+    tax: f64,
+}
+
+impl SalesTotals {
+    pub fn add(&mut self, sale: &Sale) {
+        if !self.order_ids.contains(&sale.order_id()) {
+            self.order_ids.push(sale.order_id());
+        }
+        self.units += sale.quantity();
+        self.net += sale.net_amount();
+        self.tax += sale.tax();
+    }
+
+    pub fn orders(&self) -> u32 { self.order_ids.len() as u32 }
+    pub fn units(&self) -> u32 { self.units }
+    pub fn net(&self) -> f64 { self.net }
+    pub fn tax(&self) -> f64 { self.tax }
+    pub fn gross(&self) -> f64 { self.net + self.tax }
+
+    /// Gross takings per order.
+    pub fn average_order(&self) -> f64 {
+        if self.order_ids.is_empty() { 0.0 } else { self.gross() / self.order_ids.len() as f64 }
+    }
+}
+
+/// Sales made from `from` up to but not including `to` that pass `filter`.
+pub fn sales_between<'a>(
+    tx: &'a Transactions,
+    inv: &Inventory,
+    from: Timestamp,
+    to: Timestamp,
+    filter: &SalesFilter,
+) -> Vec<&'a Sale> {
+    tx.get_sales()
+        .iter()
+        .filter(|s| s.sold_at() >= from && s.sold_at() < to && filter.matches(s, tx, inv))
+        .collect()
+}
+
+pub fn totals(sales: &[&Sale]) -> SalesTotals {
+    let mut t = SalesTotals::default();
+    for s in sales {
+        t.add(s);
+    }
+    t
+}
+
+/// One row per period between `from` and `to`, including periods with no sales.
+pub fn by_period(sales: &[&Sale], from: Timestamp, to: Timestamp, period: Period) -> Vec<(Timestamp, SalesTotals)> {
+    let mut rows = Vec::new();
+    let mut start = period.start(from);
+    while start < to {
+        let end = period.next(start);
+        let mut t = SalesTotals::default();
+        for s in sales.iter().filter(|s| s.sold_at() >= start && s.sold_at() < end) {
+            t.add(s);
+        }
+        rows.push((start, t));
+        start = end;
+    }
+    rows
+}
+
+/// Totals for each hour of the day, 0 to 23.
+pub fn by_hour(sales: &[&Sale]) -> Vec<SalesTotals> {
+    let mut hours = vec![SalesTotals::default(); 24];
+    for s in sales {
+        hours[clock::hour(s.sold_at()) as usize].add(s);
+    }
+    hours
+}
+
+/// Totals for each day of the week, Monday first.
+pub fn by_weekday(sales: &[&Sale]) -> Vec<SalesTotals> {
+    let mut days = vec![SalesTotals::default(); 7];
+    for s in sales {
+        days[clock::weekday(s.sold_at()) as usize].add(s);
+    }
+    days
+}
+
+/// Totals per product, best sellers by takings first.
+pub fn by_product(sales: &[&Sale]) -> Vec<(String, SalesTotals)> {
+    let mut rows: Vec<(String, SalesTotals)> = Vec::new();
+    for s in sales {
+        match rows.iter_mut().find(|(p, _)| p == s.product_name()) {
+            Some((_, t)) => t.add(s),
+            None => {
+                let mut t = SalesTotals::default();
+                t.add(s);
+                rows.push((s.product_name().to_string(), t));
+            }
+        }
+    }
+    rows.sort_by(|a, b| b.1.gross().total_cmp(&a.1.gross()).then_with(|| a.0.cmp(&b.0)));
+    rows
+}
+
+/// Percentage change from `previous` to `current`; empty when there was nothing before.
+fn change(current: f64, previous: f64) -> Value {
+    if previous == 0.0 { Value::Empty } else { Value::Percent((current - previous) / previous * 100.0) }
+}
+
+const TOTALS_COLUMNS: [&str; 5] = ["Orders", "Units", "Net", "Gross", "Avg order"];
+
+/// A table of totals, one row per label, under `heading`.
+fn totals_table<'a>(title: &str, heading: &str, rows: impl IntoIterator<Item = (String, &'a SalesTotals)>) -> Report {
+    let mut columns = vec![heading];
+    columns.extend(TOTALS_COLUMNS);
+    let mut report = Report::new(title, &columns);
+    for (label, t) in rows {
+        report.add_row(vec![
+            Value::Text(label),
+            Value::Integer(t.orders() as i64),
+            Value::Integer(t.units() as i64),
+            Value::Money(t.net()),
+            Value::Money(t.gross()),
+            Value::Money(t.average_order()),
+        ]);
+    }
+    report
+}
+
+/// Sales from `from` up to but not including `to`, by period, with hour,
+/// weekday and product sections, averages and a comparison against the
+/// period of the same length just before.
+pub fn sales_summary_report(
+    tx: &Transactions,
+    inv: &Inventory,
+    from: Timestamp,
+    to: Timestamp,
+    period: Period,
+    filter: &SalesFilter,
+) -> Report {
+    let sales = sales_between(tx, inv, from, to, filter);
+    let total = totals(&sales);
+    let mut title = format!("Sales {} to {}", clock::format_date(from), clock::format_date(to.saturating_sub(1)));
+    let filters = filter.describe();
+    if !filters.is_empty() {
+        title.push_str(&format!(", only {filters}"));
+    }
+
+    let periods = by_period(&sales, from, to, period);
+    let rows = periods.iter().map(|(start, t)| (period.label(*start), t));
+    let mut report = totals_table(&title, "Period", rows.chain([(String::from("Total"), &total)]));
+    let name = format!("{period:?}").to_lowercase();
+    if !periods.is_empty() {
+        let count = periods.len() as f64;
+        report.add_total(&format!("Average gross per {name}"), Value::Money(total.gross() / count));
+        report.add_total(
+            &format!("Average orders per {name}"),
+            Value::Text(format!("{:.1}", total.orders() as f64 / count)),
+        );
+    }
+    let length = to.saturating_sub(from);
+    let previous = totals(&sales_between(tx, inv, from.saturating_sub(length), from, filter));
+    report.add_total("Previous period gross", Value::Money(previous.gross()));
+    report.add_total("Previous period orders", Value::Integer(previous.orders() as i64));
+    report.add_total("Change in gross", change(total.gross(), previous.gross()));
+    report.add_total("Change in orders", change(total.orders() as f64, previous.orders() as f64));
+
+    let hours = by_hour(&sales);
+    let hours = hours.iter().enumerate().filter(|(_, t)| t.orders() > 0);
+    report.add_section(totals_table("By hour", "Hour", hours.map(|(h, t)| (format!("{h:02}:00"), t))));
+    let days = by_weekday(&sales);
+    let days = days.iter().enumerate().filter(|(_, t)| t.orders() > 0);
+    report.add_section(totals_table("By weekday", "Weekday", days.map(|(d, t)| (WEEKDAYS[d].to_string(), t))));
+    let products = by_product(&sales);
+    report.add_section(totals_table("By product", "Product", products.iter().map(|(p, t)| (p.clone(), t))));
+    report
+}
+
+/// The sales summary as text.
+pub fn report_sales_summary(
+    tx: &Transactions,
+    inv: &Inventory,
+    from: Timestamp,
+    to: Timestamp,
+    period: Period,
+    filter: &SalesFilter,
+) -> String {
+    sales_summary_report(tx, inv, from, to, period, filter).render(ReportFormat::Text)
+}
@@ -1,4 +1,4 @@
-use std::fs::{self, OpenOptions};
+use std::fs;
 use std::io::{self, Write, BufRead, BufReader};
 
 #[derive(Debug, Clone, PartialEq)]
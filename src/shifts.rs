@@ -0,0 +1,97 @@
+#![allow(dead_code)]
+use crate::clock::Timestamp;
+use crate::payments::{self, TenderKind};
+use crate::transactions::Transactions;
+
+/// A till session: opened by a cashier with a float of cash, closed when the
+/// cashier counts what is in the drawer.
+pub struct Shift {
+    id: u32,
+    opened_by: String,
+    opening_float: f64,
+    opened_at: Timestamp,
+    closed_at: Option<Timestamp>,
+    counted: Vec<(TenderKind, f64)>,
+}
+
+impl Shift {
+    pub(crate) fn new(id: u32, opened_by: &str, opening_float: f64, opened_at: Timestamp) -> Self {
+        Self {
+            id,
+            opened_by: opened_by.to_string(),
+            opening_float,
+            opened_at,
+            closed_at: None,
+            counted: Vec::new(),
+        }
+    }
+
+    pub(crate) fn close(&mut self, counted: &[(TenderKind, f64)], closed_at: Timestamp) {
+        self.counted = counted.to_vec();
+        self.closed_at = Some(closed_at);
+    }
+
+    pub fn id(&self) -> u32 { self.id }
+    pub fn opened_by(&self) -> &str { &self.opened_by }
+    pub fn opening_float(&self) -> f64 { self.opening_float }
+    pub fn opened_at(&self) -> Timestamp { self.opened_at }
+    pub fn closed_at(&self) -> Option<Timestamp> { self.closed_at }
+    pub fn is_open(&self) -> bool { self.closed_at.is_none() }
+
+    /// What the cashier counted for `kind` at close, if they counted it.
+    pub fn counted(&self, kind: TenderKind) -> Option<f64> {
+        self.counted.iter().find(|(k, _)| *k == kind).map(|(_, a)| *a)
+    }
+}
+
+/// Amount that should be in the till per tender for the shift so far. Cash
+/// starts from the float, loses change handed back and cash refunds.
+pub fn expected_by_tender(tx: &Transactions, shift: &Shift) -> Vec<(TenderKind, f64)> {
+    let orders: Vec<_> = tx.get_orders().iter().filter(|o| o.shift_id() == Some(shift.id)).collect();
+    let mut expected = payments::totals_by_kind(orders.iter().flat_map(|o| o.payments()));
+
+    let change: f64 = orders.iter().map(|o| o.change()).sum();
+    let refunds: f64 = tx
+        .get_returns()
+        .iter()
+        .filter(|r| r.shift_id() == Some(shift.id))
+        .map(|r| r.cash_refund())
+        .sum();
+    let cash = shift.opening_float - change - refunds;
+    match expected.iter_mut().find(|(k, _)| *k == TenderKind::Cash) {
+        Some((_, amount)) => *amount += cash,
+        None => expected.insert(0, (TenderKind::Cash, cash)),
+    }
+    expected
+}
+
+/// X report while the shift is open, Z report once it is closed. Tenders the
+/// cashier did not count show no over/short.
+pub fn report_shift(tx: &Transactions, shift: &Shift) -> String {
+    let mut out = String::new();
+    let title = if shift.is_open() { "X report" } else { "Z report" };
+    out.push_str(&format!(
+        "{} - shift #{} opened by {} (float {:.2})\n",
+        title, shift.id, shift.opened_by, shift.opening_float
+    ));
+    let orders = tx.get_orders().iter().filter(|o| o.shift_id() == Some(shift.id)).count();
+    let returns = tx.get_returns().iter().filter(|r| r.shift_id() == Some(shift.id)).count();
+    out.push_str(&format!("Orders: {orders}, returns: {returns}\n"));
+    out.push_str("Tender         | Expected  | Counted   | Over/Short\n");
+    out.push_str("---------------------------------------------------\n");
+
+    for (kind, expected) in expected_by_tender(tx, shift) {
+        let (counted, diff) = match shift.counted(kind) {
+            Some(c) => (format!("{:.2}", c), format!("{:+.2}", c - expected)),
+            None => (String::from("-"), String::from("-")),
+        };
+        out.push_str(&format!(
+            "{:<14} | {:>9.2} | {:>9} | {:>10}\n",
+            format!("{:?}", kind),
+            expected,
+            counted,
+            diff
+        ));
+    }
+    out
+}
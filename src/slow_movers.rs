@@ -0,0 +1,198 @@
+#![allow(dead_code)]
+use crate::clock::{self, DAY, Timestamp};
+use crate::inventory::Inventory;
+use crate::report::{Report, ReportFormat, Value};
+use crate::transactions::Transactions;
+use crate::valuation::{stock_as_of, units_of};
+
+/// How fast one product's stock sells over a window of days.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StockMovement {
+    product_name: String,
+    category: String,
+    on_hand: u32,
+    /// Stock at the start of the window.
+    opening: u32,
+    /// Units sold in the window, less units returned.
+    sold: u32,
+    /// Units sold in the window before any returns.
+    gross_sold: u32,
+    last_sold: Option<Timestamp>,
+    unit_cost: Option<f64>,
+    days: u32,
+}
+
+impl StockMovement {
+    pub fn product_name(&self) -> &str { &self.product_name }
+    pub fn category(&self) -> &str { &self.category }
+    pub fn on_hand(&self) -> u32 { self.on_hand }
+    pub fn sold(&self) -> u32 { self.sold }
+    pub fn gross_sold(&self) -> u32 { self.gross_sold }
+    pub fn last_sold(&self) -> Option<Timestamp> { self.last_sold }
+    pub fn unit_cost(&self) -> Option<f64> { self.unit_cost }
+    /// Stock at cost; products never purchased count as zero.
+    pub fn value(&self) -> f64 { self.unit_cost.unwrap_or(0.0) * self.on_hand as f64 }
+
+    /// Whole days since the last sale at `now`; `None` if never sold.
+    pub fn days_since_sale(&self, now: Timestamp) -> Option<u64> {
+        self.last_sold.map(|at| now.saturating_sub(at) / DAY)
+    }
+
+    /// Days the stock on hand lasts at the window's rate of sale, net of
+    /// returns; `None` when nothing sold.
+    pub fn days_of_supply(&self) -> Option<f64> {
+        (self.sold > 0).then(|| self.on_hand as f64 * self.days as f64 / self.sold as f64)
+    }
+
+    /// Units sold over the average of opening and closing stock; `None`
+    /// when there was no stock to turn.
+    pub fn turnover(&self) -> Option<f64> {
+        let average = (self.opening + self.on_hand) as f64 / 2.0;
+        (average > 0.0).then(|| self.sold as f64 / average)
+    }
+
+    /// Stock on hand that did not sell at all in the window. A sale that
+    /// came back still counts as selling; see `is_slow` for that stock.
+    pub fn is_dead(&self) -> bool {
+        self.on_hand > 0 && self.gross_sold == 0
+    }
+
+    /// Stock on hand with more than `slow_days` of supply, or none sold net
+    /// of returns: dead stock, and stock whose sales all came back.
+    pub fn is_slow(&self, slow_days: u32) -> bool {
+        self.on_hand > 0 && self.days_of_supply().is_none_or(|d| d > slow_days as f64)
+    }
+}
+
+/// Movement of every product that holds stock of its own over the `days`
+/// days up to `now`.
+pub fn stock_movement(tx: &Transactions, inv: &Inventory, days: u32, now: Timestamp) -> Vec<StockMovement> {
+    let from = now.saturating_sub(days as u64 * DAY);
+    inv.all()
+        .iter()
+        .filter(|p| !p.is_kit() && !p.is_parent())
+        .map(|p| {
+            let name = p.name();
+            let sold: u32 = tx
+                .get_sales()
+                .iter()
+                .filter(|s| s.sold_at() > from && s.sold_at() <= now)
+                .map(|s| units_of(s, name, s.quantity()))
+                .sum();
+            let returned: u32 = tx
+                .get_returns()
+                .iter()
+                .filter(|r| r.returned_at() > from && r.returned_at() <= now)
+                .filter_map(|r| {
+                    let sale = tx.get_sales().iter().find(|s| s.id() == r.sale_id())?;
+                    Some(units_of(sale, name, r.quantity()))
+                })
+                .sum();
+            let last_sold = tx
+                .get_sales()
+                .iter()
+                .filter(|s| s.sold_at() <= now && units_of(s, name, s.quantity()) > 0)
+                .map(|s| s.sold_at())
+                .max();
+            StockMovement {
+                product_name: name.to_string(),
+                category: p.category().to_string(),
+                on_hand: stock_as_of(tx, inv, name, now),
+                opening: stock_as_of(tx, inv, name, from),
+                sold: sold.saturating_sub(returned),
+                gross_sold: sold,
+                last_sold,
+                unit_cost: tx.unit_cost_at(name, inv, now),
+                days,
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MovementSort {
+    Name,
+    DaysSinceSale,
+    DaysOfSupply,
+    Turnover,
+    Value,
+}
+
+/// Sorts with the slowest first for every key but the name: longest since
+/// a sale, most days of supply, lowest turnover, most value tied up.
+pub fn sort_movement(rows: &mut [StockMovement], key: MovementSort, now: Timestamp) {
+    rows.sort_by(|a, b| match key {
+        MovementSort::Name => a.product_name.to_lowercase().cmp(&b.product_name.to_lowercase()),
+        // never sold counts as longest
+        MovementSort::DaysSinceSale => {
+            let days = |m: &StockMovement| m.days_since_sale(now).unwrap_or(u64::MAX);
+            days(b).cmp(&days(a))
+        }
+        MovementSort::DaysOfSupply => {
+            let supply = |m: &StockMovement| m.days_of_supply().unwrap_or(f64::INFINITY);
+            supply(b).total_cmp(&supply(a))
+        }
+        MovementSort::Turnover => a.turnover().unwrap_or(0.0).total_cmp(&b.turnover().unwrap_or(0.0)),
+        MovementSort::Value => b.value().total_cmp(&a.value()),
+    });
+}
+
+/// Products with stock on hand that sold nothing in the last `days` days or
+/// have more than `slow_days` of supply, with the value tied up in them.
+/// `all` lists every stocked product instead of only the slow ones.
+pub fn slow_movers_report(
+    tx: &Transactions,
+    inv: &Inventory,
+    days: u32,
+    slow_days: u32,
+    sort: MovementSort,
+    all: bool,
+    now: Timestamp,
+) -> Report {
+    let mut rows = stock_movement(tx, inv, days, now);
+    sort_movement(&mut rows, sort, now);
+    let mut report = Report::new(
+        &format!("Stock movement over the {days} days to {}", clock::format_date(now)),
+        &["Product", "Category", "On hand", "Sold", "Last sold", "Days of supply", "Turnover", "Value", "Status"],
+    );
+    for m in rows.iter().filter(|m| all || m.is_slow(slow_days)) {
+        let status = if m.is_dead() {
+            "Dead"
+        } else if m.is_slow(slow_days) {
+            "Slow"
+        } else {
+            ""
+        };
+        report.add_row(vec![
+            Value::text(m.product_name()),
+            Value::text(m.category()),
+            Value::Integer(m.on_hand() as i64),
+            Value::Integer(m.sold() as i64),
+            m.last_sold().map_or(Value::text("never"), |at| Value::Text(clock::format_date(at))),
+            m.days_of_supply().map_or(Value::Empty, |d| Value::Integer(d.round() as i64)),
+            m.turnover().map_or(Value::Empty, |t| Value::Text(format!("{t:.2}"))),
+            Value::Money(m.value()),
+            Value::text(status),
+        ]);
+    }
+
+    let dead: Vec<&StockMovement> = rows.iter().filter(|m| m.is_dead()).collect();
+    let slow_value: f64 = rows.iter().filter(|m| m.is_slow(slow_days)).map(|m| m.value()).sum();
+    report.add_total(&format!("No sales in {days} days"), Value::Integer(dead.len() as i64));
+    report.add_total("Dead stock value", Value::Money(dead.iter().map(|m| m.value()).sum()));
+    report.add_total(&format!("Slow mover value (dead or over {slow_days} days of supply)"), Value::Money(slow_value));
+    report.add_total("Total stock value", Value::Money(rows.iter().map(|m| m.value()).sum()));
+    report
+}
+
+/// The slow movers as text.
+pub fn report_slow_movers(
+    tx: &Transactions,
+    inv: &Inventory,
+    days: u32,
+    slow_days: u32,
+    sort: MovementSort,
+    now: Timestamp,
+) -> String {
+    slow_movers_report(tx, inv, days, slow_days, sort, false, now).render(ReportFormat::Text)
+}
@@ -0,0 +1,239 @@
+#![allow(dead_code)]
+use crate::clock::{DAY, Timestamp};
+use crate::report::{Report, ReportFormat, Value};
+use crate::tax;
+use crate::transactions::StoreError;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CardKind {
+    GiftCard,
+    StoreCredit,
+}
+
+impl CardKind {
+    fn prefix(&self) -> &'static str {
+        match self {
+            CardKind::GiftCard => "GC",
+            CardKind::StoreCredit => "SC",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CardEntryKind {
+    Issued,
+    Redeemed,
+    Expired,
+}
+
+/// One movement on a card's balance.
+#[derive(Debug, Clone)]
+pub struct CardEntry {
+    kind: CardEntryKind,
+    /// Signed change to the balance.
+    amount: f64,
+    order_id: Option<u32>,
+    at: Timestamp,
+}
+
+impl CardEntry {
+    pub fn kind(&self) -> CardEntryKind { self.kind }
+    pub fn amount(&self) -> f64 { self.amount }
+    pub fn order_id(&self) -> Option<u32> { self.order_id }
+    pub fn at(&self) -> Timestamp { self.at }
+}
+
+/// A gift card or store credit account. Like loyalty points, the balance is
+/// always derived from the entries.
+pub struct StoredValueCard {
+    number: String,
+    kind: CardKind,
+    customer_id: Option<u32>,
+    issued_at: Timestamp,
+    expires_at: Option<Timestamp>,
+    entries: Vec<CardEntry>,
+}
+
+impl StoredValueCard {
+    pub fn number(&self) -> &str { &self.number }
+    pub fn kind(&self) -> CardKind { self.kind }
+    pub fn customer_id(&self) -> Option<u32> { self.customer_id }
+    pub fn issued_at(&self) -> Timestamp { self.issued_at }
+    pub fn expires_at(&self) -> Option<Timestamp> { self.expires_at }
+    pub fn entries(&self) -> &[CardEntry] { &self.entries }
+
+    /// Value the card was loaded with when issued.
+    pub fn issued_amount(&self) -> f64 {
+        self.entries.iter().filter(|e| e.kind == CardEntryKind::Issued).map(|e| e.amount).sum()
+    }
+
+    pub fn balance(&self) -> f64 {
+        tax::round_cents(self.entries.iter().map(|e| e.amount).sum())
+    }
+
+    pub fn is_expired(&self, now: Timestamp) -> bool {
+        self.expires_at.is_some_and(|e| e <= now)
+    }
+
+    /// What the card can still be spent for at `now`.
+    pub fn available(&self, now: Timestamp) -> f64 {
+        if self.is_expired(now) { 0.0 } else { self.balance() }
+    }
+}
+
+/// Every card the store has issued, with how long each kind stays valid.
+pub struct StoredValue {
+    cards: Vec<StoredValueCard>,
+    next_number: u32,
+    gift_card_days: Option<u32>,
+    store_credit_days: Option<u32>,
+}
+
+impl Default for StoredValue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StoredValue {
+    /// Cards never expire until a validity period is set.
+    pub fn new() -> Self {
+        Self {
+            cards: Vec::new(),
+            next_number: 1,
+            gift_card_days: None,
+            store_credit_days: None,
+        }
+    }
+
+    /// How many days new cards of `kind` stay valid; `None` for no expiry.
+    /// Cards already issued keep their expiry date.
+    pub fn set_validity(&mut self, kind: CardKind, days: Option<u32>) {
+        match kind {
+            CardKind::GiftCard => self.gift_card_days = days,
+            CardKind::StoreCredit => self.store_credit_days = days,
+        }
+    }
+
+    pub fn validity(&self, kind: CardKind) -> Option<u32> {
+        match kind {
+            CardKind::GiftCard => self.gift_card_days,
+            CardKind::StoreCredit => self.store_credit_days,
+        }
+    }
+
+    /// Issues a new card loaded with `amount`, numbered e.g. GC000001.
+    pub fn issue(
+        &mut self,
+        kind: CardKind,
+        amount: f64,
+        customer_id: Option<u32>,
+        now: Timestamp,
+    ) -> Result<&StoredValueCard, StoreError> {
+        if amount <= 0.0 {
+            return Err(StoreError::InvalidInput("Card amount must be > 0".into()));
+        }
+        let number = format!("{}{:06}", kind.prefix(), self.next_number);
+        self.next_number += 1;
+        let expires_at = self
+            .validity(kind)
+            .map(|days| now.saturating_add(days as u64 * DAY));
+        self.cards.push(StoredValueCard {
+            number,
+            kind,
+            customer_id,
+            issued_at: now,
+            expires_at,
+            entries: vec![CardEntry {
+                kind: CardEntryKind::Issued,
+                amount: tax::round_cents(amount),
+                order_id: None,
+                at: now,
+            }],
+        });
+        Ok(self.cards.last().unwrap())
+    }
+
+    pub fn find(&self, number: &str) -> Option<&StoredValueCard> {
+        let number = number.trim().to_uppercase();
+        self.cards.iter().find(|c| c.number == number)
+    }
+
+    pub fn all(&self) -> &[StoredValueCard] {
+        &self.cards
+    }
+
+    pub fn cards_for(&self, customer_id: u32) -> Vec<&StoredValueCard> {
+        self.cards.iter().filter(|c| c.customer_id == Some(customer_id)).collect()
+    }
+
+    /// Balance that can be spent on `number` right now.
+    pub fn balance(&self, number: &str, now: Timestamp) -> Result<f64, StoreError> {
+        self.find(number)
+            .map(|c| c.available(now))
+            .ok_or_else(|| StoreError::NotFound(format!("stored-value card {number}")))
+    }
+
+    /// Takes `amount` off a card as payment for an order. Checkout has
+    /// already checked the card and its balance.
+    pub(crate) fn redeem(&mut self, number: &str, amount: f64, order_id: u32, now: Timestamp) {
+        let number = number.trim().to_uppercase();
+        if let Some(card) = self.cards.iter_mut().find(|c| c.number == number) {
+            card.entries.push(CardEntry {
+                kind: CardEntryKind::Redeemed,
+                amount: -amount,
+                order_id: Some(order_id),
+                at: now,
+            });
+        }
+    }
+
+    /// Writes off whatever is left on cards that have expired by `now` and
+    /// returns their numbers, so they stop counting as a liability. Goes
+    /// through `Transactions::expire_cards` so the write-off is posted.
+    pub(crate) fn expire(&mut self, now: Timestamp) -> Vec<String> {
+        let mut expired = Vec::new();
+        for card in self.cards.iter_mut().filter(|c| c.is_expired(now)) {
+            let left = card.balance();
+            if left > 0.0 {
+                card.entries.push(CardEntry {
+                    kind: CardEntryKind::Expired,
+                    amount: -left,
+                    order_id: None,
+                    at: now,
+                });
+                expired.push(card.number.clone());
+            }
+        }
+        expired
+    }
+
+    /// Outstanding balances the store still owes, per card kind.
+    pub fn liability(&self, kind: CardKind) -> f64 {
+        tax::round_cents(self.cards.iter().filter(|c| c.kind == kind).map(|c| c.balance()).sum())
+    }
+}
+
+/// Cards with a balance left, and what the store owes in total. Expired
+/// cards stay listed until `StoredValue::expire` writes them off.
+pub fn liability_report(sv: &StoredValue, now: Timestamp) -> Report {
+    let mut report = Report::new("Gift cards & store credit", &["Card", "Kind", "Customer", "Balance", "Status"]);
+    for c in sv.all().iter().filter(|c| c.balance() > 0.0) {
+        let customer = c.customer_id.map(|id| format!("#{id}")).unwrap_or_default();
+        let status = if c.is_expired(now) { "Expired" } else { "Active" };
+        report.add_row(vec![
+            Value::text(&c.number),
+            Value::Text(format!("{:?}", c.kind)),
+            Value::Text(customer),
+            Value::Money(c.balance()),
+            Value::text(status),
+        ]);
+    }
+    report.add_total("Outstanding gift cards", Value::Money(sv.liability(CardKind::GiftCard)));
+    report.add_total("Outstanding store credit", Value::Money(sv.liability(CardKind::StoreCredit)));
+    report
+}
+
+pub fn report_liability(sv: &StoredValue, now: Timestamp) -> String {
+    liability_report(sv, now).render(ReportFormat::Text)
+}
@@ -0,0 +1,116 @@
+#![allow(dead_code)]
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TaxClass {
+    #[default]
+    Standard,
+    Reduced,
+    Exempt,
+}
+
+/// Whether shelf prices already contain tax or have it added at the till.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PricingMode {
+    TaxInclusive,
+    TaxExclusive,
+}
+
+/// Where tax is rounded to cents: on every sale line, or once per rate on
+/// the whole order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TaxRounding {
+    PerLine,
+    PerOrder,
+}
+
+pub struct TaxSettings {
+    standard_rate: f64,
+    reduced_rate: f64,
+    mode: PricingMode,
+    rounding: TaxRounding,
+}
+
+impl Default for TaxSettings {
+    /// No tax until the store configures its rates.
+    fn default() -> Self {
+        Self::new(0.0, 0.0, PricingMode::TaxInclusive, TaxRounding::PerLine)
+    }
+}
+
+impl TaxSettings {
+    /// Rates are fractions, e.g. 0.2 for 20%.
+    pub fn new(standard_rate: f64, reduced_rate: f64, mode: PricingMode, rounding: TaxRounding) -> Self {
+        Self {
+            standard_rate: standard_rate.max(0.0),
+            reduced_rate: reduced_rate.max(0.0),
+            mode,
+            rounding,
+        }
+    }
+
+    pub fn mode(&self) -> PricingMode { self.mode }
+    pub fn rounding(&self) -> TaxRounding { self.rounding }
+
+    pub fn rate(&self, class: TaxClass) -> f64 {
+        match class {
+            TaxClass::Standard => self.standard_rate,
+            TaxClass::Reduced => self.reduced_rate,
+            TaxClass::Exempt => 0.0,
+        }
+    }
+
+    /// Tax on a line priced at `amount`, rounded to cents when rounding per line.
+    pub fn line_tax(&self, amount: f64, class: TaxClass) -> f64 {
+        let rate = self.rate(class);
+        let tax = match self.mode {
+            PricingMode::TaxInclusive => amount - amount / (1.0 + rate),
+            PricingMode::TaxExclusive => amount * rate,
+        };
+        match self.rounding {
+            TaxRounding::PerLine => round_cents(tax),
+            TaxRounding::PerOrder => tax,
+        }
+    }
+}
+
+/// Tax collected at one rate on an order or in a report.
+#[derive(Debug, Clone)]
+pub struct TaxLine {
+    rate: f64,
+    net: f64,
+    tax: f64,
+}
+
+impl TaxLine {
+    pub fn rate(&self) -> f64 { self.rate }
+    pub fn net(&self) -> f64 { self.net }
+    pub fn tax(&self) -> f64 { self.tax }
+}
+
+/// Adds `(rate, net, tax)` amounts into one line per rate, ordered by rate.
+pub fn tax_by_rate(amounts: impl IntoIterator<Item = (f64, f64, f64)>) -> Vec<TaxLine> {
+    let mut lines: Vec<TaxLine> = Vec::new();
+    for (rate, net, tax) in amounts {
+        match lines.iter_mut().find(|l| (l.rate - rate).abs() < 1e-9) {
+            Some(l) => {
+                l.net += net;
+                l.tax += tax;
+            }
+            None => lines.push(TaxLine { rate, net, tax }),
+        }
+    }
+    lines.sort_by(|a, b| a.rate.total_cmp(&b.rate));
+    for l in lines.iter_mut() {
+        l.net = round_cents(l.net);
+        l.tax = round_cents(l.tax);
+    }
+    lines
+}
+
+/// Rounds half away from zero to two decimals. The amount is first snapped
+/// to a millionth of a cent so binary noise (0.165 stored as 0.16499...)
+/// does not flip which way a half cent goes.
+pub fn round_cents(amount: f64) -> f64 {
+    let cents = (amount * 100.0 * 1e6).round() / 1e6;
+    cents.round() / 100.0
+}
@@ -1,54 +1,442 @@
 #![allow(dead_code)]
+use crate::accounting::{AccountRole, EntryKind, ChartOfAccounts, Journal, JournalEntry, JournalLine};
+use crate::clock::{self, Timestamp};
+use crate::costing::{self, AllocationBasis, ChargeKind, CostingMethod, LandedCharge};
+use crate::currency::{ExchangeRates, Money};
 use crate::inventory::Inventory;
+use crate::loyalty::{Loyalty, PointsKind};
+use crate::promotions::{AppliedPromotion, Promotions};
+use crate::payments::{Payment, Tender, TenderKind};
+use crate::price_lists::{PriceList, PriceLists};
+use crate::procurement::{self, DocumentLine, Procurement, PurchaseOrder, SupplierInvoice};
+use crate::query::{ListKeys, ListQuery};
+use crate::report::{Report, ReportFormat, Value};
+use crate::shifts::Shift;
+use crate::stored_value::{CardKind, StoredValue};
+use crate::tax::{self, PricingMode, TaxLine, TaxSettings};
+use crate::valuation;
 
 #[derive(Debug)]
 pub enum StoreError {
     InvalidInput(String),
     OutOfStock { product: String, requested: u32, available: u32 },
     NotFound(String),
+    InsufficientPayment { due: f64, paid: f64 },
 }
 pub struct Sale {
+    id: u32,
+    order_id: u32,
     product_name: String,
     quantity: u32,
     unit_price: f64,
+    price_list: Option<String>,
+    list_price: f64,
+    sold_at: Timestamp,
+    discounts: Vec<AppliedPromotion>,
     total_price: f64,
+    tax_rate: f64,
+    tax: f64,
+    net_amount: f64,
+    components: Vec<SaleComponent>,
+    customer_id: Option<u32>,
+    points_redeemed: u32,
+    points_value: f64,
+    cost: Option<f64>,
 }
 
 impl Sale {
+    pub fn id(&self) -> u32 { self.id }
+    pub fn order_id(&self) -> u32 { self.order_id }
     pub fn product_name(&self) -> &str { &self.product_name }
     pub fn quantity(&self) -> u32 { self.quantity }
     pub fn unit_price(&self) -> f64 { self.unit_price }
+    /// Price list the unit price came from, if the customer was on one.
+    pub fn price_list(&self) -> Option<&str> { self.price_list.as_deref() }
+    /// Shelf price of the product when it was sold, whatever was charged.
+    pub fn list_price(&self) -> f64 { self.list_price }
+    pub fn sold_at(&self) -> Timestamp { self.sold_at }
+    /// Promotions applied to this line, in the order they were applied.
+    pub fn discounts(&self) -> &[AppliedPromotion] { &self.discounts }
+    // folded from 0.0: an empty f64 sum is -0.0, shown as "-0.00"
+    pub fn discount(&self) -> f64 { self.discounts.iter().fold(0.0, |total, d| total + d.discount()) }
+    /// Line total after discounts, at shelf prices.
     pub fn total_price(&self) -> f64 { self.total_price }
+    pub fn tax_rate(&self) -> f64 { self.tax_rate }
+    /// Tax on the line; unrounded when the store rounds tax per order.
+    pub fn tax(&self) -> f64 { self.tax }
+    /// Line total excluding tax.
+    pub fn net_amount(&self) -> f64 { self.net_amount }
+    /// Line total including tax: what the customer pays for it.
+    pub fn gross_amount(&self) -> f64 { self.net_amount + self.tax }
+    /// Component lines when the sale was a kit; empty otherwise.
+    pub fn components(&self) -> &[SaleComponent] { &self.components }
+    pub fn is_kit(&self) -> bool { !self.components.is_empty() }
+    /// The customer the sale was made to; `None` for anonymous counter sales.
+    pub fn customer_id(&self) -> Option<u32> { self.customer_id }
+    /// Loyalty points used to pay for the sale, and their currency value.
+    pub fn points_redeemed(&self) -> u32 { self.points_redeemed }
+    pub fn points_value(&self) -> f64 { self.points_value }
+    /// What is left to pay after points.
+    pub fn amount_due(&self) -> f64 { self.gross_amount() - self.points_value }
+    /// Cost of goods sold posted for the line; `None` if the product had no
+    /// known cost.
+    pub fn cost(&self) -> Option<f64> { self.cost }
 }
 
+/// Units a customer brought back from an earlier sale. The refund is split
+/// between money (cash, or store credit) and the loyalty points that
+/// originally paid for those units.
+pub struct SaleReturn {
+    sale_id: u32,
+    product_name: String,
+    quantity: u32,
+    refund: f64,
+    tax: f64,
+    points_restored: u32,
+    cash_refund: f64,
+    credit_card: Option<String>,
+    shift_id: Option<u32>,
+    returned_at: Timestamp,
+    cost: f64,
+}
+
+impl SaleReturn {
+    pub fn sale_id(&self) -> u32 { self.sale_id }
+    pub fn product_name(&self) -> &str { &self.product_name }
+    pub fn quantity(&self) -> u32 { self.quantity }
+    /// Full value of the returned units, tax included.
+    pub fn refund(&self) -> f64 { self.refund }
+    /// Tax included in the refund.
+    pub fn tax(&self) -> f64 { self.tax }
+    pub fn points_restored(&self) -> u32 { self.points_restored }
+    /// Part of the refund paid back in money rather than points.
+    pub fn cash_refund(&self) -> f64 { self.cash_refund }
+    /// Store credit card the refund was issued to, if not refunded in cash.
+    pub fn credit_card(&self) -> Option<&str> { self.credit_card.as_deref() }
+    pub fn shift_id(&self) -> Option<u32> { self.shift_id }
+    pub fn returned_at(&self) -> Timestamp { self.returned_at }
+    /// Cost of goods sold taken back, the sale's cost in proportion.
+    pub fn cost(&self) -> f64 { self.cost }
+}
 
+/// One component of a kit sale, with its share of the kit revenue
+/// (split by the components' list prices at the time of sale).
+pub struct SaleComponent {
+    product_name: String,
+    quantity: u32,
+    total_price: f64,
+}
+
+impl SaleComponent {
+    pub fn product_name(&self) -> &str { &self.product_name }
+    pub fn quantity(&self) -> u32 { self.quantity }
+    pub fn total_price(&self) -> f64 { self.total_price }
+}
+
+
+/// Stock bought from a supplier. Cost is kept in the base currency; the
+/// supplier's own price and the rate used to convert it are kept alongside.
 pub struct Purchase {
+    id: u32,
     product_name: String,
     quantity: u32,
     unit_price: f64,
     total_cost: f64,
+    supplier_unit_price: Money,
+    exchange_rate: f64,
+    purchased_at: Timestamp,
+    /// Freight, duty and other charges allocated to this purchase.
+    landed_charges: f64,
+    /// Purchase order this delivery was received against.
+    po_id: Option<u32>,
 }
 
 impl Purchase {
+    pub fn id(&self) -> u32 { self.id }
     pub fn product_name(&self) -> &str { &self.product_name }
     pub fn quantity(&self) -> u32 { self.quantity }
     pub fn unit_price(&self) -> f64 { self.unit_price }
     pub fn total_cost(&self) -> f64 { self.total_cost }
+    /// Unit price as invoiced by the supplier, in their currency.
+    pub fn supplier_unit_price(&self) -> &Money { &self.supplier_unit_price }
+    pub fn supplier_total(&self) -> Money {
+        Money::new(self.quantity as f64 * self.supplier_unit_price.amount(), self.supplier_unit_price.currency())
+    }
+    /// Base currency per unit of the supplier's currency on the purchase date.
+    pub fn exchange_rate(&self) -> f64 { self.exchange_rate }
+    pub fn purchased_at(&self) -> Timestamp { self.purchased_at }
+    pub fn landed_charges(&self) -> f64 { self.landed_charges }
+    pub fn po_id(&self) -> Option<u32> { self.po_id }
+    /// Cost of the purchase including its share of landed charges.
+    pub fn landed_cost(&self) -> f64 { self.total_cost + self.landed_charges }
+    pub fn landed_unit_cost(&self) -> f64 { self.landed_cost() / self.quantity as f64 }
+}
+
+/// Units sent back to the supplier from an earlier purchase (return to
+/// vendor). Each return raises a debit note for the value claimed back.
+pub struct SupplierReturn {
+    id: u32,
+    purchase_id: u32,
+    product_name: String,
+    quantity: u32,
+    reason: String,
+    /// Value claimed back, in the base currency at the purchase's unit cost.
+    amount: f64,
+    /// The same value in the currency the supplier billed in.
+    supplier_amount: Money,
+    returned_at: Timestamp,
+}
+
+impl SupplierReturn {
+    pub fn id(&self) -> u32 { self.id }
+    pub fn purchase_id(&self) -> u32 { self.purchase_id }
+    pub fn product_name(&self) -> &str { &self.product_name }
+    pub fn quantity(&self) -> u32 { self.quantity }
+    pub fn reason(&self) -> &str { &self.reason }
+    pub fn amount(&self) -> f64 { self.amount }
+    pub fn supplier_amount(&self) -> &Money { &self.supplier_amount }
+    pub fn returned_at(&self) -> Timestamp { self.returned_at }
+    /// Debit note number, e.g. DN-0001.
+    pub fn debit_note(&self) -> String { format!("DN-{:04}", self.id) }
+}
+
+/// A stock count that set a product's quantity to what was on the shelf.
+pub struct StockCount {
+    id: u32,
+    product_name: String,
+    counted: u32,
+    /// Counted less the quantity on record before the count.
+    difference: i64,
+    reason: String,
+    counted_at: Timestamp,
+}
+
+impl StockCount {
+    pub fn id(&self) -> u32 { self.id }
+    pub fn product_name(&self) -> &str { &self.product_name }
+    pub fn counted(&self) -> u32 { self.counted }
+    pub fn difference(&self) -> i64 { self.difference }
+    pub fn reason(&self) -> &str { &self.reason }
+    pub fn counted_at(&self) -> Timestamp { self.counted_at }
+}
+
+/// Quantity a customer ordered that could not be served from stock. Incoming
+/// purchases are allocated to open backorders in order of creation; the
+/// allocated units are held as reservations until the customer picks them up.
+pub struct Backorder {
+    id: u32,
+    customer_id: u32,
+    product_name: String,
+    quantity: u32,
+    unit_price: f64,
+    allocated: u32,
+    reservation_ids: Vec<u32>,
+    fulfilled: bool,
+}
+
+impl Backorder {
+    pub fn id(&self) -> u32 { self.id }
+    pub fn customer_id(&self) -> u32 { self.customer_id }
+    pub fn product_name(&self) -> &str { &self.product_name }
+    pub fn quantity(&self) -> u32 { self.quantity }
+    pub fn unit_price(&self) -> f64 { self.unit_price }
+    pub fn allocated(&self) -> u32 { self.allocated }
+    pub fn outstanding(&self) -> u32 { self.quantity - self.allocated }
+    pub fn is_fulfilled(&self) -> bool { self.fulfilled }
+    pub fn is_ready(&self) -> bool { !self.fulfilled && self.allocated == self.quantity }
+}
+
+/// Lines a customer is about to buy, with any coupon codes they presented.
+pub struct Cart {
+    customer_id: Option<u32>,
+    lines: Vec<CartLine>,
+    coupons: Vec<String>,
+    tenders: Vec<Tender>,
+    gift_cards: Vec<f64>,
+}
+
+pub struct CartLine {
+    product_name: String,
+    quantity: u32,
+    unit_price: f64,
+}
+
+impl CartLine {
+    pub fn product_name(&self) -> &str { &self.product_name }
+    pub fn quantity(&self) -> u32 { self.quantity }
+    pub fn unit_price(&self) -> f64 { self.unit_price }
+}
+
+impl Default for Cart {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Cart {
+    pub fn new() -> Self {
+        Self {
+            customer_id: None,
+            lines: Vec::new(),
+            coupons: Vec::new(),
+            tenders: Vec::new(),
+            gift_cards: Vec::new(),
+        }
+    }
+
+    pub fn for_customer(customer_id: u32) -> Self {
+        Self {
+            customer_id: Some(customer_id),
+            ..Self::new()
+        }
+    }
+
+    pub fn add_line(&mut self, product_name: &str, quantity: u32, unit_price: f64) {
+        self.lines.push(CartLine {
+            product_name: product_name.to_string(),
+            quantity,
+            unit_price,
+        });
+    }
+
+    pub fn add_coupon(&mut self, code: &str) {
+        self.coupons.push(code.to_string());
+    }
+
+    pub fn add_tender(&mut self, tender: Tender) {
+        self.tenders.push(tender);
+    }
+
+    /// Sells a new gift card loaded with `amount`. Gift cards carry no tax
+    /// or promotions, and the card is issued when the order completes.
+    pub fn add_gift_card(&mut self, amount: f64) {
+        self.gift_cards.push(amount);
+    }
+
+    pub fn customer_id(&self) -> Option<u32> { self.customer_id }
+    pub fn lines(&self) -> &[CartLine] { &self.lines }
+    pub fn coupons(&self) -> &[String] { &self.coupons }
+    pub fn tenders(&self) -> &[Tender] { &self.tenders }
+    pub fn gift_cards(&self) -> &[f64] { &self.gift_cards }
+}
+
+/// A cart line with promotions and tax worked out, ready to be posted.
+struct PricedLine {
+    category: String,
+    unit_price: f64,
+    price_list: Option<String>,
+    discounts: Vec<AppliedPromotion>,
+    total_price: f64,
+    tax_rate: f64,
+    tax: f64,
+    net_amount: f64,
+}
+
+/// One checkout: the sale lines rung up together for a customer.
+pub struct Order {
+    id: u32,
+    customer_id: Option<u32>,
+    coupons: Vec<String>,
+    sale_ids: Vec<u32>,
+    tax_lines: Vec<TaxLine>,
+    total: f64,
+    payments: Vec<Payment>,
+    change: f64,
+    shift_id: Option<u32>,
+    gift_cards: Vec<String>,
+}
+
+impl Order {
+    pub fn id(&self) -> u32 { self.id }
+    pub fn customer_id(&self) -> Option<u32> { self.customer_id }
+    pub fn coupons(&self) -> &[String] { &self.coupons }
+    pub fn sale_ids(&self) -> &[u32] { &self.sale_ids }
+    /// Net amount and tax per rate, rounded to cents.
+    pub fn tax_lines(&self) -> &[TaxLine] { &self.tax_lines }
+    pub fn tax_total(&self) -> f64 { self.tax_lines.iter().map(|l| l.tax()).sum() }
+    /// Amount payable for the order, tax included.
+    pub fn total(&self) -> f64 { self.total }
+    /// How the order was paid, one entry per tender.
+    pub fn payments(&self) -> &[Payment] { &self.payments }
+    pub fn amount_tendered(&self) -> f64 { self.payments.iter().map(|p| p.amount()).sum() }
+    /// Cash handed back to the customer.
+    pub fn change(&self) -> f64 { self.change }
+    /// The till shift the order was rung up in, if one was open.
+    pub fn shift_id(&self) -> Option<u32> { self.shift_id }
+    /// Numbers of the gift cards sold on this order.
+    pub fn gift_cards(&self) -> &[String] { &self.gift_cards }
 }
 
 pub struct Transactions {
     sales: Vec<Sale>,
+    orders: Vec<Order>,
+    promotions: Promotions,
+    tax: TaxSettings,
     purchases: Vec<Purchase>,
+    backorders: Vec<Backorder>,
+    next_backorder_id: u32,
+    returns: Vec<SaleReturn>,
+    loyalty: Loyalty,
+    shifts: Vec<Shift>,
+    stored_value: StoredValue,
+    price_lists: PriceLists,
+    rates: ExchangeRates,
+    landed_charges: Vec<LandedCharge>,
+    supplier_returns: Vec<SupplierReturn>,
+    stock_counts: Vec<StockCount>,
+    procurement: Procurement,
+    journal: Journal,
+    chart: ChartOfAccounts,
+    costing: CostingMethod,
+    /// Time to record at instead of the system clock.
+    time: Option<Timestamp>,
+}
+
+impl Default for Transactions {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Transactions {
     pub fn new() -> Self {
         Self {
             sales: Vec::new(),
-            purchases: Vec::new()
+            orders: Vec::new(),
+            promotions: Promotions::new(),
+            tax: TaxSettings::default(),
+            purchases: Vec::new(),
+            backorders: Vec::new(),
+            next_backorder_id: 1,
+            returns: Vec::new(),
+            loyalty: Loyalty::default(),
+            shifts: Vec::new(),
+            stored_value: StoredValue::new(),
+            price_lists: PriceLists::new(),
+            rates: ExchangeRates::default(),
+            landed_charges: Vec::new(),
+            supplier_returns: Vec::new(),
+            stock_counts: Vec::new(),
+            procurement: Procurement::new(),
+            journal: Journal::new(),
+            chart: ChartOfAccounts::new(),
+            costing: CostingMethod::default(),
+            time: None,
         }
     }
 
+    /// The time sales, purchases and postings are recorded at.
+    pub fn now(&self) -> Timestamp {
+        self.time.unwrap_or_else(clock::now)
+    }
+
+    /// Records everything that follows at `at` rather than the system time,
+    /// e.g. to enter past trading; `None` goes back to the system clock.
+    pub fn set_time(&mut self, at: Option<Timestamp>) {
+        self.time = at;
+    }
+
+    /// Records a counter sale paid in exact cash.
     pub fn record_sale(
         &mut self,
         product_name: &str,
@@ -56,67 +444,1418 @@ impl Transactions {
         unit_price: f64,
         inv: &mut Inventory,
     ) -> Result<&Sale, StoreError> {
-        if quantity == 0 {
-            return Err(StoreError::InvalidInput("Quantity must be > 0".into()));
+        let mut cart = Cart::new();
+        cart.add_line(product_name, quantity, unit_price);
+        self.complete(&cart, inv, true)?;
+        Ok(self.sales.last().unwrap())
+    }
+
+    /// Records a sale made to a known customer, paid in exact cash.
+    pub fn record_sale_for(
+        &mut self,
+        customer_id: u32,
+        product_name: &str,
+        quantity: u32,
+        unit_price: f64,
+        inv: &mut Inventory,
+    ) -> Result<&Sale, StoreError> {
+        let mut cart = Cart::for_customer(customer_id);
+        cart.add_line(product_name, quantity, unit_price);
+        self.complete(&cart, inv, true)?;
+        Ok(self.sales.last().unwrap())
+    }
+
+    /// Sells every line of the cart as one order, paid with the cart's
+    /// tenders. Nothing is recorded if any line is invalid or short of stock,
+    /// or if the tenders do not cover the order total.
+    pub fn checkout(&mut self, cart: &Cart, inv: &mut Inventory) -> Result<&Order, StoreError> {
+        self.complete(cart, inv, false)
+    }
+
+    /// Amount the cart would cost at checkout, tax included, without
+    /// recording anything.
+    pub fn quote(&self, cart: &Cart, inv: &Inventory) -> Result<f64, StoreError> {
+        let priced = self.price_cart(cart, inv)?;
+        Ok(self.order_totals(cart, &priced).1)
+    }
+
+    fn price_cart(&self, cart: &Cart, inv: &Inventory) -> Result<Vec<PricedLine>, StoreError> {
+        if cart.lines.is_empty() && cart.gift_cards.is_empty() {
+            return Err(StoreError::InvalidInput("Cart is empty".into()));
         }
-        if unit_price <= 0.0 {
-            return Err(StoreError::InvalidInput("Unit price must be > 0".into()));
+        if cart.gift_cards.iter().any(|a| *a <= 0.0) {
+            return Err(StoreError::InvalidInput("Gift card amount must be > 0".into()));
+        }
+        for code in &cart.coupons {
+            if !self.promotions.is_coupon(code) {
+                return Err(StoreError::InvalidInput(format!("Unknown coupon {code}")));
+            }
         }
+        Self::check_stock(cart, inv)?;
 
-        if let Some(p) = inv.find_mut(product_name) {
-            if p.quantity() < quantity {
+        let list = cart.customer_id.and_then(|c| self.price_lists.list_for(c));
+        Ok(cart
+            .lines
+            .iter()
+            .map(|l| self.price_line(&l.product_name, l.quantity, l.unit_price, list, &cart.coupons, inv))
+            .collect())
+    }
+
+    fn complete(&mut self, cart: &Cart, inv: &mut Inventory, exact_cash: bool) -> Result<&Order, StoreError> {
+        inv.release_expired(self.now());
+        let priced = self.price_cart(cart, inv)?;
+        let (tax_lines, total) = self.order_totals(cart, &priced);
+        // nothing to tender for a free order
+        let tenders = match exact_cash {
+            true if total == 0.0 => Vec::new(),
+            true => vec![Tender::Cash(total)],
+            false => cart.tenders.clone(),
+        };
+        // checked here, as cards are only issued once stock has been taken
+        if cart.gift_cards.iter().any(|a| *a <= 0.0) {
+            return Err(StoreError::InvalidInput("Card amount must be > 0".into()));
+        }
+        if !cart.gift_cards.is_empty() && tenders.iter().any(|t| t.kind() == TenderKind::LoyaltyPoints) {
+            return Err(StoreError::InvalidInput("Points cannot pay for gift cards".into()));
+        }
+        let (payments, change, points) = self.settle(&tenders, total, cart.customer_id)?;
+        let costs = self.line_costs(cart, inv);
+        let entry = self.order_entry(cart, &tax_lines, total, &payments, change, &costs)?;
+
+        let now = self.now();
+        let order = self.open_order(cart.customer_id, &cart.coupons);
+        let order_id = self.orders[order].id;
+        for ((line, price), cost) in cart.lines.iter().zip(priced).zip(costs) {
+            self.push_sale(order, &line.product_name, line.quantity, price, cost, inv);
+        }
+        if points > 0 {
+            self.allocate_points(order, points, total);
+        }
+        for p in payments.iter().filter(|p| matches!(p.kind(), TenderKind::GiftCard | TenderKind::StoreCredit)) {
+            self.stored_value.redeem(p.reference(), p.amount(), order_id, now);
+        }
+        for amount in &cart.gift_cards {
+            let card = self.stored_value.issue(CardKind::GiftCard, *amount, cart.customer_id, now)?;
+            let number = card.number().to_string();
+            self.orders[order].gift_cards.push(number);
+        }
+
+        self.journal.post(entry);
+
+        let o = &mut self.orders[order];
+        o.tax_lines = tax_lines;
+        o.total = total;
+        o.payments = payments;
+        o.change = change;
+        Ok(o)
+    }
+
+    // Revenue is what is left of the order total after tax and gift cards,
+    // so the entry balances against the payments taken to the cent.
+    fn order_entry(
+        &self,
+        cart: &Cart,
+        tax_lines: &[TaxLine],
+        total: f64,
+        payments: &[Payment],
+        change: f64,
+        costs: &[Option<f64>],
+    ) -> Result<JournalEntry, StoreError> {
+        let paid = |kinds: &[TenderKind]| -> f64 {
+            payments.iter().filter(|p| kinds.contains(&p.kind())).map(|p| p.amount()).sum()
+        };
+        let tax_total: f64 = tax_lines.iter().map(|l| l.tax()).sum();
+        let gift_cards: f64 = cart.gift_cards.iter().sum();
+        let cost = tax::round_cents(costs.iter().flatten().sum());
+        let lines = vec![
+            JournalLine::debit(AccountRole::Cash, paid(&[TenderKind::Cash]) - change),
+            JournalLine::debit(AccountRole::CardClearing, paid(&[TenderKind::Card])),
+            JournalLine::debit(AccountRole::StoredValue, paid(&[TenderKind::GiftCard, TenderKind::StoreCredit])),
+            JournalLine::debit(AccountRole::LoyaltyRedemptions, paid(&[TenderKind::LoyaltyPoints])),
+            JournalLine::credit(AccountRole::Revenue, tax::round_cents(total - tax_total - gift_cards)),
+            JournalLine::credit(AccountRole::TaxPayable, tax_total),
+            JournalLine::credit(AccountRole::StoredValue, gift_cards),
+            JournalLine::debit(AccountRole::Cogs, cost),
+            JournalLine::credit(AccountRole::Inventory, cost),
+        ];
+        JournalEntry::new(EntryKind::Sale, &format!("Order #{}", self.orders.len() + 1), self.now(), lines)
+    }
+
+    /// Cost of goods sold for each cart line. Stock is taken line by line, so
+    /// under FIFO two lines of one product do not both use the oldest units.
+    fn line_costs(&self, cart: &Cart, inv: &Inventory) -> Vec<Option<f64>> {
+        let mut taken: Vec<(&str, u32)> = Vec::new();
+        cart.lines
+            .iter()
+            .map(|l| {
+                let before: u32 = taken.iter().filter(|(p, _)| *p == l.product_name).map(|(_, q)| q).sum();
+                taken.push((&l.product_name, l.quantity));
+                let cost = self.cost_of(&l.product_name, before + l.quantity, inv)?;
+                Some(tax::round_cents(cost - self.cost_of(&l.product_name, before, inv).unwrap_or(0.0)))
+            })
+            .collect()
+    }
+
+    // Units reserved for someone else cannot be sold over the counter.
+    // Lines are checked one by one, then combined per stocked product so
+    // that two lines (or a kit and its component) cannot oversell together.
+    fn check_stock(cart: &Cart, inv: &Inventory) -> Result<(), StoreError> {
+        let mut demand: Vec<(String, u32)> = Vec::new();
+        for line in &cart.lines {
+            let (product_name, quantity) = (line.product_name.as_str(), line.quantity);
+            if quantity == 0 {
+                return Err(StoreError::InvalidInput("Quantity must be > 0".into()));
+            }
+            if line.unit_price <= 0.0 {
+                return Err(StoreError::InvalidInput("Unit price must be > 0".into()));
+            }
+            let p = inv
+                .find(product_name)
+                .ok_or_else(|| StoreError::NotFound(product_name.to_string()))?;
+            if p.is_parent() {
+                return Err(StoreError::InvalidInput(format!("{product_name} has variants; choose one")));
+            }
+            let available = inv.available(product_name).unwrap_or(0);
+            if available < quantity {
                 return Err(StoreError::OutOfStock {
                     product: product_name.to_string(),
                     requested: quantity,
-                    available: p.quantity(),
+                    available,
                 });
             }
-            p.remove_stock(quantity);
 
-            let total_price = quantity as f64 * unit_price;
-            self.sales.push(Sale {
-                product_name: product_name.to_string(),
-                quantity,
-                unit_price,
-                total_price,
-            });
-            Ok(self.sales.last().unwrap())
+            let stocked: Vec<(String, u32)> = if p.is_kit() {
+                p.components().iter().map(|(c, per_kit)| (c.clone(), per_kit * quantity)).collect()
+            } else {
+                vec![(product_name.to_string(), quantity)]
+            };
+            for (name, qty) in stocked {
+                match demand.iter_mut().find(|(n, _)| *n == name) {
+                    Some((_, total)) => *total += qty,
+                    None => demand.push((name, qty)),
+                }
+            }
+        }
+        for (name, requested) in &demand {
+            let available = inv.available(name).unwrap_or(0);
+            if available < *requested {
+                return Err(StoreError::OutOfStock {
+                    product: name.clone(),
+                    requested: *requested,
+                    available,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Prices one line. A customer on a price list pays the list price
+    /// instead of the unit price given at the till.
+    fn price_line(
+        &self,
+        product_name: &str,
+        quantity: u32,
+        unit_price: f64,
+        list: Option<&PriceList>,
+        coupons: &[String],
+        inv: &Inventory,
+    ) -> PricedLine {
+        let product = inv.find(product_name);
+        let category = product.map_or(String::new(), |p| p.category().to_string());
+        let tax_class = product.map(|p| p.tax_class()).unwrap_or_default();
+        let (unit_price, price_list) = match (list, product) {
+            (Some(list), Some(p)) => (list.unit_price(p, self.now()), Some(list.name().to_string())),
+            _ => (unit_price, None),
+        };
+
+        let discounts = self.promotions.apply(product_name, &category, quantity, unit_price, self.now(), coupons);
+        let discount: f64 = discounts.iter().map(|d| d.discount()).sum();
+        let total_price = quantity as f64 * unit_price - discount;
+        let tax = self.tax.line_tax(total_price, tax_class);
+        let net_amount = match self.tax.mode() {
+            PricingMode::TaxInclusive => total_price - tax,
+            PricingMode::TaxExclusive => total_price,
+        };
+        PricedLine {
+            category,
+            unit_price,
+            price_list,
+            discounts,
+            total_price,
+            tax_rate: self.tax.rate(tax_class),
+            tax,
+            net_amount,
+        }
+    }
+
+    // Tax is summed per rate across the order's lines and rounded once per
+    // rate, so per-order rounding never drifts by more than a cent per rate.
+    fn order_totals(&self, cart: &Cart, lines: &[PricedLine]) -> (Vec<TaxLine>, f64) {
+        let tax_lines = tax::tax_by_rate(lines.iter().map(|l| (l.tax_rate, l.net_amount, l.tax)));
+        let goods: f64 = match self.tax.mode() {
+            PricingMode::TaxInclusive => lines.iter().map(|l| l.total_price).sum(),
+            PricingMode::TaxExclusive => tax_lines.iter().map(|l| l.net() + l.tax()).sum(),
+        };
+        let gift_cards: f64 = cart.gift_cards.iter().sum();
+        (tax_lines, tax::round_cents(goods + gift_cards))
+    }
+
+    /// Checks the tenders against the order total without touching any
+    /// balance. Only cash may exceed what is due; the excess is change.
+    fn settle(
+        &self,
+        tenders: &[Tender],
+        total: f64,
+        customer_id: Option<u32>,
+    ) -> Result<(Vec<Payment>, f64, u32), StoreError> {
+        let now = self.now();
+        let mut payments = Vec::new();
+        let (mut cash, mut other, mut points) = (0.0, 0.0, 0u32);
+        for t in tenders {
+            match t {
+                Tender::Cash(amount) | Tender::Card(amount) => {
+                    if *amount <= 0.0 {
+                        return Err(StoreError::InvalidInput("Tender amount must be > 0".into()));
+                    }
+                    if t.kind() == TenderKind::Cash {
+                        cash += amount;
+                    } else {
+                        other += amount;
+                    }
+                    payments.push(Payment::new(t.kind(), "", *amount));
+                }
+                Tender::StoreCredit { number, amount } | Tender::GiftCard { number, amount } => {
+                    if *amount <= 0.0 {
+                        return Err(StoreError::InvalidInput("Tender amount must be > 0".into()));
+                    }
+                    let Some(card) = self.stored_value.find(number) else {
+                        return Err(StoreError::NotFound(format!("stored-value card {number}")));
+                    };
+                    let kind = match card.kind() {
+                        CardKind::GiftCard => TenderKind::GiftCard,
+                        CardKind::StoreCredit => TenderKind::StoreCredit,
+                    };
+                    if kind != t.kind() {
+                        return Err(StoreError::InvalidInput(format!("{number} is not a {:?}", t.kind())));
+                    }
+                    if card.is_expired(now) {
+                        return Err(StoreError::InvalidInput(format!("{number} has expired")));
+                    }
+                    // the same card may be presented more than once
+                    let used: f64 = payments
+                        .iter()
+                        .filter(|p| p.reference() == card.number())
+                        .map(|p| p.amount())
+                        .sum();
+                    let left = card.balance() - used;
+                    if tax::round_cents(amount - left) > 0.0 {
+                        return Err(StoreError::InvalidInput(format!("Only {left:.2} left on {number}")));
+                    }
+                    other += amount;
+                    payments.push(Payment::new(kind, card.number(), *amount));
+                }
+                Tender::LoyaltyPoints(p) => {
+                    let Some(customer_id) = customer_id else {
+                        return Err(StoreError::InvalidInput("Anonymous sales cannot use points".into()));
+                    };
+                    if *p == 0 {
+                        return Err(StoreError::InvalidInput("Points must be > 0".into()));
+                    }
+                    points += p;
+                    let balance = self.loyalty.balance(customer_id);
+                    if points as i64 > balance {
+                        return Err(StoreError::InvalidInput(format!("Only {balance} points available")));
+                    }
+                    let value = tax::round_cents(*p as f64 * self.loyalty.rules().point_value());
+                    other += value;
+                    payments.push(Payment::new(TenderKind::LoyaltyPoints, &p.to_string(), value));
+                }
+            }
+        }
+
+        if tax::round_cents(other - total) > 0.0 {
+            return Err(StoreError::InvalidInput(format!(
+                "Non-cash tenders ({other:.2}) exceed the {total:.2} due"
+            )));
+        }
+        let paid = tax::round_cents(cash + other);
+        if paid < total {
+            return Err(StoreError::InsufficientPayment { due: total, paid });
+        }
+        Ok((payments, tax::round_cents(paid - total), points))
+    }
+
+    // Redeemed points are spread over the order's lines by value so a later
+    // return of one line gives back its share.
+    fn allocate_points(&mut self, order: usize, points: u32, total: f64) {
+        let Some(customer_id) = self.orders[order].customer_id else { return };
+        let point_value = self.loyalty.rules().point_value();
+        let ids = self.orders[order].sale_ids.clone();
+        let mut left = points;
+        for (i, id) in ids.iter().enumerate() {
+            let sale = &mut self.sales[*id as usize - 1];
+            let share = if i + 1 == ids.len() || total <= 0.0 {
+                left
+            } else {
+                ((points as f64 * sale.gross_amount() / total).floor() as u32).min(left)
+            };
+            left -= share;
+            sale.points_redeemed = share;
+            sale.points_value = share as f64 * point_value;
+            self.loyalty.post(customer_id, *id, PointsKind::Redeemed, -(share as i64));
+        }
+    }
+
+    /// Completes a reservation as a sale paid in exact cash, consuming the held units.
+    pub fn record_reserved_sale(
+        &mut self,
+        reservation_id: u32,
+        unit_price: f64,
+        inv: &mut Inventory,
+    ) -> Result<&Sale, StoreError> {
+        if unit_price <= 0.0 {
+            return Err(StoreError::InvalidInput("Unit price must be > 0".into()));
+        }
+        inv.release_expired(self.now());
+        // released first so the sale can take the held units; put back if it fails
+        let reservation = inv.release_reservation(reservation_id)?;
+
+        let mut cart = Cart::new();
+        cart.add_line(reservation.product_name(), reservation.quantity(), unit_price);
+        if let Err(e) = self.complete(&cart, inv, true) {
+            inv.restore_reservations(vec![reservation]);
+            return Err(e);
+        }
+        Ok(self.sales.last().unwrap())
+    }
+
+    fn open_order(&mut self, customer_id: Option<u32>, coupons: &[String]) -> usize {
+        self.orders.push(Order {
+            id: self.orders.len() as u32 + 1,
+            customer_id,
+            coupons: coupons.iter().map(|c| c.to_uppercase()).collect(),
+            sale_ids: Vec::new(),
+            tax_lines: Vec::new(),
+            total: 0.0,
+            payments: Vec::new(),
+            change: 0.0,
+            shift_id: self.current_shift().map(|s| s.id()),
+            gift_cards: Vec::new(),
+        });
+        self.orders.len() - 1
+    }
+
+    // Callers check availability first, so a kit's components are either all
+    // deducted here or the sale was rejected before touching any stock.
+    fn push_sale(
+        &mut self,
+        order: usize,
+        product_name: &str,
+        quantity: u32,
+        price: PricedLine,
+        cost: Option<f64>,
+        inv: &mut Inventory,
+    ) -> &Sale {
+        let (order_id, customer_id) = (self.orders[order].id, self.orders[order].customer_id);
+        let total_price = price.total_price;
+        let sold_at = self.now();
+        let bom = inv.find(product_name).map(|p| p.components().to_vec()).unwrap_or_default();
+
+        let mut components = Vec::new();
+        if bom.is_empty() {
+            if let Some(p) = inv.find_mut(product_name) {
+                p.remove_stock(quantity);
+            }
         } else {
-            Err(StoreError::NotFound(product_name.to_string()))
+            let weights: Vec<f64> = bom
+                .iter()
+                .map(|(c, per_kit)| inv.find(c).map_or(0.0, |p| p.price_at(sold_at)) * *per_kit as f64)
+                .collect();
+            let weight_sum: f64 = weights.iter().sum();
+            let units_sum: u32 = bom.iter().map(|(_, per_kit)| per_kit).sum();
+
+            for ((component, per_kit), weight) in bom.iter().zip(weights) {
+                let share = if weight_sum > 0.0 {
+                    weight / weight_sum
+                } else {
+                    *per_kit as f64 / units_sum as f64
+                };
+                if let Some(p) = inv.find_mut(component) {
+                    p.remove_stock(per_kit * quantity);
+                }
+                components.push(SaleComponent {
+                    product_name: component.clone(),
+                    quantity: per_kit * quantity,
+                    total_price: total_price * share,
+                });
+            }
+        }
+
+        let id = self.sales.len() as u32 + 1;
+        let list_price = inv.find(product_name).map_or(0.0, |p| p.price_at(sold_at));
+        if let Some(customer_id) = customer_id {
+            let points = self.loyalty.rules().points_for(total_price, &price.category);
+            self.loyalty.post(customer_id, id, PointsKind::Earned, points);
+        }
+
+        self.orders[order].sale_ids.push(id);
+        self.sales.push(Sale {
+            id,
+            order_id,
+            product_name: product_name.to_string(),
+            quantity,
+            unit_price: price.unit_price,
+            price_list: price.price_list,
+            list_price,
+            sold_at,
+            discounts: price.discounts,
+            total_price,
+            tax_rate: price.tax_rate,
+            tax: price.tax,
+            net_amount: price.net_amount,
+            components,
+            customer_id,
+            points_redeemed: 0,
+            points_value: 0.0,
+            cost,
+        });
+        self.sales.last().unwrap()
+    }
+
+    /// Takes units of an earlier sale back into stock. Points the sale earned
+    /// are clawed back and points it was paid with are restored, both in
+    /// proportion to the units returned. The rest is refunded in cash.
+    pub fn record_return(&mut self, sale_id: u32, quantity: u32, inv: &mut Inventory) -> Result<&SaleReturn, StoreError> {
+        self.return_sale(sale_id, quantity, inv, false)
+    }
+
+    /// Like `record_return`, but the refund is issued as a new store credit
+    /// card for the sale's customer instead of cash.
+    pub fn record_return_for_credit(
+        &mut self,
+        sale_id: u32,
+        quantity: u32,
+        inv: &mut Inventory,
+    ) -> Result<&SaleReturn, StoreError> {
+        self.return_sale(sale_id, quantity, inv, true)
+    }
+
+    fn return_sale(&mut self, sale_id: u32, quantity: u32, inv: &mut Inventory, to_credit: bool) -> Result<&SaleReturn, StoreError> {
+        if quantity == 0 {
+            return Err(StoreError::InvalidInput("Quantity must be > 0".into()));
+        }
+        let sale = self.find_sale(sale_id)?;
+        let already: u32 = self.returns.iter().filter(|r| r.sale_id == sale_id).map(|r| r.quantity).sum();
+        if already + quantity > sale.quantity {
+            return Err(StoreError::InvalidInput(format!(
+                "Only {} of sale #{sale_id} can still be returned",
+                sale.quantity - already
+            )));
+        }
+
+        let share = quantity as f64 / sale.quantity as f64;
+        let refund = tax::round_cents(sale.gross_amount() * share);
+        let tax_refunded = tax::round_cents(sale.tax * share);
+        let points_restored = if sale.customer_id.is_some() { (sale.points_redeemed as f64 * share).round() as u32 } else { 0 };
+        let cash_refund = tax::round_cents(refund - points_restored as f64 * self.loyalty.rules().point_value());
+        // reverses the cost the sale posted, not today's
+        let cost = tax::round_cents(sale.cost.unwrap_or(0.0) * share);
+        let refunded_to = if to_credit && cash_refund > 0.0 { AccountRole::StoredValue } else { AccountRole::Cash };
+        let entry = JournalEntry::new(
+            EntryKind::Return,
+            &format!("Return of sale #{sale_id}"),
+            self.now(),
+            vec![
+                JournalLine::debit(AccountRole::Revenue, refund - tax_refunded),
+                JournalLine::debit(AccountRole::TaxPayable, tax_refunded),
+                JournalLine::credit(refunded_to, cash_refund),
+                JournalLine::credit(AccountRole::LoyaltyRedemptions, refund - cash_refund),
+                JournalLine::debit(AccountRole::Inventory, cost),
+                JournalLine::credit(AccountRole::Cogs, cost),
+            ],
+        )?;
+
+        if sale.is_kit() {
+            for c in &sale.components {
+                if let Some(p) = inv.find_mut(&c.product_name) {
+                    p.add_stock(c.quantity / sale.quantity * quantity);
+                }
+            }
+        } else if let Some(p) = inv.find_mut(&sale.product_name) {
+            p.add_stock(quantity);
+        }
+
+        let customer_id = sale.customer_id;
+        let product_name = sale.product_name.clone();
+
+        if let Some(customer_id) = customer_id {
+            let earned = self.loyalty.points_for_sale(sale_id, PointsKind::Earned);
+            self.loyalty.post(customer_id, sale_id, PointsKind::ClawedBack, -(earned as f64 * share).round() as i64);
+            self.loyalty.post(customer_id, sale_id, PointsKind::Restored, points_restored as i64);
+        }
+
+        let mut cash_refund = cash_refund;
+        let mut credit_card = None;
+        if to_credit && cash_refund > 0.0 {
+            let card = self.stored_value.issue(CardKind::StoreCredit, cash_refund, customer_id, self.now())?;
+            credit_card = Some(card.number().to_string());
+            cash_refund = 0.0;
         }
+        self.returns.push(SaleReturn {
+            sale_id,
+            product_name,
+            quantity,
+            refund,
+            tax: tax_refunded,
+            points_restored,
+            cash_refund,
+            credit_card,
+            shift_id: self.current_shift().map(|s| s.id()),
+            returned_at: self.now(),
+            cost,
+        });
+        self.journal.post(entry);
+        Ok(self.returns.last().unwrap())
+    }
+
+    fn find_sale(&self, sale_id: u32) -> Result<&Sale, StoreError> {
+        self.sales
+            .iter()
+            .find(|s| s.id == sale_id)
+            .ok_or_else(|| StoreError::NotFound(format!("sale #{sale_id}")))
+    }
+
+    pub fn stored_value(&self) -> &StoredValue {
+        &self.stored_value
+    }
+
+    pub fn stored_value_mut(&mut self) -> &mut StoredValue {
+        &mut self.stored_value
+    }
+
+    /// Writes off what is left on cards expired by `now`, posting it from
+    /// the card liability to breakage income. Returns the card numbers.
+    pub fn expire_cards(&mut self, now: Timestamp) -> Result<Vec<String>, StoreError> {
+        let left: f64 = self
+            .stored_value
+            .all()
+            .iter()
+            .filter(|c| c.is_expired(now) && c.balance() > 0.0)
+            .map(|c| c.balance())
+            .sum();
+        let entry = JournalEntry::new(
+            EntryKind::Breakage,
+            "Expired card balances",
+            now,
+            vec![
+                JournalLine::debit(AccountRole::StoredValue, left),
+                JournalLine::credit(AccountRole::Breakage, left),
+            ],
+        )?;
+        let expired = self.stored_value.expire(now);
+        if !entry.lines().is_empty() {
+            self.journal.post(entry);
+        }
+        Ok(expired)
+    }
+
+    pub fn loyalty(&self) -> &Loyalty {
+        &self.loyalty
+    }
+
+    pub fn loyalty_mut(&mut self) -> &mut Loyalty {
+        &mut self.loyalty
+    }
+
+    /// Opens a till shift; orders and returns are attributed to it until it closes.
+    pub fn open_shift(&mut self, opened_by: &str, opening_float: f64) -> Result<&Shift, StoreError> {
+        if let Some(open) = self.current_shift() {
+            return Err(StoreError::InvalidInput(format!("Shift #{} is still open", open.id())));
+        }
+        if opening_float < 0.0 {
+            return Err(StoreError::InvalidInput("Opening float must be >= 0".into()));
+        }
+        let id = self.shifts.len() as u32 + 1;
+        self.shifts.push(Shift::new(id, opened_by, opening_float, self.now()));
+        Ok(self.shifts.last().unwrap())
+    }
+
+    /// Closes the open shift with the amounts the cashier counted per tender.
+    pub fn close_shift(&mut self, counted: &[(TenderKind, f64)]) -> Result<&Shift, StoreError> {
+        let now = self.now();
+        let Some(shift) = self.shifts.iter_mut().find(|s| s.is_open()) else {
+            return Err(StoreError::NotFound("open shift".into()));
+        };
+        if counted.iter().any(|(_, amount)| *amount < 0.0) {
+            return Err(StoreError::InvalidInput("Counted amounts must be >= 0".into()));
+        }
+        shift.close(counted, now);
+        Ok(shift)
+    }
+
+    pub fn current_shift(&self) -> Option<&Shift> {
+        self.shifts.iter().find(|s| s.is_open())
     }
 
+    pub fn find_shift(&self, id: u32) -> Option<&Shift> {
+        self.shifts.iter().find(|s| s.id() == id)
+    }
+
+    pub fn get_shifts(&self) -> &[Shift] {
+        &self.shifts
+    }
+
+    pub fn tax_settings(&self) -> &TaxSettings {
+        &self.tax
+    }
+
+    pub fn set_tax_settings(&mut self, tax: TaxSettings) {
+        self.tax = tax;
+    }
+
+    pub fn exchange_rates(&self) -> &ExchangeRates {
+        &self.rates
+    }
+
+    pub fn exchange_rates_mut(&mut self) -> &mut ExchangeRates {
+        &mut self.rates
+    }
+
+    pub fn price_lists(&self) -> &PriceLists {
+        &self.price_lists
+    }
+
+    pub fn price_lists_mut(&mut self) -> &mut PriceLists {
+        &mut self.price_lists
+    }
+
+    pub fn promotions(&self) -> &Promotions {
+        &self.promotions
+    }
+
+    pub fn promotions_mut(&mut self) -> &mut Promotions {
+        &mut self.promotions
+    }
+
+    pub fn sale(&self, sale_id: u32) -> Option<&Sale> {
+        self.find_sale(sale_id).ok()
+    }
+
+    pub fn get_orders(&self) -> &[Order] {
+        &self.orders
+    }
+
+    pub fn find_order(&self, order_id: u32) -> Option<&Order> {
+        self.orders.iter().find(|o| o.id == order_id)
+    }
+
+    /// Sale lines of an order, in the order they were rung up.
+    pub fn order_lines(&self, order_id: u32) -> Vec<&Sale> {
+        self.sales.iter().filter(|s| s.order_id == order_id).collect()
+    }
+
+    pub fn get_returns(&self) -> &[SaleReturn] {
+        &self.returns
+    }
+
+    /// Records a purchase priced in the base currency.
     pub fn record_purchase(
         &mut self,
         product_name: &str,
         quantity: u32,
         unit_price: f64,
         inv: &mut Inventory,
+    ) -> Result<&Purchase, StoreError> {
+        let unit_price = Money::new(unit_price, self.rates.base());
+        self.record_purchase_in(product_name, quantity, unit_price, inv)
+    }
+
+    /// Records a purchase priced in the supplier's currency, converted to
+    /// the base currency at today's rate.
+    pub fn record_purchase_in(
+        &mut self,
+        product_name: &str,
+        quantity: u32,
+        supplier_unit_price: Money,
+        inv: &mut Inventory,
     ) -> Result<&Purchase, StoreError> {
         if quantity == 0 {
             return Err(StoreError::InvalidInput("Quantity must be > 0".into()));
         }
-        if unit_price <= 0.0 {
+        if supplier_unit_price.amount() <= 0.0 {
             return Err(StoreError::InvalidInput("Unit price must be > 0".into()));
         }
+        let purchased_at = self.now();
+        let exchange_rate = self
+            .rates
+            .rate_at(supplier_unit_price.currency(), purchased_at)
+            .ok_or_else(|| StoreError::NotFound(format!("exchange rate for {}", supplier_unit_price.currency())))?;
+        let unit_price = supplier_unit_price.amount() * exchange_rate;
 
+        match inv.find(product_name) {
+            Some(p) if p.is_kit() => {
+                return Err(StoreError::InvalidInput(format!("{product_name} is a kit; purchase its components")));
+            }
+            Some(p) if p.is_parent() => {
+                return Err(StoreError::InvalidInput(format!("{product_name} has variants; choose one")));
+            }
+            Some(_) => {}
+            None => return Err(StoreError::NotFound(product_name.to_string())),
+        }
+
+        let total_cost = quantity as f64 * unit_price;
+        let id = self.purchases.len() as u32 + 1;
+        let entry = JournalEntry::new(
+            EntryKind::Purchase,
+            &format!("Purchase #{id}"),
+            purchased_at,
+            vec![
+                JournalLine::debit(AccountRole::Inventory, total_cost),
+                JournalLine::credit(AccountRole::AccountsPayable, total_cost),
+            ],
+        )?;
         if let Some(p) = inv.find_mut(product_name) {
             p.add_stock(quantity);
+        }
+        self.allocate_backorders(product_name, quantity, inv);
+        self.journal.post(entry);
+        self.purchases.push(Purchase {
+            id,
+            product_name: product_name.to_string(),
+            quantity,
+            unit_price,
+            total_cost,
+            supplier_unit_price,
+            exchange_rate,
+            purchased_at,
+            landed_charges: 0.0,
+            po_id: None,
+        });
+        Ok(self.purchases.last().unwrap())
+    }
 
-            let total_cost = quantity as f64 * unit_price;
-            self.purchases.push(Purchase {
-                product_name: product_name.to_string(),
-                quantity,
-                unit_price,
-                total_cost,
+    /// Spreads a freight, duty or handling charge over the given purchases.
+    /// The charge may be billed in a foreign currency; it is converted at
+    /// today's rate.
+    pub fn add_landed_charge(
+        &mut self,
+        purchase_ids: &[u32],
+        kind: ChargeKind,
+        billed: Money,
+        basis: AllocationBasis,
+        inv: &Inventory,
+    ) -> Result<&LandedCharge, StoreError> {
+        if purchase_ids.is_empty() {
+            return Err(StoreError::InvalidInput("A charge needs at least one purchase".into()));
+        }
+        if billed.amount() <= 0.0 {
+            return Err(StoreError::InvalidInput("Charge amount must be > 0".into()));
+        }
+        let mut weights = Vec::new();
+        for (i, id) in purchase_ids.iter().enumerate() {
+            if purchase_ids[..i].contains(id) {
+                return Err(StoreError::InvalidInput(format!("Purchase #{id} listed twice")));
+            }
+            let p = self
+                .purchases
+                .iter()
+                .find(|p| p.id == *id)
+                .ok_or_else(|| StoreError::NotFound(format!("purchase #{id}")))?;
+            weights.push(match basis {
+                AllocationBasis::Value => p.total_cost,
+                AllocationBasis::Quantity => p.quantity as f64,
+                AllocationBasis::Weight => p.quantity as f64 * inv.find(&p.product_name).map_or(0.0, |pr| pr.weight()),
+            });
+        }
+        if weights.iter().sum::<f64>() <= 0.0 {
+            return Err(StoreError::InvalidInput(format!("Nothing to allocate by {:?}", basis)));
+        }
+        let amount = tax::round_cents(self.rates.to_base(&billed, self.now())?.amount());
+
+        let id = self.landed_charges.len() as u32 + 1;
+        let entry = JournalEntry::new(
+            EntryKind::LandedCharge,
+            &format!("Landed charge #{id}"),
+            self.now(),
+            vec![
+                JournalLine::debit(AccountRole::Inventory, amount),
+                JournalLine::credit(AccountRole::AccountsPayable, amount),
+            ],
+        )?;
+        self.journal.post(entry);
+
+        let shares = costing::split(amount, &weights);
+        for (id, share) in purchase_ids.iter().zip(&shares) {
+            if let Some(p) = self.purchases.iter_mut().find(|p| p.id == *id) {
+                p.landed_charges += share;
+            }
+        }
+        let allocations = purchase_ids.iter().copied().zip(shares).collect();
+        self.landed_charges.push(LandedCharge::new(id, kind, billed, amount, basis, allocations));
+        Ok(self.landed_charges.last().unwrap())
+    }
+
+    /// Sends units of an earlier purchase back to the supplier. The units
+    /// leave stock straight away and a debit note is raised for their cost.
+    /// Units held for customers cannot be sent back.
+    pub fn record_supplier_return(
+        &mut self,
+        purchase_id: u32,
+        quantity: u32,
+        reason: &str,
+        inv: &mut Inventory,
+    ) -> Result<&SupplierReturn, StoreError> {
+        if quantity == 0 {
+            return Err(StoreError::InvalidInput("Quantity must be > 0".into()));
+        }
+        let purchase = self
+            .purchases
+            .iter()
+            .find(|p| p.id == purchase_id)
+            .ok_or_else(|| StoreError::NotFound(format!("purchase #{purchase_id}")))?;
+        let already: u32 = self
+            .supplier_returns
+            .iter()
+            .filter(|r| r.purchase_id == purchase_id)
+            .map(|r| r.quantity)
+            .sum();
+        if already + quantity > purchase.quantity {
+            return Err(StoreError::InvalidInput(format!(
+                "Only {} of purchase #{purchase_id} can still be returned",
+                purchase.quantity - already
+            )));
+        }
+        let available = inv.available(&purchase.product_name).unwrap_or(0);
+        if available < quantity {
+            return Err(StoreError::OutOfStock {
+                product: purchase.product_name.clone(),
+                requested: quantity,
+                available,
             });
-            Ok(self.purchases.last().unwrap())
+        }
+
+        let product_name = purchase.product_name.clone();
+        let amount = tax::round_cents(quantity as f64 * purchase.unit_price);
+        let supplier_amount = Money::new(
+            tax::round_cents(quantity as f64 * purchase.supplier_unit_price.amount()),
+            purchase.supplier_unit_price.currency(),
+        );
+        let id = self.supplier_returns.len() as u32 + 1;
+        let entry = JournalEntry::new(
+            EntryKind::SupplierReturn,
+            &format!("Debit note DN-{id:04}"),
+            self.now(),
+            vec![
+                JournalLine::debit(AccountRole::AccountsPayable, amount),
+                JournalLine::credit(AccountRole::Inventory, amount),
+            ],
+        )?;
+        self.journal.post(entry);
+        if let Some(p) = inv.find_mut(&product_name) {
+            p.remove_stock(quantity);
+        }
+        self.supplier_returns.push(SupplierReturn {
+            id,
+            purchase_id,
+            product_name,
+            quantity,
+            reason: reason.to_string(),
+            amount,
+            supplier_amount,
+            returned_at: self.now(),
+        });
+        Ok(self.supplier_returns.last().unwrap())
+    }
+
+    /// Sets a product's stock to what was counted on the shelf. The
+    /// difference, at average cost, is posted to inventory adjustments.
+    /// Returns the change in units.
+    pub fn record_stock_count(
+        &mut self,
+        product_name: &str,
+        counted: u32,
+        reason: &str,
+        inv: &mut Inventory,
+    ) -> Result<i64, StoreError> {
+        let p = inv
+            .find(product_name)
+            .ok_or_else(|| StoreError::NotFound(product_name.to_string()))?;
+        if p.is_kit() || p.is_parent() {
+            return Err(StoreError::InvalidInput(format!("{product_name} has no stock of its own")));
+        }
+        let difference = counted as i64 - p.quantity() as i64;
+        let value = self.unit_cost(product_name, inv).unwrap_or(0.0) * difference as f64;
+        let reference = if reason.is_empty() {
+            format!("Stock count {product_name}")
         } else {
-            Err(StoreError::NotFound(product_name.to_string()))
+            format!("Stock count {product_name}: {reason}")
+        };
+        let entry = JournalEntry::new(
+            EntryKind::StockCount,
+            &reference,
+            self.now(),
+            vec![
+                JournalLine::debit(AccountRole::Inventory, value),
+                JournalLine::credit(AccountRole::InventoryAdjustments, value),
+            ],
+        )?;
+        if !entry.lines().is_empty() {
+            self.journal.post(entry);
+        }
+        if let Some(p) = inv.find_mut(product_name) {
+            p.set_quantity(counted);
         }
+        self.stock_counts.push(StockCount {
+            id: self.stock_counts.len() as u32 + 1,
+            product_name: product_name.to_string(),
+            counted,
+            difference,
+            reason: reason.to_string(),
+            counted_at: self.now(),
+        });
+        Ok(difference)
+    }
+
+    pub fn journal(&self) -> &Journal {
+        &self.journal
+    }
+
+    pub fn chart(&self) -> &ChartOfAccounts {
+        &self.chart
+    }
+
+    pub fn chart_mut(&mut self) -> &mut ChartOfAccounts {
+        &mut self.chart
+    }
+
+    pub fn procurement(&self) -> &Procurement {
+        &self.procurement
+    }
+
+    pub fn procurement_mut(&mut self) -> &mut Procurement {
+        &mut self.procurement
+    }
+
+    pub fn create_purchase_order(
+        &mut self,
+        supplier: &str,
+        lines: Vec<DocumentLine>,
+        inv: &Inventory,
+    ) -> Result<&PurchaseOrder, StoreError> {
+        if lines.is_empty() {
+            return Err(StoreError::InvalidInput("A purchase order needs at least one line".into()));
+        }
+        for (i, l) in lines.iter().enumerate() {
+            match inv.find(l.product_name()) {
+                None => return Err(StoreError::NotFound(l.product_name().to_string())),
+                Some(p) if p.is_kit() || p.is_parent() => {
+                    return Err(StoreError::InvalidInput(format!("{} cannot be purchased", l.product_name())));
+                }
+                Some(_) => {}
+            }
+            if l.quantity() == 0 || l.unit_price().amount() <= 0.0 {
+                return Err(StoreError::InvalidInput(format!("Invalid quantity or price for {}", l.product_name())));
+            }
+            if lines[..i].iter().any(|o| o.product_name() == l.product_name()) {
+                return Err(StoreError::InvalidInput(format!("{} is on the order twice", l.product_name())));
+            }
+        }
+        let id = self.procurement.orders().len() as u32 + 1;
+        Ok(self.procurement.push_order(PurchaseOrder::new(id, supplier, lines, self.now())))
+    }
+
+    /// Records a delivery against a purchase order, at the ordered price.
+    pub fn receive_purchase_order(
+        &mut self,
+        po_id: u32,
+        product_name: &str,
+        quantity: u32,
+        inv: &mut Inventory,
+    ) -> Result<&Purchase, StoreError> {
+        let po = self
+            .procurement
+            .find_order(po_id)
+            .ok_or_else(|| StoreError::NotFound(format!("purchase order #{po_id}")))?;
+        let line = po
+            .line(product_name)
+            .ok_or_else(|| StoreError::InvalidInput(format!("{product_name} is not on purchase order #{po_id}")))?;
+        let unit_price = line.unit_price().clone();
+        self.record_purchase_in(product_name, quantity, unit_price, inv)?;
+        let purchase = self.purchases.last_mut().unwrap();
+        purchase.po_id = Some(po_id);
+        Ok(purchase)
+    }
+
+    /// Units of a product received against a purchase order, less any sent back.
+    pub fn received_on_order(&self, po_id: u32, product_name: &str) -> u32 {
+        let received: u32 = self
+            .purchases
+            .iter()
+            .filter(|p| p.po_id == Some(po_id) && p.product_name == product_name)
+            .map(|p| p.quantity)
+            .sum();
+        let returned: u32 = self
+            .supplier_returns
+            .iter()
+            .filter(|r| r.product_name == product_name)
+            .filter(|r| self.purchases.iter().any(|p| p.id == r.purchase_id && p.po_id == Some(po_id)))
+            .map(|r| r.quantity)
+            .sum();
+        received.saturating_sub(returned)
+    }
+
+    /// Captures a supplier's invoice against a purchase order and matches it.
+    pub fn capture_invoice(
+        &mut self,
+        po_id: u32,
+        number: &str,
+        lines: Vec<DocumentLine>,
+    ) -> Result<&SupplierInvoice, StoreError> {
+        let po = self
+            .procurement
+            .find_order(po_id)
+            .ok_or_else(|| StoreError::NotFound(format!("purchase order #{po_id}")))?;
+        if number.trim().is_empty() || lines.is_empty() {
+            return Err(StoreError::InvalidInput("An invoice needs a number and at least one line".into()));
+        }
+        let supplier = po.supplier().to_string();
+        let duplicate = self.procurement.invoices().iter().any(|i| {
+            i.number() == number && self.procurement.find_order(i.po_id()).is_some_and(|o| o.supplier() == supplier)
+        });
+        if duplicate {
+            return Err(StoreError::InvalidInput(format!("Invoice {number} from {supplier} already captured")));
+        }
+        let id = self.procurement.invoices().len() as u32 + 1;
+        self.procurement.push_invoice(SupplierInvoice::new(id, number, po_id, lines));
+        self.match_invoices();
+        Ok(self.procurement.find_invoice(id).unwrap())
+    }
+
+    /// Re-matches every invoice that is not disputed, e.g. after more goods
+    /// arrive or the tolerances change.
+    pub fn match_invoices(&mut self) {
+        let procurement = &self.procurement;
+        let mut results = Vec::new();
+        for (i, invoice) in procurement.invoices().iter().enumerate() {
+            if invoice.status() == procurement::InvoiceStatus::Disputed {
+                continue;
+            }
+            let Some(po) = procurement.find_order(invoice.po_id()) else { continue };
+            let earlier: Vec<&SupplierInvoice> = procurement.invoices()[..i]
+                .iter()
+                .filter(|e| e.po_id() == invoice.po_id() && e.status() != procurement::InvoiceStatus::Disputed)
+                .collect();
+            let invoiced_before = |product: &str| -> u32 {
+                earlier
+                    .iter()
+                    .flat_map(|e| e.lines())
+                    .filter(|l| l.product_name() == product)
+                    .map(|l| l.quantity())
+                    .sum()
+            };
+            let received = |product: &str| self.received_on_order(po.id(), product);
+            results.push((
+                i,
+                procurement::match_invoice(invoice, po, received, invoiced_before, procurement.tolerances()),
+            ));
+        }
+        for (i, discrepancies) in results {
+            self.procurement.invoices_mut()[i].set_result(discrepancies);
+        }
+    }
+
+    /// Marks an invoice as raised with the supplier; it is no longer matched.
+    pub fn dispute_invoice(&mut self, id: u32, note: &str) -> Result<(), StoreError> {
+        let invoice = self
+            .procurement
+            .invoices_mut()
+            .iter_mut()
+            .find(|i| i.id() == id)
+            .ok_or_else(|| StoreError::NotFound(format!("invoice #{id}")))?;
+        invoice.dispute(note);
+        self.match_invoices();
+        Ok(())
+    }
+
+    pub fn get_supplier_returns(&self) -> &[SupplierReturn] {
+        &self.supplier_returns
+    }
+
+    pub fn get_stock_counts(&self) -> &[StockCount] {
+        &self.stock_counts
+    }
+
+    pub fn get_landed_charges(&self) -> &[LandedCharge] {
+        &self.landed_charges
+    }
+
+    /// Average landed cost of one unit over every purchase of the product;
+    /// for a kit, the cost of its components. `None` if never purchased.
+    pub fn unit_cost(&self, product_name: &str, inv: &Inventory) -> Option<f64> {
+        self.unit_cost_at(product_name, inv, Timestamp::MAX)
+    }
+
+    /// Landed cost per unit of the stock on hand at `at`, from the purchases
+    /// made up to and including then: their average, or under FIFO the
+    /// average of the units still on the shelf.
+    pub fn unit_cost_at(&self, product_name: &str, inv: &Inventory, at: Timestamp) -> Option<f64> {
+        if let Some(p) = inv.find(product_name)
+            && p.is_kit()
+        {
+            return p
+                .components()
+                .iter()
+                .map(|(c, per_kit)| self.unit_cost_at(c, inv, at).map(|cost| cost * *per_kit as f64))
+                .sum();
+        }
+        let (units, cost) = match self.costing {
+            CostingMethod::Average => {
+                let bought = self.purchases.iter().filter(|p| p.product_name == product_name && p.purchased_at <= at);
+                bought.fold((0, 0.0), |(units, cost), p| (units + p.quantity, cost + p.landed_cost()))
+            }
+            // with nothing on hand, the next unit in costs what the last one did
+            CostingMethod::Fifo => {
+                let on_hand = valuation::stock_as_of(self, inv, product_name, at).max(1);
+                let layers = self.fifo_layers(product_name, on_hand, at);
+                layers.iter().fold((0, 0.0), |(units, cost), (n, c)| (units + n, cost + *n as f64 * c))
+            }
+        };
+        (units > 0).then(|| cost / units as f64)
+    }
+
+    /// Cost of `quantity` units taken from stock now: at the unit cost, or
+    /// under FIFO the oldest units on the shelf.
+    pub fn cost_of(&self, product_name: &str, quantity: u32, inv: &Inventory) -> Option<f64> {
+        let p = inv.find(product_name)?;
+        if p.is_kit() {
+            return p
+                .components()
+                .iter()
+                .map(|(c, per_kit)| self.cost_of(c, quantity * per_kit, inv))
+                .sum();
+        }
+        if self.costing == CostingMethod::Average {
+            return self.unit_cost(product_name, inv).map(|c| c * quantity as f64);
+        }
+        let layers = self.fifo_layers(product_name, p.quantity().max(quantity), Timestamp::MAX);
+        if layers.is_empty() {
+            return None;
+        }
+        let mut left = quantity;
+        let mut cost = 0.0;
+        for (units, unit_cost) in layers {
+            let taken = units.min(left);
+            cost += taken as f64 * unit_cost;
+            left -= taken;
+        }
+        Some(cost)
+    }
+
+    /// The newest `on_hand` units bought up to `at` as units and landed unit
+    /// cost per purchase, oldest first. Stock older than every recorded
+    /// purchase is costed like the oldest one.
+    fn fifo_layers(&self, product_name: &str, on_hand: u32, at: Timestamp) -> Vec<(u32, f64)> {
+        let mut layers = Vec::new();
+        let mut left = on_hand;
+        let bought = self.purchases.iter().rev().filter(|p| p.product_name == product_name && p.purchased_at <= at);
+        for p in bought {
+            if left == 0 {
+                break;
+            }
+            let units = p.quantity.min(left);
+            layers.push((units, p.landed_unit_cost()));
+            left -= units;
+        }
+        if left > 0
+            && let Some(&(_, cost)) = layers.last()
+        {
+            layers.push((left, cost));
+        }
+        layers.reverse();
+        layers
+    }
+
+    pub fn costing_method(&self) -> CostingMethod {
+        self.costing
+    }
+
+    /// Sets how stock and goods sold are costed from here on; postings
+    /// already made keep their amounts.
+    pub fn set_costing_method(&mut self, method: CostingMethod) {
+        self.costing = method;
+    }
+
+    /// Edits a product. A rename is refused if the name is empty or taken,
+    /// and the product's history, backorders, list prices and promotions
+    /// follow it to the new name. A new quantity is posted as a stock
+    /// adjustment.
+    pub fn edit_product(
+        &mut self,
+        current_name: &str,
+        name: Option<&str>,
+        description: Option<&str>,
+        price: Option<f64>,
+        quantity: Option<u32>,
+        inv: &mut Inventory,
+    ) -> Result<(), StoreError> {
+        let Some(p) = inv.find(current_name) else {
+            return Err(StoreError::NotFound(current_name.to_string()));
+        };
+        let counted = !p.is_kit() && !p.is_parent();
+        let renames = match name {
+            Some(name) => inv.rename_product(current_name, name)?,
+            None => Vec::new(),
+        };
+        for (old, new) in &renames {
+            self.rename_product(old, new);
+        }
+        let name = renames.first().map_or(current_name, |(_, new)| new.as_str()).to_string();
+        inv.edit_product(&name, None, description, price, None);
+        // a new quantity is a stock adjustment and is posted like a count
+        if let Some(quantity) = quantity
+            && counted
+        {
+            self.record_stock_count(&name, quantity, "edited", inv)?;
+        }
+        Ok(())
+    }
+
+    // Points everything recorded against `old` at `new`, so the product
+    // keeps its history, prices and promotions.
+    fn rename_product(&mut self, old: &str, new: &str) {
+        let rename = |name: &mut String| {
+            if name == old {
+                *name = new.to_string();
+            }
+        };
+        for s in self.sales.iter_mut() {
+            rename(&mut s.product_name);
+            s.components.iter_mut().for_each(|c| rename(&mut c.product_name));
+        }
+        self.returns.iter_mut().for_each(|r| rename(&mut r.product_name));
+        self.purchases.iter_mut().for_each(|p| rename(&mut p.product_name));
+        self.supplier_returns.iter_mut().for_each(|r| rename(&mut r.product_name));
+        self.stock_counts.iter_mut().for_each(|c| rename(&mut c.product_name));
+        self.backorders.iter_mut().for_each(|b| rename(&mut b.product_name));
+        self.price_lists.rename_product(old, new);
+        self.promotions.rename_product(old, new);
+        self.procurement.rename_product(old, new);
+    }
+
+    /// Checks that a backorder could be taken, without taking it.
+    pub fn check_backorder(&self, product_name: &str, quantity: u32, unit_price: f64, inv: &Inventory) -> Result<(), StoreError> {
+        if quantity == 0 {
+            return Err(StoreError::InvalidInput("Quantity must be > 0".into()));
+        }
+        if unit_price <= 0.0 {
+            return Err(StoreError::InvalidInput("Unit price must be > 0".into()));
+        }
+        match inv.find(product_name) {
+            Some(p) if p.is_kit() => Err(StoreError::InvalidInput(format!("{product_name} is a kit; backorder its components"))),
+            Some(p) if p.is_parent() => Err(StoreError::InvalidInput(format!("{product_name} has variants; choose one"))),
+            Some(_) => Ok(()),
+            None => Err(StoreError::NotFound(product_name.to_string())),
+        }
+    }
+
+    /// Records a backorder for units the customer could not get from stock.
+    pub fn record_backorder(
+        &mut self,
+        customer_id: u32,
+        product_name: &str,
+        quantity: u32,
+        unit_price: f64,
+        inv: &Inventory,
+    ) -> Result<&Backorder, StoreError> {
+        self.check_backorder(product_name, quantity, unit_price, inv)?;
+        let id = self.next_backorder_id;
+        self.next_backorder_id += 1;
+        self.backorders.push(Backorder {
+            id,
+            customer_id,
+            product_name: product_name.to_string(),
+            quantity,
+            unit_price,
+            allocated: 0,
+            reservation_ids: Vec::new(),
+            fulfilled: false,
+        });
+        Ok(self.backorders.last().unwrap())
+    }
+
+    /// Hands a ready backorder to the customer, turning it into a sale.
+    pub fn fulfil_backorder(&mut self, id: u32, inv: &mut Inventory) -> Result<&Sale, StoreError> {
+        let Some(pos) = self.backorders.iter().position(|b| b.id == id && !b.fulfilled) else {
+            return Err(StoreError::NotFound(format!("backorder #{id}")));
+        };
+        let b = &self.backorders[pos];
+        if !b.is_ready() {
+            return Err(StoreError::OutOfStock {
+                product: b.product_name.clone(),
+                requested: b.quantity,
+                available: b.allocated,
+            });
+        }
+        let mut cart = Cart::for_customer(b.customer_id);
+        cart.add_line(&b.product_name, b.quantity, b.unit_price);
+
+        // the held units are released for the sale and put back if it fails
+        let mut released = Vec::new();
+        for rid in &b.reservation_ids {
+            match inv.release_reservation(*rid) {
+                Ok(r) => released.push(r),
+                Err(e) => {
+                    inv.restore_reservations(released);
+                    return Err(e);
+                }
+            }
+        }
+        if let Err(e) = self.complete(&cart, inv, true) {
+            inv.restore_reservations(released);
+            return Err(e);
+        }
+        self.backorders[pos].fulfilled = true;
+        Ok(self.sales.last().unwrap())
+    }
+
+    // Oldest backorders are served first; only the newly received units are
+    // handed out so stock already on the shelf keeps its current owners.
+    fn allocate_backorders(&mut self, product_name: &str, received: u32, inv: &mut Inventory) {
+        let mut remaining = received;
+        for b in self.backorders.iter_mut() {
+            if remaining == 0 {
+                break;
+            }
+            if b.fulfilled || b.product_name != product_name || b.outstanding() == 0 {
+                continue;
+            }
+            let qty = b.outstanding().min(remaining);
+            let owner = format!("backorder #{} (customer #{})", b.id, b.customer_id);
+            if let Ok(r) = inv.reserve(product_name, &owner, qty, Timestamp::MAX) {
+                b.reservation_ids.push(r.id());
+                b.allocated += qty;
+                remaining -= qty;
+            }
+        }
+    }
+
+    pub fn get_backorders(&self) -> &[Backorder] {
+        &self.backorders
+    }
+
+    pub fn backorders_for(&self, customer_id: u32) -> Vec<&Backorder> {
+        self.backorders.iter().filter(|b| b.customer_id == customer_id).collect()
+    }
+
+    /// Backorders whose full quantity has arrived and is waiting for pickup.
+    pub fn ready_backorders(&self) -> Vec<&Backorder> {
+        self.backorders.iter().filter(|b| b.is_ready()).collect()
+    }
+
+    pub fn sales_for(&self, customer_id: u32) -> Vec<&Sale> {
+        self.sales.iter().filter(|s| s.customer_id == Some(customer_id)).collect()
     }
 
-    
     pub fn get_sales(&self) -> &Vec<Sale> {
         &self.sales
     }
@@ -125,40 +1864,239 @@ impl Transactions {
         &self.purchases
     }
 
+    /// Sales net of returns, excluding tax.
     pub fn total_revenue(&self) -> f64 {
-        self.sales.iter().map(|s| s.total_price).sum()
+        let sold: f64 = self.sales.iter().map(|s| s.net_amount).sum();
+        let returned: f64 = self.returns.iter().map(|r| r.refund - r.tax).sum();
+        sold - returned
+    }
+
+    /// Tax collected on sales, per rate, net of refunded tax.
+    pub fn tax_summary(&self) -> Vec<TaxLine> {
+        let sold = self.orders.iter().flat_map(|o| o.tax_lines.iter()).map(|l| (l.rate(), l.net(), l.tax()));
+        let refunded = self.returns.iter().filter_map(|r| {
+            let sale = self.find_sale(r.sale_id).ok()?;
+            Some((sale.tax_rate, -(r.refund - r.tax), -r.tax))
+        });
+        tax::tax_by_rate(sold.chain(refunded))
     }
 
+    /// Cost of purchases, net of what was claimed back on debit notes.
     pub fn total_purchase_cost(&self) -> f64 {
-        self.purchases.iter().map(|p| p.total_cost).sum()
+        let bought: f64 = self.purchases.iter().map(|p| p.total_cost).sum();
+        let returned: f64 = self.supplier_returns.iter().map(|r| r.amount).sum();
+        bought - returned
+    }
+}
+
+/// Every sale with its discounts, then tax collected by rate.
+pub fn sales_report(tx: &Transactions) -> Report {
+    sales_report_for(tx, &ListQuery::new())
+}
+
+/// The sales `query` selects, matched on product, unit price, quantity and
+/// line total. Tax by rate always covers every sale.
+pub fn sales_report_for(tx: &Transactions, query: &ListQuery) -> Report {
+    let page = query.apply(tx.sales.iter().enumerate().collect(), |(_, s)| ListKeys {
+        name: &s.product_name,
+        text: s.price_list.as_deref().unwrap_or(""),
+        price: Some(s.unit_price),
+        quantity: s.quantity,
+        value: s.total_price,
+    });
+    let mut report = Report::new("Sales", &["No", "Date", "Product", "Qty", "Unit", "Disc", "Total", "Tax"]);
+    for (i, s) in page.items().iter().copied() {
+        report.add_row(vec![
+            Value::Integer(i as i64 + 1),
+            Value::Text(clock::format_date(s.sold_at)),
+            Value::text(&s.product_name),
+            Value::Integer(s.quantity as i64),
+            Value::Money(s.unit_price),
+            Value::Money(s.discount()),
+            Value::Money(s.total_price),
+            Value::Money(s.tax),
+        ]);
+        for d in &s.discounts {
+            let label = format!("  {}", d.name());
+            report.add_row(vec![Value::Empty, Value::Empty, Value::Text(label), Value::Empty, Value::Empty, Value::Money(-d.discount())]);
+        }
+        // charged something other than the shelf price of the day
+        if s.list_price > 0.0 && (s.unit_price - s.list_price).abs() >= 0.005 {
+            let shelf = Value::Text(format!("({:.2})", s.list_price));
+            report.add_row(vec![Value::Empty, Value::Empty, Value::text("  Shelf price"), Value::Empty, shelf]);
+        }
+    }
+
+    let summary = tx.tax_summary();
+    if summary.iter().any(|l| l.tax() != 0.0) {
+        let mut taxes = Report::new("Tax by rate", &["Tax rate", "Net", "Tax"]);
+        for l in &summary {
+            taxes.add_row(vec![Value::Percent(l.rate() * 100.0), Value::Money(l.net()), Value::Money(l.tax())]);
+        }
+        report.add_section(taxes);
     }
+    if query.page_size().is_some() {
+        report.set_paging(page.page(), page.pages(), page.total());
+    }
+    report
 }
 
 pub fn report_sales(tx: &Transactions) -> String {
-    let mut out = String::new();
-    out.push_str("No | Product            | Qty  | Unit   | Total\n");
-    out.push_str("-----------------------------------------------\n");
+    sales_report(tx).render(ReportFormat::Text)
+}
 
+/// Sales report with every kit broken down into the components it consumed.
+pub fn sales_exploded_report(tx: &Transactions) -> Report {
+    let mut report = Report::new("Sales (kits exploded)", &["No", "Product", "Kit", "Qty", "Total"]);
     for (i, s) in tx.sales.iter().enumerate() {
-        out.push_str(&format!(
-            "{:<2} | {:<18} | {:>3}  | {:>6.2} | {:>6.2}\n",
-            i + 1, s.product_name, s.quantity, s.unit_price, s.total_price
-        ));
+        if !s.is_kit() {
+            report.add_row(vec![
+                Value::Integer(i as i64 + 1),
+                Value::text(&s.product_name),
+                Value::Empty,
+                Value::Integer(s.quantity as i64),
+                Value::Money(s.total_price),
+            ]);
+        }
+        for c in &s.components {
+            report.add_row(vec![
+                Value::Integer(i as i64 + 1),
+                Value::text(&c.product_name),
+                Value::text(&s.product_name),
+                Value::Integer(c.quantity as i64),
+                Value::Money(c.total_price),
+            ]);
+        }
     }
-    out
+    report
+}
+
+pub fn report_sales_exploded(tx: &Transactions) -> String {
+    sales_exploded_report(tx).render(ReportFormat::Text)
+}
+
+pub fn returns_report(tx: &Transactions) -> Report {
+    let mut report = Report::new("Returns", &["Sale", "Product", "Qty", "Refund", "Points"]);
+    for r in &tx.returns {
+        report.add_row(vec![
+            Value::Integer(r.sale_id as i64),
+            Value::text(&r.product_name),
+            Value::Integer(r.quantity as i64),
+            Value::Money(r.refund),
+            Value::Integer(r.points_restored as i64),
+        ]);
+    }
+    report
+}
+
+pub fn report_returns(tx: &Transactions) -> String {
+    returns_report(tx).render(ReportFormat::Text)
+}
+
+/// Every purchase at its base-currency and landed cost, with what went back
+/// to the supplier under each.
+pub fn purchases_report(tx: &Transactions) -> Report {
+    purchases_report_for(tx, &ListQuery::new())
+}
+
+/// The purchases `query` selects, matched on product, unit cost, quantity
+/// and total cost. The totals always cover every purchase.
+pub fn purchases_report_for(tx: &Transactions, query: &ListQuery) -> Report {
+    let page = query.apply(tx.purchases.iter().enumerate().collect(), |(_, p)| ListKeys {
+        name: &p.product_name,
+        text: p.supplier_unit_price.currency(),
+        price: Some(p.unit_price),
+        quantity: p.quantity,
+        value: p.total_cost,
+    });
+    let mut report = Report::new(
+        "Purchases",
+        &["No", "Product", "Qty", "Unit", "Total", "Landed", "Supplier total"],
+    );
+    // purchases made in another currency also show what the supplier billed
+    let base = tx.rates.base();
+    for (i, p) in page.items().iter().copied() {
+        let supplier = if p.supplier_unit_price.currency() == base {
+            Value::Empty
+        } else {
+            Value::Text(format!("{} @ {:.4}", p.supplier_total(), p.exchange_rate))
+        };
+        report.add_row(vec![
+            Value::Integer(i as i64 + 1),
+            Value::text(&p.product_name),
+            Value::Integer(p.quantity as i64),
+            Value::Money(p.unit_price),
+            Value::Money(p.total_cost),
+            Value::Money(p.landed_unit_cost()),
+            supplier,
+        ]);
+        for r in tx.supplier_returns.iter().filter(|r| r.purchase_id == p.id) {
+            let label = format!("  Returned x{} ({})", r.quantity, r.debit_note());
+            report.add_row(vec![Value::Empty, Value::Text(label), Value::Empty, Value::Empty, Value::Money(-r.amount)]);
+        }
+    }
+    let returned: f64 = tx.supplier_returns.iter().map(|r| r.amount).sum();
+    if returned > 0.0 {
+        report.add_total(&format!("Returned to suppliers ({base})"), Value::Money(returned));
+    }
+    report.add_total(&format!("Total ({base})"), Value::Money(tx.total_purchase_cost()));
+    let charges: f64 = tx.landed_charges.iter().map(|c| c.amount()).sum();
+    if charges > 0.0 {
+        report.add_total(&format!("Landed charges ({base})"), Value::Money(charges));
+    }
+    if query.page_size().is_some() {
+        report.set_paging(page.page(), page.pages(), page.total());
+    }
+    report
 }
 
 pub fn report_purchases(tx: &Transactions) -> String {
+    purchases_report(tx).render(ReportFormat::Text)
+}
+
+/// The debit note sent to the supplier for a return.
+pub fn report_debit_note(tx: &Transactions, return_id: u32) -> Result<String, StoreError> {
+    let r = tx
+        .supplier_returns
+        .iter()
+        .find(|r| r.id == return_id)
+        .ok_or_else(|| StoreError::NotFound(format!("supplier return #{return_id}")))?;
     let mut out = String::new();
-    out.push_str("No | Product            | Qty  | Unit   | Total\n");
-    out.push_str("-----------------------------------------------\n");
+    out.push_str(&format!("DEBIT NOTE {}\n", r.debit_note()));
+    out.push_str(&format!("Against purchase #{}\n", r.purchase_id));
+    out.push_str(&format!("Returned: {} x{}\n", r.product_name, r.quantity));
+    if !r.reason.is_empty() {
+        out.push_str(&format!("Reason: {}\n", r.reason));
+    }
+    out.push_str(&format!("Amount claimed: {}\n", r.supplier_amount));
+    if r.supplier_amount.currency() != tx.rates.base() {
+        out.push_str(&format!("({:.2} {})\n", r.amount, tx.rates.base()));
+    }
+    Ok(out)
+}
 
-    for (i, p) in tx.purchases.iter().enumerate() {
-        out.push_str(&format!(
-            "{:<2} | {:<18} | {:>3}  | {:>6.2} | {:>6.2}\n",
-            i + 1, p.product_name, p.quantity, p.unit_price, p.total_cost
-        ));
+pub fn backorders_report(tx: &Transactions) -> Report {
+    let mut report = Report::new("Backorders", &["No", "Customer", "Product", "Qty", "Alloc", "Status"]);
+    for b in &tx.backorders {
+        let status = if b.fulfilled {
+            "Picked up"
+        } else if b.is_ready() {
+            "Ready"
+        } else {
+            "Waiting"
+        };
+        report.add_row(vec![
+            Value::Integer(b.id as i64),
+            Value::Text(format!("#{}", b.customer_id)),
+            Value::text(&b.product_name),
+            Value::Integer(b.quantity as i64),
+            Value::Integer(b.allocated as i64),
+            Value::text(status),
+        ]);
     }
-    out
+    report
 }
 
+pub fn report_backorders(tx: &Transactions) -> String {
+    backorders_report(tx).render(ReportFormat::Text)
+}
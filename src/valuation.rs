@@ -0,0 +1,179 @@
+#![allow(dead_code)]
+use crate::clock::{self, Timestamp};
+use crate::inventory::{Inventory, Product};
+use crate::report::{Report, ReportFormat, Value};
+use crate::transactions::{Sale, Transactions};
+
+/// Stock of one product valued at cost and at its shelf price.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValuationLine {
+    product_name: String,
+    category: String,
+    quantity: u32,
+    /// Landed cost per unit under the costing method; `None` if the
+    /// product was never purchased.
+    unit_cost: Option<f64>,
+    price: f64,
+}
+
+impl ValuationLine {
+    pub fn product_name(&self) -> &str { &self.product_name }
+    pub fn category(&self) -> &str { &self.category }
+    pub fn quantity(&self) -> u32 { self.quantity }
+    pub fn unit_cost(&self) -> Option<f64> { self.unit_cost }
+    pub fn price(&self) -> f64 { self.price }
+    /// Stock at cost; products without a cost count as zero.
+    pub fn cost_value(&self) -> f64 { self.unit_cost.unwrap_or(0.0) * self.quantity as f64 }
+    pub fn retail_value(&self) -> f64 { self.price * self.quantity as f64 }
+    /// Gross margin if the stock sold at its shelf price.
+    pub fn margin(&self) -> f64 { self.retail_value() - self.cost_value() }
+}
+
+/// Units of `product_name` on the shelf at `at`, worked back from today's
+/// stock by undoing the purchases, sales, returns and stock counts recorded
+/// since.
+pub fn stock_as_of(tx: &Transactions, inv: &Inventory, product_name: &str, at: Timestamp) -> u32 {
+    let Some(p) = inv.find(product_name) else {
+        return 0;
+    };
+    let mut quantity = p.quantity() as i64;
+    for purchase in tx.get_purchases().iter().filter(|x| x.purchased_at() > at) {
+        if purchase.product_name() == product_name {
+            quantity -= purchase.quantity() as i64;
+        }
+    }
+    for r in tx.get_supplier_returns().iter().filter(|r| r.returned_at() > at) {
+        if r.product_name() == product_name {
+            quantity += r.quantity() as i64;
+        }
+    }
+    for sale in tx.get_sales().iter().filter(|s| s.sold_at() > at) {
+        quantity += units_of(sale, product_name, sale.quantity()) as i64;
+    }
+    for r in tx.get_returns().iter().filter(|r| r.returned_at() > at) {
+        if let Some(sale) = tx.get_sales().iter().find(|s| s.id() == r.sale_id()) {
+            quantity -= units_of(sale, product_name, r.quantity()) as i64;
+        }
+    }
+    for count in tx.get_stock_counts().iter().filter(|c| c.counted_at() > at) {
+        if count.product_name() == product_name {
+            quantity -= count.difference();
+        }
+    }
+    quantity.max(0) as u32
+}
+
+/// Units of `product_name` taken from stock by `quantity` of `sale`: the
+/// product itself, or its share of a kit.
+pub(crate) fn units_of(sale: &Sale, product_name: &str, quantity: u32) -> u32 {
+    if sale.is_kit() {
+        sale.components()
+            .iter()
+            .filter(|c| c.product_name() == product_name)
+            .map(|c| c.quantity() / sale.quantity() * quantity)
+            .sum()
+    } else if sale.product_name() == product_name {
+        quantity
+    } else {
+        0
+    }
+}
+
+/// Every product that holds stock of its own, valued now or, with `as_of`,
+/// on that date at the cost and shelf price of the day. Kits and parent
+/// products are left out; their stock is held by components and variants.
+pub fn valuation(tx: &Transactions, inv: &Inventory, as_of: Option<Timestamp>) -> Vec<ValuationLine> {
+    let stocked = |p: &&Product| !p.is_kit() && !p.is_parent();
+    inv.all()
+        .iter()
+        .filter(stocked)
+        .map(|p| {
+            let (quantity, unit_cost, price) = match as_of {
+                Some(at) => (
+                    stock_as_of(tx, inv, p.name(), at),
+                    tx.unit_cost_at(p.name(), inv, at),
+                    p.price_at(at),
+                ),
+                None => (p.quantity(), tx.unit_cost(p.name(), inv), p.price()),
+            };
+            ValuationLine {
+                product_name: p.name().to_string(),
+                category: p.category().to_string(),
+                quantity,
+                unit_cost,
+                price,
+            }
+        })
+        .collect()
+}
+
+fn margin_percent(margin: f64, retail: f64) -> Value {
+    if retail == 0.0 { Value::Empty } else { Value::Percent(margin / retail * 100.0) }
+}
+
+/// Stock value at cost and at retail, product by product and with a
+/// subtotal per category, then the potential gross margin over all stock.
+pub fn valuation_report(tx: &Transactions, inv: &Inventory, as_of: Option<Timestamp>) -> Report {
+    let lines = valuation(tx, inv, as_of);
+    let date = clock::format_date(as_of.unwrap_or_else(|| tx.now()));
+    let mut report = Report::new(
+        &format!("Inventory valuation as of {date}"),
+        &["Category", "Product", "Qty", "Unit cost", "Cost value", "Price", "Retail value", "Margin", "Margin %"],
+    );
+
+    let mut categories: Vec<&str> = Vec::new();
+    for l in &lines {
+        if !categories.contains(&l.category()) {
+            categories.push(l.category());
+        }
+    }
+    for category in categories {
+        let label = if category.is_empty() { "(none)" } else { category };
+        let in_category: Vec<&ValuationLine> = lines.iter().filter(|l| l.category() == category).collect();
+        for l in &in_category {
+            report.add_row(vec![
+                Value::text(label),
+                Value::text(l.product_name()),
+                Value::Integer(l.quantity() as i64),
+                l.unit_cost().map_or(Value::Empty, Value::Money),
+                Value::Money(l.cost_value()),
+                Value::Money(l.price()),
+                Value::Money(l.retail_value()),
+                Value::Money(l.margin()),
+                margin_percent(l.margin(), l.retail_value()),
+            ]);
+        }
+        let cost: f64 = in_category.iter().map(|l| l.cost_value()).sum();
+        let retail: f64 = in_category.iter().map(|l| l.retail_value()).sum();
+        report.add_row(vec![
+            Value::text(label),
+            Value::text("Subtotal"),
+            Value::Integer(in_category.iter().map(|l| l.quantity() as i64).sum()),
+            Value::Empty,
+            Value::Money(cost),
+            Value::Empty,
+            Value::Money(retail),
+            Value::Money(retail - cost),
+            margin_percent(retail - cost, retail),
+        ]);
+    }
+
+    let cost: f64 = lines.iter().map(|l| l.cost_value()).sum();
+    let retail: f64 = lines.iter().map(|l| l.retail_value()).sum();
+    report.add_total("Total at cost", Value::Money(cost));
+    report.add_total("Total at retail", Value::Money(retail));
+    report.add_total("Potential gross margin", Value::Money(retail - cost));
+    if retail != 0.0 {
+        report.add_total("Margin on retail", margin_percent(retail - cost, retail));
+    }
+    let uncosted = lines.iter().filter(|l| l.unit_cost().is_none() && l.quantity() > 0).count();
+    if uncosted > 0 {
+        report.add_total("Products without a cost (valued at 0)", Value::Integer(uncosted as i64));
+    }
+    report
+}
+
+/// The valuation as text.
+pub fn report_valuation(tx: &Transactions, inv: &Inventory, as_of: Option<Timestamp>) -> String {
+    valuation_report(tx, inv, as_of).render(ReportFormat::Text)
+}
@@ -1,5 +1,22 @@
 // Integration tests live under `tests/` and use the crate as an external dependency.
 use store_inventory_management_system::{Inventory, Transactions, report_inventory, report_sales, report_purchases, StoreError};
+use store_inventory_management_system::{
+    Tender, TenderKind, TaxClass, TaxSettings, PricingMode, TaxRounding, Cart, Promotion,
+    PromotionKind, PromotionTarget, LoyaltyRules, PointsKind, Customers,
+    report_customer_history, report_backorders, report_sales_exploded, expected_by_tender,
+    report_shift, StoreHeader, ReceiptFormat, render_receipt, CardKind, report_liability,
+    PriceList, report_price_history, Money, ChargeKind, AllocationBasis, report_margins,
+    margins_report, report_debit_note, DocumentLine, InvoiceStatus, Discrepancy, MatchTolerances,
+    report_invoice_matching, AccountRole, EntryKind, JournalEntry, JournalLine,
+    report_trial_balance, ExportFormat, journal_transactions, daily_summaries, render_export,
+    Period, SalesFilter, sales_between, totals, by_period, by_hour, by_weekday, by_product,
+    report_sales_summary, sales_summary_report, inventory_report, sales_report, purchases_report,
+    Report, ReportFormat, ReportFormatter, Value, inventory_report_for, sales_report_for,
+    purchases_report_for, ListQuery, SortKey, valuation, valuation_report, report_valuation,
+    stock_as_of, StockMovement, MovementSort, stock_movement, sort_movement, slow_movers_report,
+    report_slow_movers, CostingMethod, trial_balance_report, backorders_report,
+    report_journal, report_rates,
+};
 
 #[test]
 fn add_edit_delete_product_flow() {
@@ -121,3 +138,1319 @@ fn purchasing_unknown_product_should_fail_unless_implemented_otherwise() {
     let res = tx.record_purchase("NewThing", 5, 3.0, &mut inv);
     assert!(res.is_err(), "If your logic auto-creates products, flip this assertion.");
 }
+
+/// A store with `cola` cans of Cola at 10.00 and no transactions yet.
+fn cola_store(cola: u32) -> (Inventory, Transactions) {
+    let mut inv = Inventory::new();
+    inv.add_product("Cola", "330ml can", 10.0, cola);
+    (inv, Transactions::new())
+}
+
+#[test]
+fn reservations_hold_stock_back_from_sale() {
+    let (mut inv, mut tx) = cola_store(5);
+    let far_future = u64::MAX;
+    let id = inv.reserve("Cola", "phone: 555-0101", 3, far_future).expect("reserve should work").id();
+
+    assert_eq!(inv.on_hand("Cola"), Some(5));
+    assert_eq!(inv.reserved("Cola"), 3);
+    assert_eq!(inv.available("Cola"), Some(2));
+
+    // only the unreserved units can be sold over the counter
+    match tx.record_sale("Cola", 3, 10.0, &mut inv) {
+        Err(StoreError::OutOfStock { available, .. }) => assert_eq!(available, 2),
+        other => panic!("expected OutOfStock, got {:?}", other.map(|s| s.quantity())),
+    }
+
+    // a sale that fails keeps the reservation
+    inv.find_mut("Cola").unwrap().set_quantity(1);
+    assert!(tx.record_reserved_sale(id, 10.0, &mut inv).is_err());
+    assert_eq!(inv.reserved("Cola"), 3);
+    inv.find_mut("Cola").unwrap().set_quantity(5);
+
+    // the reservation owner collects their units
+    let s = tx.record_reserved_sale(id, 10.0, &mut inv).expect("reserved sale should work");
+    assert_eq!(s.quantity(), 3);
+    assert_eq!(inv.on_hand("Cola"), Some(2));
+    assert_eq!(inv.reserved("Cola"), 0);
+    assert!(inv.reservations().is_empty());
+}
+
+#[test]
+fn expired_reservations_are_released() {
+    let mut inv = Inventory::new();
+    let mut tx = Transactions::new();
+
+    inv.add_product("Chips", "Salted", 7.5, 4);
+    let id = inv.reserve("Chips", "cart-42", 4, 1).expect("reserve should work").id();
+
+    // already expired, so it no longer blocks stock
+    assert_eq!(inv.available("Chips"), Some(4));
+    tx.record_sale("Chips", 4, 7.5, &mut inv).expect("sale should work");
+    assert!(inv.reservations().is_empty());
+    assert!(tx.record_reserved_sale(id, 7.5, &mut inv).is_err());
+}
+
+#[test]
+fn purchases_allocate_to_backorders_in_order() {
+    let mut inv = Inventory::new();
+    let mut tx = Transactions::new();
+
+    inv.add_product("Bread", "Whole grain", 18.0, 0);
+    let (alice, bob) = (1, 2);
+    let first = tx.record_backorder(alice, "Bread", 3, 18.0, &inv).expect("backorder should work").id();
+    let second = tx.record_backorder(bob, "Bread", 2, 18.0, &inv).expect("backorder should work").id();
+
+    // 4 arrive: alice is served in full, bob gets 1 of 2
+    tx.record_purchase("Bread", 4, 12.0, &mut inv).expect("purchase should work");
+    let ready: Vec<u32> = tx.ready_backorders().iter().map(|b| b.id()).collect();
+    assert_eq!(ready, vec![first]);
+    assert_eq!(tx.backorders_for(bob)[0].allocated(), 1);
+
+    // allocated units are held for the backorders, not sold over the counter
+    assert_eq!(inv.available("Bread"), Some(0));
+    assert!(tx.record_sale("Bread", 1, 18.0, &mut inv).is_err());
+
+    // bob cannot collect a partial backorder
+    assert!(tx.fulfil_backorder(second, &mut inv).is_err());
+
+    // a pickup that fails leaves the units held
+    inv.find_mut("Bread").unwrap().set_quantity(2);
+    assert!(tx.fulfil_backorder(first, &mut inv).is_err());
+    assert_eq!(inv.reserved("Bread"), 4);
+    assert_eq!(tx.ready_backorders().len(), 1);
+    inv.find_mut("Bread").unwrap().set_quantity(4);
+
+    let s = tx.fulfil_backorder(first, &mut inv).expect("pickup should work");
+    assert_eq!(s.quantity(), 3);
+    assert_eq!(inv.on_hand("Bread"), Some(1));
+    assert!(tx.fulfil_backorder(first, &mut inv).is_err());
+
+    let rep = report_backorders(&tx);
+    assert_eq!(backorders_report(&tx).rows()[1][4..], [Value::Integer(1), Value::text("Waiting")]);
+    assert!(rep.contains("#1"));
+    assert!(rep.contains("Picked up"));
+    assert!(rep.contains("Waiting"));
+}
+
+#[test]
+fn kits_sell_from_component_stock() {
+    let (mut inv, mut tx) = cola_store(5);
+    inv.add_product("Chips", "Salted", 5.0, 1);
+    inv.add_kit("Party Pack", "2 Cola + 1 Chips", 22.0, &[("Cola", 2), ("Chips", 1)])
+        .expect("kit should be created");
+    assert!(inv.add_kit("Broken", "", 1.0, &[("Nope", 1)]).is_err());
+
+    // limited by Chips: min(5 / 2, 1 / 1) = 1
+    assert_eq!(inv.available("Party Pack"), Some(1));
+
+    // failing kit sale leaves every component untouched
+    assert!(tx.record_sale("Party Pack", 2, 22.0, &mut inv).is_err());
+    assert_eq!(inv.on_hand("Cola"), Some(5));
+    assert_eq!(inv.on_hand("Chips"), Some(1));
+    // a kit cannot be backordered, so none is offered
+    assert!(tx.check_backorder("Party Pack", 1, 22.0, &inv).is_err());
+    assert!(tx.check_backorder("Chips", 1, 5.0, &inv).is_ok());
+
+    let s = tx.record_sale("Party Pack", 1, 22.0, &mut inv).expect("kit sale should work");
+    assert!(s.is_kit());
+    let split: f64 = s.components().iter().map(|c| c.total_price()).sum();
+    assert!((split - 22.0).abs() < 1e-6);
+    assert_eq!(inv.on_hand("Cola"), Some(3));
+    assert_eq!(inv.on_hand("Chips"), Some(0));
+    assert_eq!(inv.available("Party Pack"), Some(0));
+
+    // kits have no stock of their own to restock
+    assert!(tx.record_purchase("Party Pack", 1, 10.0, &mut inv).is_err());
+
+    assert!(report_sales(&tx).contains("Party Pack"));
+    let exploded = report_sales_exploded(&tx);
+    assert!(exploded.contains("Cola"));
+    assert!(exploded.contains("Chips"));
+}
+
+#[test]
+fn variants_roll_up_under_their_parent() {
+    let mut inv = Inventory::new();
+    let mut tx = Transactions::new();
+
+    inv.add_parent("Cola", "Soft drink", &["size"]).expect("parent should be created");
+    inv.add_variant("Cola", "COLA-330", &[("size", "330ml")], 10.0, 12).expect("variant should be created");
+    inv.add_variant("Cola", "COLA-1500", &[("size", "1.5L")], 25.0, 4).expect("variant should be created");
+    assert!(inv.add_variant("Cola", "COLA-330", &[("size", "2L")], 30.0, 1).is_err(), "duplicate SKU");
+    assert!(inv.add_variant("Cola", "COLA-X", &[("flavour", "lime")], 9.0, 1).is_err(), "unknown attribute");
+
+    let big = inv.find_by_sku("COLA-1500").expect("lookup by SKU");
+    assert_eq!(big.name(), "Cola 1.5L");
+    assert_eq!(big.parent(), Some("Cola"));
+    assert_eq!(inv.variants("Cola").len(), 2);
+
+    // variants sell on their own; the parent only groups them
+    assert!(tx.record_sale("Cola", 1, 10.0, &mut inv).is_err());
+    tx.record_sale("Cola 1.5L", 1, 25.0, &mut inv).expect("variant sale should work");
+    assert_eq!(inv.on_hand("Cola 1.5L"), Some(3));
+    assert_eq!(inv.on_hand("Cola"), Some(15));
+
+    let rep = report_inventory(&inv);
+    let lines: Vec<&str> = rep.lines().collect();
+    let parent_at = lines.iter().position(|l| l.starts_with("Cola ")).unwrap();
+    assert!(lines[parent_at].contains(" 15 |"));
+    assert!(lines[parent_at + 1].contains("Cola 330ml [COLA-330]"));
+    assert!(lines[parent_at + 2].contains("Cola 1.5L [COLA-1500]"));
+
+    inv.del_product("Cola");
+    assert!(inv.find("Cola 330ml").is_none());
+}
+
+#[test]
+fn sales_are_tagged_with_their_customer() {
+    let (mut inv, mut tx) = cola_store(10);
+    let mut customers = Customers::new();
+
+    let alice = customers.for_user("alice").id();
+    assert_eq!(customers.for_user("alice").id(), alice, "linked customer is reused");
+    let walk_in = customers.add_walk_in("Sam", Some("555-0199"), None).expect("walk-in should be added").id();
+    assert!(customers.add_walk_in("Nobody", None, None).is_err());
+    assert!(customers.add_walk_in("Sam again", Some("555-0199"), None).is_err());
+    assert_eq!(customers.lookup("555-0199").map(|c| c.id()), Some(walk_in));
+
+    tx.record_sale_for(alice, "Cola", 2, 10.0, &mut inv).expect("sale should work");
+    tx.record_sale_for(walk_in, "Cola", 1, 10.0, &mut inv).expect("sale should work");
+    tx.record_sale("Cola", 1, 10.0, &mut inv).expect("anonymous sale should work");
+
+    assert_eq!(tx.sales_for(alice).len(), 1);
+    assert_eq!(tx.get_sales()[2].customer_id(), None);
+
+    let history = report_customer_history(&tx, customers.find(alice).unwrap());
+    assert!(history.contains("alice"));
+    assert!(history.contains("Total spent: 20.00"));
+}
+
+#[test]
+fn loyalty_points_are_earned_redeemed_and_clawed_back() {
+    let (mut inv, mut tx) = cola_store(10);
+    inv.add_product("Bread", "Whole grain", 20.0, 10);
+    inv.find_mut("Bread").unwrap().set_category("Bakery");
+
+    // 1 point per unit, double points on bakery, a point is worth 0.10
+    let mut rules = LoyaltyRules::new(1.0, 0.1);
+    rules.set_category_multiplier("Bakery", 2.0);
+    tx.loyalty_mut().set_rules(rules);
+
+    let alice = 1;
+    tx.record_sale_for(alice, "Cola", 3, 10.0, &mut inv).expect("sale should work");
+    tx.record_sale_for(alice, "Bread", 2, 20.0, &mut inv).expect("sale should work");
+    assert_eq!(tx.loyalty().balance(alice), 30 + 80);
+
+    // anonymous sales earn nothing and cannot pay with points
+    tx.record_sale("Cola", 1, 10.0, &mut inv).expect("sale should work");
+    let mut anon = Cart::new();
+    anon.add_line("Cola", 1, 10.0);
+    anon.add_tender(Tender::LoyaltyPoints(10));
+    assert!(tx.checkout(&anon, &mut inv).is_err());
+
+    let mut greedy = Cart::for_customer(alice);
+    greedy.add_line("Cola", 1, 10.0);
+    greedy.add_tender(Tender::LoyaltyPoints(111));
+    assert!(tx.checkout(&greedy, &mut inv).is_err(), "more points than the balance");
+
+    // 100 points pay 10.00 of two loaves, cash pays the rest
+    let mut cart = Cart::for_customer(alice);
+    cart.add_line("Bread", 2, 20.0);
+    cart.add_tender(Tender::LoyaltyPoints(100));
+    cart.add_tender(Tender::Cash(30.0));
+    let bread = tx.checkout(&cart, &mut inv).expect("checkout should work").sale_ids()[0];
+    assert!((tx.sale(bread).unwrap().amount_due() - 30.0).abs() < 1e-6);
+    assert_eq!(tx.loyalty().balance(alice), 110 - 100 + 80);
+
+    // returning one of two loaves claws back half the earned points and restores half the redeemed
+    let r = tx.record_return(bread, 1, &mut inv).expect("return should work");
+    assert_eq!(r.points_restored(), 50);
+    assert!((r.cash_refund() - 15.0).abs() < 1e-6);
+    assert_eq!(tx.loyalty().balance(alice), 90 - 40 + 50);
+    assert_eq!(inv.on_hand("Bread"), Some(7));
+    assert!(tx.record_return(bread, 2, &mut inv).is_err(), "cannot return more than was sold");
+
+    let kinds: Vec<PointsKind> = tx.loyalty().history(alice).iter().map(|e| e.kind()).collect();
+    assert_eq!(kinds.last(), Some(&PointsKind::Restored));
+    assert!((tx.total_revenue() - (30.0 + 40.0 + 10.0 + 40.0 - 20.0)).abs() < 1e-6);
+}
+
+#[test]
+fn promotions_apply_at_checkout_and_show_in_report() {
+    let (mut inv, mut tx) = cola_store(20);
+    inv.add_product("Chips", "Salted", 7.5, 20);
+    inv.add_product("Bread", "Whole grain", 18.0, 20);
+    inv.find_mut("Bread").unwrap().set_category("Bakery");
+
+    let promos = tx.promotions_mut();
+    promos.add(Promotion::new("Cola 3 for 20", PromotionKind::MultiBuy { quantity: 3, price: 20.0 }, PromotionTarget::Product("Cola".into()))).unwrap();
+    promos.add(Promotion::new("Cola 10% off", PromotionKind::PercentOff(10.0), PromotionTarget::Product("Cola".into()))).unwrap();
+    promos.add(Promotion::new("Chips 2+1", PromotionKind::BuyXGetY { buy: 2, get: 1 }, PromotionTarget::Product("Chips".into()))).unwrap();
+    let mut expired = Promotion::new("Old bakery sale", PromotionKind::PercentOff(50.0), PromotionTarget::Category("Bakery".into()));
+    expired.set_window(Some(0), Some(1));
+    promos.add(expired).unwrap();
+    let mut coupon = Promotion::new("WELCOME5", PromotionKind::AmountOff(1.0), PromotionTarget::All);
+    coupon.set_coupon("welcome5");
+    promos.add(coupon).unwrap();
+    assert!(promos.add(Promotion::new("Bad", PromotionKind::PercentOff(120.0), PromotionTarget::All)).is_err());
+
+    let mut cart = Cart::new();
+    cart.add_line("Cola", 6, 10.0);
+    cart.add_line("Chips", 3, 7.5);
+    cart.add_line("Bread", 1, 18.0);
+    cart.add_coupon("Welcome5");
+    cart.add_tender(Tender::Cash(100.0));
+    let order = tx.checkout(&cart, &mut inv).expect("checkout should work");
+    let ids = order.sale_ids().to_vec();
+    assert_eq!(ids.len(), 3);
+
+    // best automatic promotion wins: 3 for 20 (-20) beats 10% (-6), then the coupon takes 1.00 off each unit
+    let cola = tx.sale(ids[0]).unwrap();
+    let names: Vec<&str> = cola.discounts().iter().map(|d| d.name()).collect();
+    assert_eq!(names, vec!["Cola 3 for 20", "WELCOME5"]);
+    assert!((cola.total_price() - (40.0 - 6.0)).abs() < 1e-6);
+
+    // one free bag of chips, then 1.00 off each of the 3 units
+    let chips = tx.sale(ids[1]).unwrap();
+    assert!((chips.total_price() - (15.0 - 3.0)).abs() < 1e-6);
+
+    // the bakery sale has ended, so only the coupon applies
+    let bread = tx.sale(ids[2]).unwrap();
+    assert!((bread.discount() - 1.0).abs() < 1e-6);
+
+    let mut bad = Cart::new();
+    bad.add_line("Cola", 1, 10.0);
+    bad.add_coupon("NOPE");
+    assert!(tx.checkout(&bad, &mut inv).is_err());
+
+    let rep = report_sales(&tx);
+    assert!(rep.contains("Cola 3 for 20"));
+    assert!(rep.contains("-20.00"));
+}
+
+#[test]
+fn checkout_is_all_or_nothing() {
+    let (mut inv, mut tx) = cola_store(3);
+    inv.add_product("Chips", "Salted", 7.5, 5);
+
+    // each line fits on its own, but together they need 4 Cola
+    let mut cart = Cart::new();
+    cart.add_line("Chips", 1, 7.5);
+    cart.add_line("Cola", 2, 10.0);
+    cart.add_line("Cola", 2, 10.0);
+    assert!(tx.checkout(&cart, &mut inv).is_err());
+    assert_eq!(inv.on_hand("Chips"), Some(5));
+    assert!(tx.get_sales().is_empty());
+}
+
+#[test]
+fn tax_is_computed_per_class_and_rounded_per_order() {
+    let mut inv = Inventory::new();
+    let mut tx = Transactions::new();
+
+    inv.add_product("Cola", "330ml can", 0.99, 100);
+    inv.add_product("Bread", "Whole grain", 1.05, 100);
+    inv.add_product("Stamp", "Postage", 1.0, 100);
+    inv.find_mut("Bread").unwrap().set_tax_class(TaxClass::Reduced);
+    inv.find_mut("Stamp").unwrap().set_tax_class(TaxClass::Exempt);
+
+    // exclusive pricing: tax is added on top of the shelf price
+    tx.set_tax_settings(TaxSettings::new(0.2, 0.05, PricingMode::TaxExclusive, TaxRounding::PerOrder));
+    let mut cart = Cart::new();
+    cart.add_line("Cola", 1, 0.99);
+    cart.add_line("Cola", 1, 0.99);
+    cart.add_line("Bread", 1, 1.05);
+    cart.add_line("Stamp", 1, 1.0);
+    cart.add_tender(Tender::Card(4.48));
+    let order = tx.checkout(&cart, &mut inv).expect("checkout should work");
+
+    // 2 x 0.198 = 0.396 rounds once to 0.40 (per line it would be 0.20 + 0.20)
+    let rates: Vec<(f64, f64)> = order.tax_lines().iter().map(|l| (l.rate(), l.tax())).collect();
+    assert_eq!(rates, vec![(0.0, 0.0), (0.05, 0.05), (0.2, 0.40)]);
+    assert!((order.total() - (4.03 + 0.45)).abs() < 1e-9);
+
+    // inclusive pricing, rounded per line: 0.99 contains 0.165 -> 0.17 of tax
+    tx.set_tax_settings(TaxSettings::new(0.2, 0.05, PricingMode::TaxInclusive, TaxRounding::PerLine));
+    let s = tx.record_sale("Cola", 1, 0.99, &mut inv).expect("sale should work");
+    assert!((s.tax() - 0.17).abs() < 1e-9);
+    assert!((s.net_amount() - 0.82).abs() < 1e-9);
+    assert!((s.gross_amount() - 0.99).abs() < 1e-9);
+
+    let rep = report_sales(&tx);
+    assert!(rep.contains(" 20.0% |"));
+    assert!(rep.contains(" 5.0% |"));
+}
+
+#[test]
+fn checkout_requires_full_payment_and_gives_change() {
+    let (mut inv, mut tx) = cola_store(10);
+    inv.add_product("Chips", "Salted", 7.5, 10);
+
+    let mut cart = Cart::new();
+    cart.add_line("Cola", 2, 10.0);
+    cart.add_line("Chips", 1, 7.5);
+    assert!((tx.quote(&cart, &inv).unwrap() - 27.5).abs() < 1e-9);
+
+    // no tenders, or not enough, and nothing is sold
+    match tx.checkout(&cart, &mut inv) {
+        Err(StoreError::InsufficientPayment { due, paid }) => {
+            assert!((due - 27.5).abs() < 1e-9);
+            assert_eq!(paid, 0.0);
+        }
+        _ => panic!("expected InsufficientPayment"),
+    }
+    cart.add_tender(Tender::Card(20.0));
+    assert!(tx.checkout(&cart, &mut inv).is_err());
+    assert_eq!(inv.on_hand("Cola"), Some(10));
+
+    // card cannot be overpaid; cash can, and the excess is change
+    let mut over = Cart::new();
+    over.add_line("Cola", 1, 10.0);
+    over.add_tender(Tender::Card(15.0));
+    assert!(tx.checkout(&over, &mut inv).is_err());
+
+    cart.add_tender(Tender::Cash(10.0));
+    let order = tx.checkout(&cart, &mut inv).expect("checkout should work");
+    assert!((order.change() - 2.5).abs() < 1e-9);
+    let kinds: Vec<TenderKind> = order.payments().iter().map(|p| p.kind()).collect();
+    assert_eq!(kinds, vec![TenderKind::Card, TenderKind::Cash]);
+    assert_eq!(inv.on_hand("Cola"), Some(8));
+
+    // counter sales are recorded as exact cash
+    let id = tx.record_sale("Chips", 1, 7.5, &mut inv).unwrap().order_id();
+    let order = tx.find_order(id).unwrap();
+    assert_eq!(order.payments()[0].kind(), TenderKind::Cash);
+    assert_eq!(order.change(), 0.0);
+}
+
+#[test]
+fn shifts_reconcile_expected_and_counted_tenders() {
+    let (mut inv, mut tx) = cola_store(20);
+
+    // sold before any shift is open: not attributed
+    tx.record_sale("Cola", 1, 10.0, &mut inv).unwrap();
+
+    let shift_id = tx.open_shift("alice", 50.0).unwrap().id();
+    assert!(tx.open_shift("bob", 0.0).is_err());
+
+    let mut cart = Cart::new();
+    cart.add_line("Cola", 2, 10.0);
+    cart.add_tender(Tender::Cash(25.0));
+    tx.checkout(&cart, &mut inv).unwrap();
+
+    let mut cart = Cart::new();
+    cart.add_line("Cola", 3, 10.0);
+    cart.add_tender(Tender::Card(30.0));
+    let sale_id = tx.checkout(&cart, &mut inv).unwrap().sale_ids()[0];
+    tx.record_return(sale_id, 1, &mut inv).unwrap();
+
+    // float 50 + cash 25 - change 5 - refund 10
+    let shift = tx.current_shift().unwrap();
+    let expected = expected_by_tender(&tx, shift);
+    assert_eq!(expected, vec![(TenderKind::Cash, 60.0), (TenderKind::Card, 30.0)]);
+    assert!(report_shift(&tx, shift).starts_with("X report"));
+
+    tx.close_shift(&[(TenderKind::Cash, 58.0), (TenderKind::Card, 30.0)]).unwrap();
+    assert!(tx.current_shift().is_none());
+    let report = report_shift(&tx, tx.find_shift(shift_id).unwrap());
+    assert!(report.starts_with("Z report"));
+    assert!(report.contains("Orders: 2, returns: 1"));
+    assert!(report.contains("-2.00"));
+    assert!(report.contains("+0.00"));
+}
+
+#[test]
+fn receipts_render_as_text_html_and_pdf() {
+    let (mut inv, mut tx) = cola_store(10);
+    tx.promotions_mut()
+        .add(Promotion::new("Cola 10% off", PromotionKind::PercentOff(10.0), PromotionTarget::Product("Cola".into())))
+        .unwrap();
+
+    let mut cart = Cart::new();
+    cart.add_line("Cola", 2, 10.0);
+    cart.add_tender(Tender::Cash(20.0));
+    let order_id = tx.checkout(&cart, &mut inv).unwrap().id();
+    let header = StoreHeader::new("Corner <Shop>", "1 High St", "555-0100");
+
+    let text = render_receipt(&tx, &header, order_id, ReceiptFormat::Text).unwrap();
+    assert!(text.contains(&format!("Order #{order_id}")));
+    assert!(text.contains("Cola x2 @ 10.00"));
+    assert!(text.contains("Cola 10% off"));
+    assert!(text.lines().any(|l| l.starts_with("TOTAL") && l.ends_with("18.00")));
+    assert!(text.lines().any(|l| l.starts_with("Change") && l.ends_with("2.00")));
+    assert!(text.lines().all(|l| l.chars().count() <= 40));
+
+    let html = render_receipt(&tx, &header, order_id, ReceiptFormat::Html).unwrap();
+    assert!(html.contains("Corner &lt;Shop&gt;"));
+
+    let pdf = render_receipt(&tx, &header, order_id, ReceiptFormat::Pdf).unwrap();
+    assert!(pdf.starts_with("%PDF-1.4"));
+    assert!(pdf.contains("Corner <Shop>"));
+    let xref: usize = pdf.lines().rev().nth(1).unwrap().parse().unwrap();
+    assert!(pdf[xref..].starts_with("xref"));
+
+    assert!(matches!(
+        render_receipt(&tx, &header, 99, ReceiptFormat::Text),
+        Err(StoreError::NotFound(_))
+    ));
+}
+
+#[test]
+fn gift_cards_are_sold_redeemed_and_expire() {
+    let (mut inv, mut tx) = cola_store(10);
+
+    // sold as an untaxed line on an order
+    let mut cart = Cart::new();
+    cart.add_gift_card(25.0);
+    cart.add_tender(Tender::Cash(25.0));
+    let order = tx.checkout(&cart, &mut inv).unwrap();
+    let number = order.gift_cards()[0].clone();
+    assert_eq!(tx.stored_value().balance(&number, 0).unwrap(), 25.0);
+
+    // partial redemption, then an overdraw that must fail without selling anything
+    let mut cart = Cart::new();
+    cart.add_line("Cola", 1, 10.0);
+    cart.add_tender(Tender::GiftCard { number: number.clone(), amount: 10.0 });
+    tx.checkout(&cart, &mut inv).unwrap();
+    assert_eq!(tx.stored_value().balance(&number, 0).unwrap(), 15.0);
+
+    let mut cart = Cart::new();
+    cart.add_line("Cola", 2, 10.0);
+    cart.add_tender(Tender::GiftCard { number: number.clone(), amount: 10.0 });
+    cart.add_tender(Tender::GiftCard { number: number.clone(), amount: 10.0 });
+    assert!(tx.checkout(&cart, &mut inv).is_err());
+    assert_eq!(inv.on_hand("Cola"), Some(9));
+
+    // wrong kind and unknown cards are refused
+    let mut cart = Cart::new();
+    cart.add_line("Cola", 1, 10.0);
+    cart.add_tender(Tender::StoreCredit { number: number.clone(), amount: 10.0 });
+    assert!(tx.checkout(&cart, &mut inv).is_err());
+    let mut cart = Cart::new();
+    cart.add_line("Cola", 1, 10.0);
+    cart.add_tender(Tender::GiftCard { number: "GC999999".into(), amount: 10.0 });
+    assert!(matches!(tx.checkout(&cart, &mut inv), Err(StoreError::NotFound(_))));
+
+    // an empty gift card is refused before any stock is taken
+    let (stock, sales, entries) = (inv.find("Cola").unwrap().quantity(), tx.get_sales().len(), tx.journal().entries().len());
+    let mut cart = Cart::new();
+    cart.add_line("Cola", 1, 10.0);
+    cart.add_gift_card(0.0);
+    cart.add_tender(Tender::Cash(10.0));
+    assert!(matches!(tx.checkout(&cart, &mut inv), Err(StoreError::InvalidInput(_))));
+    assert_eq!(inv.find("Cola").unwrap().quantity(), stock);
+    assert_eq!((tx.get_sales().len(), tx.journal().entries().len()), (sales, entries));
+
+    // refunds can go to store credit instead of cash
+    let sale_id = tx.record_sale("Cola", 2, 10.0, &mut inv).unwrap().id();
+    let ret = tx.record_return_for_credit(sale_id, 1, &mut inv).unwrap();
+    assert_eq!(ret.cash_refund(), 0.0);
+    let credit = ret.credit_card().unwrap().to_string();
+    assert_eq!(tx.stored_value().find(&credit).unwrap().kind(), CardKind::StoreCredit);
+    assert_eq!(tx.stored_value().liability(CardKind::StoreCredit), 10.0);
+
+    // cards issued while a validity of 0 days is set are expired at once
+    tx.stored_value_mut().set_validity(CardKind::GiftCard, Some(0));
+    let expiring = tx.stored_value_mut().issue(CardKind::GiftCard, 5.0, None, 100).unwrap().number().to_string();
+    assert_eq!(tx.stored_value().balance(&expiring, 100).unwrap(), 0.0);
+    let report = report_liability(tx.stored_value(), 100);
+    assert!(report.contains("Expired"));
+    assert!(report.contains("Outstanding gift cards: 20.00\nOutstanding store credit: 10.00\n"));
+    assert_eq!(tx.expire_cards(100).unwrap(), vec![expiring]);
+    assert_eq!(tx.stored_value().liability(CardKind::GiftCard), 15.0);
+    // the write-off moves the balance from the card liability to breakage
+    assert_eq!(tx.journal().balance(AccountRole::Breakage), -5.0);
+    assert_eq!(tx.journal().entries().last().unwrap().kind(), EntryKind::Breakage);
+}
+
+#[test]
+fn customer_groups_buy_from_their_price_list() {
+    let (mut inv, mut tx) = cola_store(50);
+    inv.add_product("Chips", "Salted", 8.0, 50);
+    inv.add_product("Bread", "Loaf", 4.0, 50);
+    inv.find_mut("Bread").unwrap().set_category("Bakery");
+
+    let mut wholesale = PriceList::new("Wholesale");
+    wholesale.set_adjustment(-20.0);
+    wholesale.set_category_adjustment("Bakery", -50.0);
+    wholesale.set_price("Cola", 7.0).unwrap();
+    tx.price_lists_mut().add(wholesale).unwrap();
+    assert!(tx.price_lists_mut().add(PriceList::new("wholesale")).is_err());
+    assert!(tx.price_lists_mut().set_group_list("Trade", "Nope").is_err());
+    tx.price_lists_mut().set_group_list("Trade", "Wholesale").unwrap();
+    tx.price_lists_mut().assign(7, "Trade");
+
+    // the till price is ignored for a customer on a list
+    let mut cart = Cart::for_customer(7);
+    cart.add_line("Cola", 2, 10.0);
+    cart.add_line("Chips", 1, 8.0);
+    cart.add_line("Bread", 1, 4.0);
+    assert!((tx.quote(&cart, &inv).unwrap() - (14.0 + 6.4 + 2.0)).abs() < 1e-9);
+    cart.add_tender(Tender::Cash(22.4));
+    let sale_ids = tx.checkout(&cart, &mut inv).unwrap().sale_ids().to_vec();
+    let first = tx.sale(sale_ids[0]).unwrap();
+    assert_eq!(first.unit_price(), 7.0);
+    assert_eq!(first.price_list(), Some("Wholesale"));
+
+    // everyone else still pays the given price
+    let sale = tx.record_sale_for(8, "Cola", 1, 10.0, &mut inv).unwrap();
+    assert_eq!(sale.unit_price(), 10.0);
+    assert_eq!(sale.price_list(), None);
+
+    // list rules apply to the shelf price in effect when the sale is made
+    let later = 4_000_000_000;
+    inv.schedule_price("Chips", 10.0, later).unwrap();
+    tx.set_time(Some(later - 1));
+    assert_eq!(tx.record_sale_for(7, "Chips", 1, 8.0, &mut inv).unwrap().unit_price(), 6.4);
+    tx.set_time(Some(later));
+    assert_eq!(tx.record_sale_for(7, "Chips", 1, 8.0, &mut inv).unwrap().unit_price(), 8.0);
+}
+
+#[test]
+fn price_changes_are_kept_and_can_be_scheduled() {
+    let (mut inv, mut tx) = cola_store(20);
+
+    let now = store_inventory_management_system::clock::now();
+    let day = 24 * 60 * 60;
+    inv.schedule_price("Cola", 12.0, now + 30 * day).unwrap();
+    assert!(inv.schedule_price("Nope", 1.0, now).is_err());
+    inv.edit_product("Cola", None, None, Some(11.0), None);
+
+    let cola = inv.find("Cola").unwrap();
+    assert_eq!(cola.price(), 11.0);
+    assert_eq!(cola.price_at(0), 10.0);
+    assert_eq!(cola.price_at(now + 31 * day), 12.0);
+    assert_eq!(cola.price_history().len(), 3);
+    let history = report_price_history(cola, now + day);
+    assert!(history.contains("Past"));
+    assert!(history.contains("Current"));
+    assert!(history.contains("Scheduled"));
+    assert!(history.contains("1970-01-01     |   10.00 |"));
+
+    // pending changes can be dropped; past ones cannot
+    let cola = inv.find_mut("Cola").unwrap();
+    assert!(cola.cancel_price_change(now + 30 * day, now));
+    assert!(!cola.cancel_price_change(0, now));
+    assert_eq!(cola.price_at(now + 31 * day), 11.0);
+
+    // a sale remembers the shelf price of the day, even when charged less
+    let sale = tx.record_sale("Cola", 1, 9.0, &mut inv).unwrap();
+    assert_eq!(sale.list_price(), 11.0);
+    assert!(report_sales(&tx).contains("Shelf price"));
+}
+
+#[test]
+fn purchases_in_supplier_currency_are_converted_to_base() {
+    let (mut inv, mut tx) = cola_store(0);
+    let base = tx.exchange_rates().base().to_string();
+
+    // no rate yet for euros
+    assert!(matches!(
+        tx.record_purchase_in("Cola", 10, Money::new(2.0, "eur"), &mut inv),
+        Err(StoreError::NotFound(_))
+    ));
+    assert!(tx.exchange_rates_mut().set_rate(&base, 2.0, 0).is_err());
+
+    let now = store_inventory_management_system::clock::now();
+    tx.exchange_rates_mut().set_rate("EUR", 1.10, 0).unwrap();
+    tx.exchange_rates_mut().set_rate("EUR", 1.25, now + 1000).unwrap();
+    assert_eq!(tx.exchange_rates().rate_at("eur", now), Some(1.10));
+    assert_eq!(tx.exchange_rates().rate_at("EUR", now + 2000), Some(1.25));
+    assert_eq!(tx.exchange_rates().history("EUR").len(), 2);
+    assert!(report_rates(tx.exchange_rates()).contains("EUR      | 1.1000 | 1970-01-01"));
+
+    let p = tx.record_purchase_in("Cola", 10, Money::new(2.0, "eur"), &mut inv).unwrap();
+    assert!((p.total_cost() - 22.0).abs() < 1e-9);
+    assert_eq!(p.supplier_total(), Money::new(20.0, "EUR"));
+    tx.record_purchase("Cola", 5, 3.0, &mut inv).unwrap();
+    assert!((tx.total_purchase_cost() - 37.0).abs() < 1e-9);
+    assert_eq!(inv.on_hand("Cola"), Some(15));
+
+    let report = report_purchases(&tx);
+    assert!(report.contains("20.00 EUR @ 1.1000"));
+    assert!(report.contains(&format!("Total ({base}): 37.00")));
+}
+
+#[test]
+fn landed_charges_are_allocated_and_feed_margins() {
+    let (mut inv, mut tx) = cola_store(0);
+    inv.add_product("Chips", "Salted", 8.0, 0);
+    inv.find_mut("Cola").unwrap().set_weight(0.4);
+    inv.find_mut("Chips").unwrap().set_weight(0.1);
+
+    let cola = tx.record_purchase("Cola", 10, 5.0, &mut inv).unwrap().id();
+    let chips = tx.record_purchase("Chips", 30, 3.0, &mut inv).unwrap().id();
+
+    // 14 over values 50 : 90
+    let base = tx.exchange_rates().base().to_string();
+    let c = tx
+        .add_landed_charge(&[cola, chips], ChargeKind::Duty, Money::new(14.0, &base), AllocationBasis::Value, &inv)
+        .unwrap();
+    assert_eq!(c.allocations(), &[(cola, 5.0), (chips, 9.0)]);
+
+    // weights 4kg : 3kg, and shares always add up to the charge
+    let c = tx
+        .add_landed_charge(&[cola, chips], ChargeKind::Freight, Money::new(7.0, &base), AllocationBasis::Weight, &inv)
+        .unwrap();
+    assert_eq!(c.allocations(), &[(cola, 4.0), (chips, 3.0)]);
+    let c = tx
+        .add_landed_charge(&[cola, chips], ChargeKind::Handling, Money::new(1.0, &base), AllocationBasis::Quantity, &inv)
+        .unwrap();
+    assert_eq!(c.allocations().iter().map(|(_, a)| a).sum::<f64>(), 1.0);
+    assert!(tx.add_landed_charge(&[99], ChargeKind::Other, Money::new(1.0, &base), AllocationBasis::Value, &inv).is_err());
+
+    // cola: 50 + 5 + 4 + 0.25 over 10 units
+    let p = &tx.get_purchases()[0];
+    assert!((p.landed_unit_cost() - 5.925).abs() < 1e-9);
+    assert!((tx.unit_cost("Cola", &inv).unwrap() - 5.925).abs() < 1e-9);
+
+    tx.record_sale("Cola", 2, 10.0, &mut inv).unwrap();
+    let report = margins_report(&tx);
+    assert_eq!(report.rows()[0][0], Value::text("Cola"));
+    assert_eq!(report.rows()[0][3], Value::Money(11.85));
+    assert_eq!(report.rows()[0][4], Value::Money(8.15));
+    assert!(report_margins(&tx).starts_with("Margins\n"));
+}
+
+#[test]
+fn supplier_returns_raise_debit_notes_and_net_purchase_cost() {
+    let (mut inv, mut tx) = cola_store(0);
+    tx.exchange_rates_mut().set_rate("EUR", 2.0, 0).unwrap();
+
+    let id = tx.record_purchase_in("Cola", 10, Money::new(3.0, "EUR"), &mut inv).unwrap().id();
+    inv.reserve("Cola", "phone order", 6, u64::MAX).unwrap();
+
+    // reserved units stay; only 4 are free to go back
+    assert!(matches!(
+        tx.record_supplier_return(id, 5, "Damaged", &mut inv),
+        Err(StoreError::OutOfStock { available: 4, .. })
+    ));
+    let r = tx.record_supplier_return(id, 4, "Damaged", &mut inv).unwrap();
+    assert_eq!(r.debit_note(), "DN-0001");
+    assert_eq!(r.amount(), 24.0);
+    assert_eq!(r.supplier_amount(), &Money::new(12.0, "EUR"));
+    assert_eq!(inv.on_hand("Cola"), Some(6));
+    assert!((tx.total_purchase_cost() - 36.0).abs() < 1e-9);
+
+    // cannot send back more than was bought
+    tx.record_purchase("Cola", 10, 6.0, &mut inv).unwrap();
+    assert!(tx.record_supplier_return(id, 7, "", &mut inv).is_err());
+    assert!(tx.record_supplier_return(99, 1, "", &mut inv).is_err());
+
+    let note = report_debit_note(&tx, 1).unwrap();
+    assert!(note.contains("DEBIT NOTE DN-0001"));
+    assert!(note.contains("12.00 EUR"));
+    let report = report_purchases(&tx);
+    assert!(report.contains("Returned x4 (DN-0001)"));
+    assert!(report.contains("-24.00"));
+}
+
+#[test]
+fn supplier_invoices_are_matched_against_orders_and_receipts() {
+    let (mut inv, mut tx) = cola_store(0);
+    inv.add_product("Chips", "Salted", 7.5, 0);
+    let usd = |amount| Money::new(amount, "USD");
+
+    let po = tx
+        .create_purchase_order("Acme", vec![DocumentLine::new("Cola", 10, usd(5.0)), DocumentLine::new("Chips", 4, usd(3.0))], &inv)
+        .unwrap()
+        .id();
+    assert!(tx.create_purchase_order("Acme", vec![DocumentLine::new("Ghost", 1, usd(1.0))], &inv).is_err());
+    assert!(tx.receive_purchase_order(po, "Bread", 1, &mut inv).is_err());
+
+    // half the cola has arrived: the invoice for all of it waits
+    tx.receive_purchase_order(po, "Cola", 5, &mut inv).unwrap();
+    assert_eq!(tx.get_purchases()[0].unit_price(), 5.0);
+    let inv1 = tx.capture_invoice(po, "A-100", vec![DocumentLine::new("Cola", 10, usd(5.0))]).unwrap();
+    assert_eq!(inv1.status(), InvoiceStatus::OnHold);
+    assert_eq!(
+        inv1.discrepancies(),
+        &[Discrepancy::NotReceived { product: "Cola".into(), received: 5, invoiced: 10 }]
+    );
+    let inv1 = inv1.id();
+    tx.receive_purchase_order(po, "Cola", 5, &mut inv).unwrap();
+    tx.match_invoices();
+    assert_eq!(tx.procurement().find_invoice(inv1).unwrap().status(), InvoiceStatus::Matched);
+    assert_eq!(inv.on_hand("Cola"), Some(10));
+    assert!(tx.capture_invoice(po, "A-100", vec![DocumentLine::new("Chips", 4, usd(3.0))]).is_err());
+
+    // price 3% over the order: held until the tolerance allows it
+    tx.receive_purchase_order(po, "Chips", 4, &mut inv).unwrap();
+    let inv2 = tx.capture_invoice(po, "A-101", vec![DocumentLine::new("Chips", 4, usd(3.09))]).unwrap().id();
+    assert!(matches!(tx.procurement().find_invoice(inv2).unwrap().discrepancies(), [Discrepancy::Price { .. }]));
+    tx.procurement_mut().set_tolerances(MatchTolerances::new(0.0, 5.0));
+    tx.match_invoices();
+    assert_eq!(tx.procurement().find_invoice(inv2).unwrap().status(), InvoiceStatus::Matched);
+
+    // billing the cola twice is over the order
+    let inv3 = tx.capture_invoice(po, "A-102", vec![DocumentLine::new("Cola", 2, usd(5.0))]).unwrap();
+    assert!(inv3.discrepancies().contains(&Discrepancy::OverOrdered { product: "Cola".into(), ordered: 10, invoiced: 12 }));
+    let inv3 = inv3.id();
+    tx.dispute_invoice(inv3, "Duplicate billing").unwrap();
+    assert_eq!(tx.procurement().find_invoice(inv3).unwrap().status(), InvoiceStatus::Disputed);
+
+    let report = report_invoice_matching(tx.procurement());
+    assert!(report.contains("A-100"));
+    assert!(report.contains("Matched"));
+    assert!(report.contains("Dispute: Duplicate billing"));
+}
+
+#[test]
+fn postings_produce_balanced_journal_entries() {
+    let (mut inv, mut tx) = cola_store(0);
+    tx.set_tax_settings(TaxSettings::new(0.2, 0.0, PricingMode::TaxExclusive, TaxRounding::PerLine));
+    tx.set_time(Some(1_760_832_000));
+
+    tx.record_purchase("Cola", 10, 5.0, &mut inv).unwrap();
+    tx.record_sale("Cola", 2, 10.0, &mut inv).unwrap();
+    tx.record_return(1, 1, &mut inv).unwrap();
+    assert_eq!(tx.record_stock_count("Cola", 8, "breakage", &mut inv).unwrap(), -1);
+    assert_eq!(inv.on_hand("Cola"), Some(8));
+
+    let journal = tx.journal();
+    assert_eq!(journal.entries().len(), 4);
+    for e in journal.entries() {
+        let debits: f64 = e.lines().iter().map(|l| l.debit_amount()).sum();
+        let credits: f64 = e.lines().iter().map(|l| l.credit_amount()).sum();
+        assert!((debits - credits).abs() < 1e-9, "{}", e.reference());
+    }
+    assert_eq!(journal.entries()[1].reference(), "Order #1");
+    assert_eq!(journal.balance(AccountRole::Cash), 12.0);
+    assert_eq!(journal.balance(AccountRole::Revenue), -10.0);
+    assert_eq!(journal.balance(AccountRole::TaxPayable), -2.0);
+    assert_eq!(journal.balance(AccountRole::Cogs), 5.0);
+    assert_eq!(journal.balance(AccountRole::Inventory), 40.0);
+    assert_eq!(journal.balance(AccountRole::AccountsPayable), -50.0);
+    assert_eq!(journal.balance(AccountRole::InventoryAdjustments), 5.0);
+
+    // an entry that does not balance is refused
+    assert!(JournalEntry::new(EntryKind::StockCount, "bad", 0, vec![JournalLine::debit(AccountRole::Cash, 1.0)]).is_err());
+
+    assert!(tx.chart_mut().set_account(AccountRole::Revenue, "1000", "Sales").is_err());
+    tx.chart_mut().set_account(AccountRole::Revenue, "4100", "Shop sales").unwrap();
+    let report = report_trial_balance(tx.journal(), tx.chart());
+    assert!(report.contains("4100 | Shop sales"));
+    assert!(report.contains("62.00"));
+    assert!(report.contains("Status: Balanced"));
+    let csv = trial_balance_report(tx.journal(), tx.chart()).render(ReportFormat::Csv);
+    assert!(csv.starts_with("Code,Account,Debit,Credit\n"));
+    assert!(csv.contains("4100,Shop sales,"));
+    assert!(report_journal(tx.journal(), tx.chart()).contains("2 | 2025-10-19 | Order #1"));
+
+    // editing the quantity is a stock adjustment too
+    tx.edit_product("Cola", None, None, None, Some(10), &mut inv).unwrap();
+    assert_eq!(inv.on_hand("Cola"), Some(10));
+    assert_eq!(tx.journal().balance(AccountRole::Inventory), 50.0);
+    assert_eq!(tx.journal().balance(AccountRole::InventoryAdjustments), -5.0);
+}
+
+#[test]
+fn journal_exports_keep_stable_ids_across_formats() {
+    use store_inventory_management_system::clock::{DAY, format_date, parse_date};
+    assert_eq!(format_date(0), "1970-01-01");
+    assert_eq!(parse_date("2024-02-29").map(format_date).as_deref(), Some("2024-02-29"));
+    assert_eq!(parse_date("2023-02-29"), None);
+
+    // bought on Monday 4 March 2024, sold that afternoon and the next morning
+    let monday = parse_date("2024-03-04").unwrap();
+    let tuesday = monday + DAY;
+    let (mut inv, mut tx) = cola_store(0);
+    tx.set_time(Some(monday + 9 * 3600));
+    tx.record_purchase("Cola", 10, 5.0, &mut inv).unwrap();
+    tx.set_time(Some(monday + 14 * 3600 + 30 * 60));
+    tx.record_sale("Cola", 2, 10.0, &mut inv).unwrap();
+    tx.set_time(Some(tuesday + 10 * 3600));
+    tx.record_sale("Cola", 1, 10.0, &mut inv).unwrap();
+
+    let entries = journal_transactions(tx.journal(), monday, tuesday);
+    assert_eq!(entries.iter().map(|t| t.id()).collect::<Vec<_>>(), ["JE-000001", "JE-000002"]);
+    let next_day = journal_transactions(tx.journal(), tuesday, tuesday + DAY);
+    assert_eq!(next_day.iter().map(|t| t.id()).collect::<Vec<_>>(), ["JE-000003"]);
+    assert!(journal_transactions(tx.journal(), tuesday + DAY, tuesday + 2 * DAY).is_empty());
+
+    let csv = render_export(&entries, tx.chart(), AccountRole::Cash, monday..tuesday, 0.0, "USD", ExportFormat::CsvJournal);
+    assert!(csv.starts_with("transaction_id,date,memo,account_code,account_name,debit,credit\n"));
+    assert!(csv.contains("JE-000002,2024-03-04,Order #1,1000,Cash,20.00,0.00"));
+
+    // the cash register only has the sale in it; the other accounts are splits
+    let qif = render_export(&entries, tx.chart(), AccountRole::Cash, monday..tuesday, 0.0, "USD", ExportFormat::Qif);
+    assert!(qif.starts_with("!Type:Bank\nD03/04/2024\n"));
+    assert!(qif.contains("T20.00\nNJE-000002\nPOrder #1\nS4000 Sales revenue\n$20.00\n"));
+    assert!(qif.contains("S5000 Cost of goods sold\n$-10.00\nS1200 Inventory\n$10.00\n^"));
+    assert!(!qif.contains("JE-000001"));
+
+    let summaries = daily_summaries(tx.journal(), monday, tuesday + DAY);
+    let ids: Vec<&str> = summaries.iter().map(|t| t.id()).collect();
+    assert_eq!(ids, ["SALES-20240304", "PURCH-20240304", "SALES-20240305"]);
+    assert_eq!(summaries[1].amount_on(AccountRole::AccountsPayable), -50.0);
+    assert_eq!(summaries[2].amount_on(AccountRole::Cash), 10.0);
+
+    let closing = tx.journal().balance_at(AccountRole::Cash, tuesday + DAY);
+    let ofx = render_export(&summaries, tx.chart(), AccountRole::Cash, monday..tuesday + DAY, closing, "USD", ExportFormat::Ofx);
+    assert!(ofx.contains("<FITID>SALES-20240304</FITID>"));
+    assert!(ofx.contains("<DTPOSTED>20240304000000</DTPOSTED>\n<TRNAMT>20.00</TRNAMT>"));
+    assert!(!ofx.contains("PURCH-"));
+
+    // a range that starts mid-day leaves that day out rather than reusing its id
+    let partial = daily_summaries(tx.journal(), monday + 12 * 3600, tuesday + DAY);
+    assert_eq!(partial.iter().map(|t| t.id()).collect::<Vec<_>>(), ["SALES-20240305"]);
+    // the ledger balance is the account's closing balance, not the range's sum
+    let closing = tx.journal().balance_at(AccountRole::Cash, tuesday + DAY);
+    assert_eq!(closing, 30.0);
+    let ofx = render_export(&partial, tx.chart(), AccountRole::Cash, monday + 12 * 3600..tuesday + DAY, closing, "USD", ExportFormat::Ofx);
+    assert!(ofx.contains("<TRNAMT>10.00</TRNAMT>"));
+    assert!(ofx.contains("<LEDGERBAL><BALAMT>30.00</BALAMT><DTASOF>20240306000000</DTASOF>"));
+    // the statement covers the range asked for, not just the days with activity
+    assert!(ofx.contains("<DTSTART>20240304120000</DTSTART>\n<DTEND>20240306000000</DTEND>"));
+
+    // exporting again after more activity keeps the earlier ids
+    tx.set_time(Some(tuesday + 11 * 3600));
+    tx.record_sale("Cola", 1, 10.0, &mut inv).unwrap();
+    let again = journal_transactions(tx.journal(), monday, tuesday + DAY);
+    assert_eq!(again[1].id(), "JE-000002");
+    assert_eq!(again[1].memo(), "Order #1");
+    assert_eq!(again.len(), 4);
+}
+
+#[test]
+fn sales_are_aggregated_by_period_hour_weekday_and_product() {
+    use store_inventory_management_system::clock::{self, DAY, parse_date};
+    let (mut inv, mut tx) = cola_store(20);
+    inv.add_product("Chips", "Salted", 5.0, 20);
+    inv.find_mut("Chips").unwrap().set_category("Snacks");
+    let hour = 3600;
+    let monday = parse_date("2024-03-04").unwrap();
+
+    // one sale the week before, then Monday, Tuesday and the Monday after
+    tx.set_time(Some(monday - 5 * DAY + 12 * hour));
+    tx.record_sale("Cola", 1, 10.0, &mut inv).unwrap();
+    tx.set_time(Some(monday + 10 * hour));
+    tx.open_shift("alice", 50.0).unwrap();
+    tx.set_time(Some(monday + 10 * hour + 15 * 60));
+    tx.record_sale("Cola", 2, 10.0, &mut inv).unwrap();
+    tx.close_shift(&[]).unwrap();
+    tx.set_time(Some(monday + DAY + 16 * hour + 40 * 60));
+    tx.record_sale_for(7, "Chips", 3, 5.0, &mut inv).unwrap();
+    tx.set_time(Some(monday + 7 * DAY + 10 * hour + 5 * 60));
+    let mut cart = Cart::new();
+    cart.add_line("Cola", 1, 10.0);
+    cart.add_line("Chips", 1, 5.0);
+    cart.add_tender(Tender::Cash(15.0));
+    tx.checkout(&cart, &mut inv).unwrap();
+
+    let (from, to) = (monday, monday + 8 * DAY);
+    let all = sales_between(&tx, &inv, from, to, &SalesFilter::new());
+    let t = totals(&all);
+    assert_eq!((t.orders(), t.units()), (3, 7));
+    assert!((t.gross() - 50.0).abs() < 1e-9);
+    assert!((t.average_order() - 50.0 / 3.0).abs() < 1e-9);
+
+    let hours = by_hour(&all);
+    assert_eq!((hours[10].units(), hours[16].units()), (4, 3));
+    let weekdays = by_weekday(&all);
+    assert_eq!((weekdays[0].orders(), weekdays[1].orders()), (2, 1));
+    let days = by_period(&all, from, to, Period::Day);
+    assert_eq!(days.len(), 8);
+    let orders: Vec<u32> = days.iter().map(|(_, t)| t.orders()).collect();
+    assert_eq!(orders, [1, 1, 0, 0, 0, 0, 0, 1]);
+    let weeks = by_period(&all, from, to, Period::Week);
+    assert_eq!(weeks.iter().map(|(_, t)| t.orders()).collect::<Vec<_>>(), [2, 1]);
+    assert_eq!(by_period(&all, from, to, Period::Month).len(), 1);
+    let products = by_product(&all);
+    assert_eq!(products[0].0, "Cola");
+    assert!((products[0].1.gross() - 30.0).abs() < 1e-9);
+
+    let mut filter = SalesFilter::new();
+    filter.set_cashier(Some("alice"));
+    assert_eq!(totals(&sales_between(&tx, &inv, from, to, &filter)).units(), 2);
+    let mut filter = SalesFilter::new();
+    filter.set_category(Some("Snacks"));
+    assert_eq!(totals(&sales_between(&tx, &inv, from, to, &filter)).units(), 4);
+    let mut filter = SalesFilter::new();
+    filter.set_customer(Some(7));
+    let only = sales_between(&tx, &inv, from, to, &filter);
+    assert_eq!(only.len(), 1);
+    assert_eq!(only[0].product_name(), "Chips");
+
+    let report = sales_summary_report(&tx, &inv, from, to, Period::Week, &SalesFilter::new());
+    assert_eq!(report.title(), "Sales 2024-03-04 to 2024-03-11");
+    let labels: Vec<String> = report.rows().iter().map(|r| r[0].display()).collect();
+    assert_eq!(labels, ["Week of 2024-03-04", "Week of 2024-03-11", "Total"]);
+    assert_eq!(report.sections().iter().map(|s| s.title()).collect::<Vec<_>>(), ["By hour", "By weekday", "By product"]);
+    assert_eq!(report.sections()[0].rows()[1][0], Value::text("16:00"));
+    let text = report_sales_summary(&tx, &inv, from, to, Period::Week, &SalesFilter::new());
+    assert!(text.starts_with("Sales 2024-03-04 to 2024-03-11\n"));
+    assert!(text.contains("Previous period gross: 10.00\nPrevious period orders: 1\n"));
+    assert!(text.contains("Change in gross: 400.0%\nChange in orders: 200.0%\n"));
+    assert_eq!(clock::weekday(monday), 0);
+}
+
+#[test]
+fn reports_render_as_aligned_text_csv_json_markdown_and_html() {
+    let mut inv = Inventory::new();
+    let mut tx = Transactions::new();
+    inv.add_product("Sparkling Mineral Water Extra Large", "1.5L bottle, \"glass\"", 2.5, 0);
+    inv.add_product("Cola", "330ml can", 10.0, 5);
+    tx.record_purchase("Sparkling Mineral Water Extra Large", 1200, 1.0, &mut inv).unwrap();
+
+    // long names and four-digit quantities keep every column lined up
+    let text = inventory_report(&inv).render(ReportFormat::Text);
+    let bars = |line: &str| line.match_indices('|').map(|(i, _)| i).collect::<Vec<_>>();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines[0], "Inventory");
+    assert_eq!(bars(lines[1]), bars(lines[3]));
+    assert_eq!(bars(lines[3]), bars(lines[4]));
+    assert!(lines[3].contains("Sparkling Mineral Water Extra Large | "));
+    assert!(lines[3].contains(" 1200 |"));
+    assert_eq!(text, report_inventory(&inv));
+
+    let csv = inventory_report(&inv).render(ReportFormat::Csv);
+    assert!(csv.starts_with("Name,Description,Price,Qty,Rsvd,Avail\n"));
+    assert!(csv.contains("Sparkling Mineral Water Extra Large,\"1.5L bottle, \"\"glass\"\"\",2.50,1200,0,1200\n"));
+
+    let json = inventory_report(&inv).render(ReportFormat::Json);
+    assert!(json.starts_with("{\"title\": \"Inventory\", \"rows\": [{\"Name\": \"Sparkling"));
+    assert!(json.contains("\"Description\": \"1.5L bottle, \\\"glass\\\"\", \"Price\": 2.50, \"Qty\": 1200"));
+
+    let md = purchases_report(&tx).render(ReportFormat::Markdown);
+    assert!(md.starts_with("## Purchases\n\n| No | Product | Qty |"));
+    assert!(md.contains("| ---: | --- | ---: |"));
+    assert!(md.contains("**Total (USD):** 1200.00"));
+
+    tx.set_tax_settings(TaxSettings::new(0.2, 0.0, PricingMode::TaxExclusive, TaxRounding::PerLine));
+    tx.record_sale("Cola", 1, 10.0, &mut inv).unwrap();
+    let html = sales_report(&tx).render(ReportFormat::Html);
+    assert!(html.contains("<h1>Sales</h1>"));
+    assert!(html.contains("<td class=\"num\">10.00</td>"));
+    assert!(html.contains("<h2>Tax by rate</h2>"));
+    assert!(html.contains("<td class=\"num\">20.0%</td>"));
+
+    // callers can plug in a format of their own
+    struct RowCount;
+    impl ReportFormatter for RowCount {
+        fn render(&self, report: &Report) -> String {
+            format!("{}: {} rows", report.title(), report.rows().len())
+        }
+    }
+    let mut custom = Report::new("Custom", &["A", "B"]);
+    custom.add_row(vec![Value::text("x")]);
+    assert_eq!(custom.rows()[0], vec![Value::text("x"), Value::Empty]);
+    assert_eq!(RowCount.render(&custom), "Custom: 1 rows");
+}
+
+#[test]
+fn listings_sort_filter_and_page() {
+    let (mut inv, mut tx) = cola_store(12);
+    inv.add_product("Chips", "Salted", 7.5, 5);
+    inv.add_product("Bread", "Whole grain", 18.0, 10);
+    inv.add_product("Cider", "Apple, can", 25.0, 2);
+    assert_eq!(inventory_report_for(&inv, &ListQuery::new()).render(ReportFormat::Text), report_inventory(&inv));
+
+    let names = |report: &Report| -> Vec<String> { report.rows().iter().map(|r| r[0].display()).collect() };
+    let mut q = ListQuery::new();
+    q.set_sort(Some(SortKey::Value), true);
+    assert_eq!(names(&inventory_report_for(&inv, &q)), ["Bread", "Cola", "Cider", "Chips"]);
+    q.set_sort(Some(SortKey::Name), false);
+    assert_eq!(names(&inventory_report_for(&inv, &q)), ["Bread", "Chips", "Cider", "Cola"]);
+
+    q.set_text(Some("CAN"));
+    assert_eq!(names(&inventory_report_for(&inv, &q)), ["Cider", "Cola"]);
+    q.set_text(None);
+    q.set_price_range(Some(8.0), Some(20.0));
+    q.set_quantity_range(Some(5), None);
+    assert_eq!(names(&inventory_report_for(&inv, &q)), ["Bread", "Cola"]);
+
+    let mut q = ListQuery::new();
+    q.set_page_size(Some(3));
+    q.set_page(2);
+    let report = inventory_report_for(&inv, &q);
+    assert_eq!(names(&report), ["Cider"]);
+    assert_eq!(report.paging(), Some((2, 2, 4)));
+    assert!(report.render(ReportFormat::Text).contains("Page 2 of 2 (4 rows)\n"));
+    // past the end shows the last page
+    q.set_page(9);
+    assert_eq!(inventory_report_for(&inv, &q).paging(), Some((2, 2, 4)));
+
+    tx.record_purchase("Cola", 24, 6.0, &mut inv).unwrap();
+    tx.record_purchase("Chips", 10, 4.0, &mut inv).unwrap();
+    tx.record_sale("Cola", 3, 10.0, &mut inv).unwrap();
+    tx.record_sale("Chips", 1, 7.5, &mut inv).unwrap();
+    tx.record_sale("Cola", 1, 10.0, &mut inv).unwrap();
+
+    let mut q = ListQuery::new();
+    q.set_sort(Some(SortKey::Quantity), false);
+    q.set_text(Some("cola"));
+    let sales = sales_report_for(&tx, &q);
+    let quantities: Vec<String> = sales.rows().iter().map(|r| r[3].display()).collect();
+    assert_eq!(quantities, ["1", "3"]);
+    // the sale keeps its number when sorted
+    assert_eq!(sales.rows()[0][0], Value::Integer(3));
+
+    let mut q = ListQuery::new();
+    q.set_price_range(None, Some(5.0));
+    let purchases = purchases_report_for(&tx, &q);
+    assert_eq!(purchases.rows().len(), 1);
+    assert_eq!(purchases.rows()[0][1], Value::text("Chips"));
+    assert_eq!(purchases.paging(), None);
+}
+
+#[test]
+fn stock_is_valued_at_cost_and_retail_now_and_on_a_past_date() {
+    use store_inventory_management_system::clock::{DAY, parse_date};
+    let (mut inv, mut tx) = cola_store(12);
+    inv.add_product("Chips", "Salted", 7.5, 5);
+    inv.add_product("Bread", "Whole grain", 18.0, 10);
+    inv.find_mut("Chips").unwrap().set_category("Snacks");
+    inv.find_mut("Cola").unwrap().set_category("Drinks");
+    inv.find_mut("Bread").unwrap().set_category("Drinks");
+
+    // bought on the 4th, sold on the 5th, a chips bag returned on the 6th
+    let day = parse_date("2024-03-04").unwrap();
+    tx.set_time(Some(day + 9 * 3600));
+    tx.record_purchase("Cola", 24, 6.0, &mut inv).unwrap();
+    tx.record_purchase("Chips", 10, 4.0, &mut inv).unwrap();
+    tx.set_time(Some(day + DAY + 14 * 3600));
+    tx.record_sale("Cola", 6, 10.0, &mut inv).unwrap();
+    let chips = tx.record_sale("Chips", 2, 7.5, &mut inv).unwrap().id();
+    tx.set_time(Some(day + 2 * DAY + 11 * 3600));
+    tx.record_return(chips, 1, &mut inv).unwrap();
+
+    let lines = valuation(&tx, &inv, None);
+    let cola = lines.iter().find(|l| l.product_name() == "Cola").unwrap();
+    assert_eq!(cola.quantity(), 30);
+    assert!((cola.cost_value() - 180.0).abs() < 1e-9);
+    assert!((cola.retail_value() - 300.0).abs() < 1e-9);
+    assert!((cola.margin() - 120.0).abs() < 1e-9);
+    let bread = lines.iter().find(|l| l.product_name() == "Bread").unwrap();
+    assert_eq!(bread.unit_cost(), None);
+
+    let report = valuation_report(&tx, &inv, None);
+    let subtotal = report.rows().iter().find(|r| r[0] == Value::text("Drinks") && r[1] == Value::text("Subtotal")).unwrap();
+    assert_eq!(subtotal[2], Value::Integer(40));
+    assert_eq!(subtotal[4], Value::Money(180.0));
+    assert_eq!(subtotal[6], Value::Money(480.0));
+    let total = |label: &str| report.totals().iter().find(|(l, _)| l == label).map(|(_, v)| v.clone());
+    assert_eq!(total("Total at cost"), Some(Value::Money(180.0 + 14.0 * 4.0)));
+    assert_eq!(total("Total at retail"), Some(Value::Money(480.0 + 14.0 * 7.5)));
+    assert_eq!(total("Potential gross margin"), Some(Value::Money(480.0 + 105.0 - 236.0)));
+    assert_eq!(total("Products without a cost (valued at 0)"), Some(Value::Integer(1)));
+    assert!(report_valuation(&tx, &inv, None).starts_with("Inventory valuation as of"));
+
+    // the day before nothing had been bought, sold or returned yet
+    let before = day - 1;
+    assert_eq!(stock_as_of(&tx, &inv, "Cola", before), 12);
+    assert_eq!(stock_as_of(&tx, &inv, "Chips", before), 5);
+    let past = valuation(&tx, &inv, Some(before));
+    assert!(past.iter().all(|l| l.unit_cost().is_none()));
+    let retail: f64 = past.iter().map(|l| l.retail_value()).sum();
+    assert!((retail - (120.0 + 37.5 + 180.0)).abs() < 1e-9);
+    // at the end of each day in between
+    let at_close = |n: u64| {
+        let at = day + n * DAY - 1;
+        (stock_as_of(&tx, &inv, "Cola", at), stock_as_of(&tx, &inv, "Chips", at))
+    };
+    assert_eq!([at_close(1), at_close(2), at_close(3)], [(36, 15), (30, 13), (30, 14)]);
+
+    // a later count that finds two cans missing is undone for earlier dates
+    tx.set_time(Some(day + 7 * DAY + 10 * 3600));
+    assert_eq!(tx.record_stock_count("Cola", 28, "damaged", &mut inv).unwrap(), -2);
+    assert_eq!(stock_as_of(&tx, &inv, "Cola", day + 7 * DAY), 30);
+    assert_eq!(stock_as_of(&tx, &inv, "Cola", day + 8 * DAY), 28);
+    let week_ago = valuation(&tx, &inv, Some(day + 7 * DAY));
+    assert_eq!(week_ago.iter().find(|l| l.product_name() == "Cola").unwrap().quantity(), 30);
+}
+
+#[test]
+fn fifo_costs_sales_from_the_oldest_stock() {
+    use store_inventory_management_system::clock::{DAY, parse_date};
+    let (mut inv, mut tx) = cola_store(0);
+    let day = parse_date("2024-03-04").unwrap();
+    tx.set_time(Some(day + 9 * 3600));
+    tx.record_purchase("Cola", 10, 5.0, &mut inv).unwrap();
+    tx.set_time(Some(day + DAY + 9 * 3600));
+    tx.record_purchase("Cola", 10, 8.0, &mut inv).unwrap();
+    assert_eq!(tx.costing_method(), CostingMethod::Average);
+    assert_eq!(tx.cost_of("Cola", 12, &inv), Some(78.0));
+
+    tx.set_costing_method(CostingMethod::Fifo);
+    assert_eq!(tx.unit_cost("Cola", &inv), Some(6.5));
+    assert_eq!(tx.cost_of("Cola", 12, &inv), Some(66.0));
+    tx.set_time(Some(day + 2 * DAY + 9 * 3600));
+    tx.record_sale("Cola", 12, 10.0, &mut inv).unwrap();
+    assert_eq!(tx.journal().balance(AccountRole::Cogs), 66.0);
+    assert_eq!(tx.journal().balance(AccountRole::Inventory), 130.0 - 66.0);
+
+    // what is left is the second delivery; earlier dates see their own stock
+    assert_eq!(tx.unit_cost("Cola", &inv), Some(8.0));
+    assert_eq!(tx.unit_cost_at("Cola", &inv, day + DAY - 1), Some(5.0));
+    assert_eq!(tx.unit_cost_at("Cola", &inv, day + 2 * DAY - 1), Some(6.5));
+    let cola = |tx: &Transactions, at| valuation(tx, &inv, at).into_iter().find(|l| l.product_name() == "Cola").unwrap();
+    assert_eq!(cola(&tx, None).cost_value(), 64.0);
+    assert_eq!(cola(&tx, Some(day + DAY - 1)).cost_value(), 50.0);
+    tx.set_costing_method(CostingMethod::Average);
+    assert_eq!(cola(&tx, None).cost_value(), 52.0);
+
+    // a return takes back what the sale posted, whatever stock costs now
+    tx.set_costing_method(CostingMethod::Fifo);
+    tx.set_time(Some(day + 3 * DAY + 9 * 3600));
+    tx.record_purchase("Cola", 10, 11.0, &mut inv).unwrap();
+    let sale = tx.get_sales()[0].id();
+    assert_eq!(tx.get_sales()[0].cost(), Some(66.0));
+    assert_eq!(tx.record_return(sale, 6, &mut inv).unwrap().cost(), 33.0);
+    assert_eq!(tx.journal().balance(AccountRole::Cogs), 33.0);
+    assert!(report_margins(&tx).contains("Cost of goods sold: 33.00\n"));
+
+    // two lines of one product take successive layers, not the same one
+    let mut cart = Cart::new();
+    cart.add_line("Cola", 3, 10.0);
+    cart.add_line("Cola", 3, 10.0);
+    cart.add_tender(Tender::Cash(60.0));
+    tx.checkout(&cart, &mut inv).unwrap();
+    // with the return back on the shelf, the oldest units are four at 5, then those at 8
+    let costs: Vec<Option<f64>> = tx.get_sales()[1..].iter().map(|s| s.cost()).collect();
+    assert_eq!(costs, [Some(3.0 * 5.0), Some(5.0 + 2.0 * 8.0)]);
+}
+
+#[test]
+fn slow_movers_and_dead_stock_are_found_from_sales_history() {
+    use store_inventory_management_system::clock::{DAY, parse_date};
+    let (mut inv, mut tx) = cola_store(12);
+    inv.add_product("Chips", "Salted", 7.5, 5);
+    inv.add_product("Bread", "Whole grain", 18.0, 10);
+    inv.add_product("Water", "500ml", 2.0, 0);
+
+    // a month of trading from 1 March: everything bought on the first day,
+    // cola sold on the 6th, water sold and all brought back, chips on the 21st
+    let start = parse_date("2024-03-01").unwrap();
+    tx.set_time(Some(start + 9 * 3600));
+    tx.record_purchase("Cola", 24, 6.0, &mut inv).unwrap();
+    tx.record_purchase("Chips", 10, 4.0, &mut inv).unwrap();
+    tx.record_purchase("Bread", 10, 12.0, &mut inv).unwrap();
+    tx.record_purchase("Water", 5, 1.0, &mut inv).unwrap();
+    tx.set_time(Some(start + 5 * DAY + 12 * 3600));
+    tx.record_sale("Cola", 6, 10.0, &mut inv).unwrap();
+    tx.set_time(Some(start + 10 * DAY + 12 * 3600));
+    let water = tx.record_sale("Water", 2, 2.0, &mut inv).unwrap().id();
+    tx.set_time(Some(start + 11 * DAY + 12 * 3600));
+    tx.record_return(water, 2, &mut inv).unwrap();
+    tx.set_time(Some(start + 20 * DAY + 12 * 3600));
+    tx.record_sale("Chips", 3, 7.5, &mut inv).unwrap();
+
+    let now = start + 30 * DAY + 12 * 3600;
+    let rows = stock_movement(&tx, &inv, 30, now);
+    let find = |name: &str| -> &StockMovement { rows.iter().find(|m| m.product_name() == name).unwrap() };
+    let cola = find("Cola");
+    assert_eq!((cola.on_hand(), cola.sold()), (30, 6));
+    assert_eq!(cola.days_of_supply(), Some(150.0));
+    // the window opened after the delivery, with 36 on the shelf
+    assert!((cola.turnover().unwrap() - 6.0 / 33.0).abs() < 1e-9);
+    assert_eq!(cola.days_since_sale(now), Some(25));
+    let bread = find("Bread");
+    assert!(bread.is_dead());
+    assert_eq!(bread.last_sold(), None);
+    assert_eq!(bread.days_of_supply(), None);
+    assert!((bread.value() - 240.0).abs() < 1e-9);
+    assert!(cola.is_slow(130) && !find("Chips").is_slow(130));
+    // water sold, so it is not dead, but with every unit back it is slow
+    let water = find("Water");
+    assert_eq!((water.gross_sold(), water.sold()), (2, 0));
+    assert!(!water.is_dead() && water.is_slow(130));
+
+    let names = |rows: &[StockMovement]| -> Vec<String> { rows.iter().map(|m| m.product_name().to_string()).collect() };
+    let mut sorted = rows.clone();
+    sort_movement(&mut sorted, MovementSort::Value, now);
+    assert_eq!(names(&sorted), ["Bread", "Cola", "Chips", "Water"]);
+    sort_movement(&mut sorted, MovementSort::DaysSinceSale, now);
+    assert_eq!(names(&sorted), ["Bread", "Cola", "Water", "Chips"]);
+    sort_movement(&mut sorted, MovementSort::DaysOfSupply, now);
+    assert_eq!(names(&sorted), ["Bread", "Water", "Cola", "Chips"]);
+    sort_movement(&mut sorted, MovementSort::Turnover, now);
+    assert_eq!(names(&sorted), ["Bread", "Water", "Cola", "Chips"]);
+    sort_movement(&mut sorted, MovementSort::Name, now);
+    assert_eq!(names(&sorted), ["Bread", "Chips", "Cola", "Water"]);
+
+    let report = slow_movers_report(&tx, &inv, 30, 130, MovementSort::Value, false, now);
+    let status: Vec<String> = report.rows().iter().map(|r| r[8].display()).collect();
+    assert_eq!(status, ["Dead", "Slow", "Slow"]);
+    let total = |label: &str| report.totals().iter().find(|(l, _)| l == label).map(|(_, v)| v.clone());
+    assert_eq!(total("No sales in 30 days"), Some(Value::Integer(1)));
+    assert_eq!(total("Dead stock value"), Some(Value::Money(240.0)));
+    assert_eq!(total("Slow mover value (dead or over 130 days of supply)"), Some(Value::Money(425.0)));
+
+    // two months on with no further sales, everything is dead stock
+    let later = now + 60 * DAY;
+    let later_rows = stock_movement(&tx, &inv, 30, later);
+    assert!(later_rows.iter().all(|m| m.is_dead()));
+    assert_eq!(later_rows[0].days_since_sale(later), Some(85));
+    let text = report_slow_movers(&tx, &inv, 30, 130, MovementSort::DaysSinceSale, later);
+    assert!(text.contains("No sales in 30 days: 4\n"));
+}
+
+#[test]
+fn free_sales_need_no_tender() {
+    let (mut inv, mut tx) = cola_store(10);
+    tx.promotions_mut()
+        .add(Promotion::new("Free cola", PromotionKind::PercentOff(100.0), PromotionTarget::Product("Cola".into())))
+        .unwrap();
+    let sale = tx.record_sale("Cola", 2, 10.0, &mut inv).expect("a free sale should go through");
+    assert_eq!(sale.total_price(), 0.0);
+    assert_eq!(inv.find("Cola").unwrap().quantity(), 8);
+    assert!(tx.get_orders()[0].payments().is_empty());
+}
+
+#[test]
+fn renaming_a_product_keeps_what_refers_to_it() {
+    let (mut inv, mut tx) = cola_store(5);
+    inv.add_product("Chips", "Salted", 7.5, 0);
+    inv.reserve("Cola", "phone: 555-0101", 3, u64::MAX).unwrap();
+    tx.record_backorder(1, "Chips", 2, 7.5, &inv).unwrap();
+
+    assert!(tx.edit_product("Cola", Some("Chips"), None, None, None, &mut inv).is_err(), "name taken");
+    tx.edit_product("Cola", Some("Cola Zero"), None, None, None, &mut inv).unwrap();
+    tx.edit_product("Chips", Some("Crisps"), None, None, None, &mut inv).unwrap();
+    assert_eq!(inv.reserved("Cola Zero"), 3);
+    assert_eq!(inv.available("Cola Zero"), Some(2));
+    assert_eq!(tx.get_backorders()[0].product_name(), "Crisps");
+    tx.record_purchase("Crisps", 2, 4.0, &mut inv).unwrap();
+    assert_eq!(tx.ready_backorders().len(), 1);
+
+    // history, list prices and promotions follow a rename too
+    let day = 1_700_000_000;
+    tx.set_time(Some(day));
+    let mut staff = PriceList::new("Staff");
+    staff.set_price("Crisps", 6.0).unwrap();
+    tx.price_lists_mut().add(staff).unwrap();
+    let promo = Promotion::new("Crisps deal", PromotionKind::PercentOff(10.0), PromotionTarget::Product("Crisps".into()));
+    tx.promotions_mut().add(promo).unwrap();
+    tx.set_time(Some(day + 3600));
+    tx.record_sale("Cola Zero", 1, 10.0, &mut inv).unwrap();
+    tx.set_time(Some(day + 7200));
+    tx.edit_product("Crisps", Some("Potato Crisps"), None, None, None, &mut inv).unwrap();
+    tx.edit_product("Cola Zero", Some("Cola Light"), None, None, None, &mut inv).unwrap();
+    assert_eq!(tx.price_lists().find("Staff").unwrap().prices(), &[("Potato Crisps".to_string(), 6.0)]);
+    assert_eq!(tx.promotions().all()[0].target(), &PromotionTarget::Product("Potato Crisps".into()));
+    assert_eq!(tx.get_purchases()[0].product_name(), "Potato Crisps");
+    assert_eq!(tx.get_backorders()[0].product_name(), "Potato Crisps");
+    assert_eq!(tx.get_sales()[0].product_name(), "Cola Light");
+    assert_eq!(stock_as_of(&tx, &inv, "Cola Light", day), 5);
+    assert_eq!(stock_as_of(&tx, &inv, "Cola Light", day + 3600), 4);
+
+    // variants are named after their parent and follow it
+    inv.add_parent("Tee", "T-shirt", &["size"]).unwrap();
+    inv.add_variant("Tee", "TEE-M", &[("size", "M")], 15.0, 4).unwrap();
+    inv.rename_product("Tee", "Shirt").unwrap();
+    let variant = inv.find_by_sku("TEE-M").unwrap();
+    assert_eq!((variant.name(), variant.parent()), ("Shirt M", Some("Shirt")));
+    assert_eq!(inv.on_hand("Shirt"), Some(4));
+}
+
+#[test]
+fn undiscounted_sales_show_no_discount() {
+    let (mut inv, mut tx) = cola_store(5);
+    let sale = tx.record_sale("Cola", 1, 10.0, &mut inv).unwrap();
+    assert!(sale.discount().is_sign_positive());
+    let report = sales_report(&tx);
+    assert_eq!(report.rows()[0][5].display(), "0.00");
+    assert!(!report_sales(&tx).contains("-0.00"));
+}